@@ -39,6 +39,7 @@ struct ResolvedNode {
     id: usize,
     family: rendy_core::hal::queue::QueueFamilyId,
     queues: Range<usize>,
+    async_family: Option<(rendy_core::hal::queue::QueueFamilyId, Range<usize>)>,
     rev_deps: Vec<usize>,
     buffers: Vec<(usize, State<Buffer>)>,
     images: Vec<(usize, State<Image>)>,
@@ -50,6 +51,7 @@ impl Default for ResolvedNode {
             id: 0,
             family: rendy_core::hal::queue::QueueFamilyId(0),
             queues: 0..0,
+            async_family: None,
             rev_deps: Vec::new(),
             buffers: Vec::new(),
             images: Vec::new(),
@@ -222,16 +224,18 @@ where
 
     for node in nodes {
         let family = node.family;
-        if !family_full.contains_key(&family) {
-            let count = max_queues(family);
-            assert!(count > 0, "Cannot create a family with 0 max queues.");
-            for i in 0..count {
-                queues.forward(QueueId::new(family, i));
+        for family in Some(family).into_iter().chain(node.async_family) {
+            if !family_full.contains_key(&family) {
+                let count = max_queues(family);
+                assert!(count > 0, "Cannot create a family with 0 max queues.");
+                for i in 0..count {
+                    queues.forward(QueueId::new(family, i));
+                }
+
+                let full_range = queues.forward(QueueId::new(family, 0))
+                    ..queues.forward(QueueId::new(family, count - 1)) + 1;
+                family_full.insert(family, full_range);
             }
-
-            let full_range = queues.forward(QueueId::new(family, 0))
-                ..queues.forward(QueueId::new(family, count - 1)) + 1;
-            family_full.insert(family, full_range);
         }
 
         let id = node_ids.forward(node.id);
@@ -249,6 +253,10 @@ where
         reified_nodes[id].id = id;
         reified_nodes[id].family = node.family;
         reified_nodes[id].queues = family_full[&family].clone();
+        reified_nodes[id].async_family = node
+            .async_family
+            .filter(|&async_family| async_family != family)
+            .map(|async_family| (async_family, family_full[&async_family].clone()));
         reified_nodes[id].buffers = node
             .buffers
             .into_iter()
@@ -293,6 +301,39 @@ fn fitness(
     images: &mut Vec<ChainData<Image>>,
     buffers: &mut Vec<ChainData<Buffer>>,
     schedule: &mut Vec<QueueData>,
+) -> (Fitness, usize) {
+    let primary = family_fitness(
+        node,
+        node.family,
+        node.queues.clone(),
+        images,
+        buffers,
+        schedule,
+    );
+
+    match &node.async_family {
+        Some((family, queues)) => {
+            let secondary =
+                family_fitness(node, *family, queues.clone(), images, buffers, schedule);
+
+            // Node goes to async family only if it can start earlier there.
+            if secondary.0.wait_factor < primary.0.wait_factor {
+                secondary
+            } else {
+                primary
+            }
+        }
+        None => primary,
+    }
+}
+
+fn family_fitness(
+    node: &ResolvedNode,
+    family: rendy_core::hal::queue::QueueFamilyId,
+    queues: Range<usize>,
+    images: &mut Vec<ChainData<Image>>,
+    buffers: &mut Vec<ChainData<Buffer>>,
+    schedule: &mut Vec<QueueData>,
 ) -> (Fitness, usize) {
     let mut transfers = 0;
     let mut wait_factor_from_chains = 0;
@@ -302,7 +343,7 @@ fn fitness(
         let chain = &buffers[id];
         if chain
             .current_family
-            .map_or(false, |family| family != node.family)
+            .map_or(false, |current| current != family)
         {
             transfers += 1;
         }
@@ -312,7 +353,7 @@ fn fitness(
         let chain = &images[id];
         if chain
             .current_family
-            .map_or(false, |family| family != node.family)
+            .map_or(false, |current| current != family)
         {
             transfers += 1;
         }
@@ -320,9 +361,7 @@ fn fitness(
     }
 
     // Find best queue for node.
-    let (wait_factor_from_queue, queue) = node
-        .queues
-        .clone()
+    let (wait_factor_from_queue, queue) = queues
         .map(|index| (schedule[index].wait_factor, index))
         .min()
         .unwrap();
@@ -354,10 +393,11 @@ fn schedule_node<'a>(
         .add_submission(node.id, wait_factor, submitted, Unsynchronized);
     let submission = queue_data.queue.submission_mut(sid).unwrap();
 
+    let family = nodes.queues[queue].family();
     for &(id, state) in &node.buffers {
         add_to_chain(
             nodes.buffers[id],
-            node.family,
+            family,
             &mut buffers[id],
             sid,
             submission,
@@ -368,7 +408,7 @@ fn schedule_node<'a>(
     for &(id, state) in &node.images {
         add_to_chain(
             nodes.images[id],
-            node.family,
+            family,
             &mut images[id],
             sid,
             submission,
@@ -36,6 +36,11 @@ pub struct Node {
     /// Family required to execute the node.
     pub family: rendy_core::hal::queue::QueueFamilyId,
 
+    /// Family that can execute the node asynchronously to nodes on `family`.
+    /// Node is scheduled there if that lets it start earlier
+    /// than it would on `family`, even at the cost of resource transfers.
+    pub async_family: Option<rendy_core::hal::queue::QueueFamilyId>,
+
     /// Dependencies of the node.
     /// Those are indices of other nodes in array.
     pub dependencies: Vec<usize>,
@@ -53,6 +58,11 @@ impl Node {
         self.family
     }
 
+    /// Get family on which this node can be executed asynchronously.
+    pub fn async_family(&self) -> Option<rendy_core::hal::queue::QueueFamilyId> {
+        self.async_family
+    }
+
     /// Get indices of nodes this node depends on.
     pub fn dependencies(&self) -> &[usize] {
         &self.dependencies
@@ -50,14 +50,18 @@ impl<S> Signal<S> {
 /// Semaphore wait info.
 /// There must be paired signal.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct Wait<S>(S, rendy_core::hal::pso::PipelineStage);
+pub struct Wait<S>(S, rendy_core::hal::pso::PipelineStage, bool);
 
 impl<S> Wait<S> {
     /// Create waiting for specified point.
     /// At this point `Signal` must be created as well.
     /// `id` and `point` combination must be unique.
-    fn new(semaphore: S, stages: rendy_core::hal::pso::PipelineStage) -> Self {
-        Wait(semaphore, stages)
+    fn new(
+        semaphore: S,
+        stages: rendy_core::hal::pso::PipelineStage,
+        previous_frame: bool,
+    ) -> Self {
+        Wait(semaphore, stages, previous_frame)
     }
 
     /// Get semaphore of the `Wait`.
@@ -69,6 +73,13 @@ impl<S> Wait<S> {
     pub fn stage(&self) -> rendy_core::hal::pso::PipelineStage {
         self.1
     }
+
+    /// Check if semaphore is signaled by the previous frame.
+    /// Such semaphore is signaled after the wait within the same frame,
+    /// so there is nothing to wait for in the first frame.
+    pub fn previous_frame(&self) -> bool {
+        self.2
+    }
 }
 
 /// Pipeline barrier info.
@@ -235,7 +246,9 @@ impl<S, W> SyncData<S, W> {
             wait: self
                 .wait
                 .into_iter()
-                .map(|Wait(semaphore, stage)| Wait(f(semaphore), stage))
+                .map(|Wait(semaphore, stage, previous_frame)| {
+                    Wait(f(semaphore), stage, previous_frame)
+                })
                 .collect(),
             acquire: Guard {
                 buffers: self.acquire.buffers,
@@ -356,15 +369,33 @@ fn generate_semaphore_pair<R: Resource>(
     id: Id,
     link: &Link<R>,
     range: Range<SubmissionId>,
+    previous_frame: bool,
 ) {
     if range.start.queue() != range.end.queue() {
         let semaphore = Semaphore::new(id, range.clone());
         sync.get_sync(range.start)
             .signal
             .push(Signal::new(semaphore.clone()));
-        sync.get_sync(range.end)
-            .wait
-            .push(Wait::new(semaphore, link.queue(range.end.queue()).stages));
+        sync.get_sync(range.end).wait.push(Wait::new(
+            semaphore,
+            link.queue(range.end.queue()).stages,
+            previous_frame,
+        ));
+    }
+}
+
+/// Make queues of the link that only reads the resource
+/// wait for the barrier recorded in the link's earliest submission.
+/// Writing links are used by a single queue.
+fn follow_acquire<R: Resource>(
+    sync: &mut SyncTemp,
+    id: Id,
+    link: &Link<R>,
+    acquire_sid: SubmissionId,
+) {
+    for (queue_id, queue) in link.queues() {
+        let head = SubmissionId::new(queue_id, queue.first);
+        generate_semaphore_pair(sync, id, link, acquire_sid..head, false);
     }
 }
 
@@ -385,8 +416,11 @@ where
                 .and_then(|first| chain.links().last().map(move |last| (last, first))),
         );
 
-    for (prev_link, link) in pairs {
+    for (index, (prev_link, link)) in pairs.enumerate() {
         log::trace!("Sync {:#?}:{:#?}", prev_link.access(), link.access());
+        // Last link is followed by the first link of the next frame.
+        let wraparound = index + 1 == chain.links().len();
+
         if prev_link.family() == link.family() {
             // Prefer to generate barriers on the acquire side, if possible.
             if prev_link.access().exclusive() && !link.access().exclusive() {
@@ -401,7 +435,7 @@ where
                 // Generate semaphores between queues in the previous link and the current one.
                 for (queue_id, queue) in link.queues() {
                     let head = SubmissionId::new(queue_id, queue.first);
-                    generate_semaphore_pair(sync, uid, link, signal_sid..head);
+                    generate_semaphore_pair(sync, uid, link, signal_sid..head, wraparound);
                 }
             } else {
                 let wait_sid = earliest(link, schedule);
@@ -409,7 +443,7 @@ where
                 // Generate semaphores between queues in the previous link and the current one.
                 for (queue_id, queue) in prev_link.queues() {
                     let tail = SubmissionId::new(queue_id, queue.last);
-                    generate_semaphore_pair(sync, uid, link, tail..wait_sid);
+                    generate_semaphore_pair(sync, uid, link, tail..wait_sid, wraparound);
                 }
 
                 // Generate barrier in next link's first submission.
@@ -418,20 +452,21 @@ where
                     .pick()
                     .insert(id, Barrier::new(prev_link.state()..link.state()));
 
-                if !link.access().exclusive() {
-                    unimplemented!("This case is unimplemented");
-                }
+                // Other queues of the link must wait for the barrier.
+                follow_acquire(sync, uid, link, wait_sid);
             }
         } else {
             let signal_sid = latest(prev_link, schedule);
             let wait_sid = earliest(link, schedule);
 
-            if !prev_link.access().exclusive() {
-                unimplemented!("This case is unimplemented");
+            // Release must happen after all queues of the previous link are done.
+            for (queue_id, queue) in prev_link.queues() {
+                let tail = SubmissionId::new(queue_id, queue.last);
+                generate_semaphore_pair(sync, uid, prev_link, tail..signal_sid, false);
             }
 
             // Generate a semaphore between the signal and wait sides of the transfer.
-            generate_semaphore_pair(sync, uid, link, signal_sid..wait_sid);
+            generate_semaphore_pair(sync, uid, link, signal_sid..wait_sid, wraparound);
 
             // Generate barriers to transfer the resource to another family.
             sync.get_sync(signal_sid).release.pick::<R>().insert(
@@ -451,9 +486,8 @@ where
                 ),
             );
 
-            if !link.access().exclusive() {
-                unimplemented!("This case is unimplemented");
-            }
+            // Other queues of the link must wait for the acquire.
+            follow_acquire(sync, uid, link, wait_sid);
         }
     }
 }
@@ -469,6 +503,10 @@ fn optimize_submission(
             .wait
             .sort_unstable_by_key(|wait| (wait.stage(), wait.semaphore().points.end.index()));
         sync_data.wait.retain(|wait| {
+            if wait.previous_frame() {
+                // Says nothing about submissions of the current frame.
+                return true;
+            }
             let start = wait.semaphore().points.start;
             if let Some(synched_to) = found.get_mut(&start.queue()) {
                 if *synched_to >= start.index() {
@@ -505,3 +543,211 @@ fn optimize<S>(schedule: &Schedule<S>, sync: &mut SyncTemp) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{collect::collect, node::Node},
+        rendy_core::hal::{buffer, pso::PipelineStage, queue::QueueFamilyId},
+    };
+
+    type Synced = Schedule<SyncData<usize, usize>>;
+
+    fn node(
+        id: usize,
+        family: usize,
+        async_family: Option<usize>,
+        dependencies: Vec<usize>,
+        buffers: Vec<(usize, buffer::Access)>,
+    ) -> Node {
+        Node {
+            id,
+            family: QueueFamilyId(family),
+            async_family: async_family.map(QueueFamilyId),
+            dependencies,
+            buffers: buffers
+                .into_iter()
+                .map(|(id, access)| {
+                    (
+                        Id(id),
+                        State {
+                            access,
+                            layout: (),
+                            stages: PipelineStage::COMPUTE_SHADER,
+                            usage: buffer::Usage::STORAGE,
+                        },
+                    )
+                })
+                .collect(),
+            images: HashMap::new(),
+        }
+    }
+
+    /// Schedule nodes and find synchronization.
+    /// Signal and wait of each semaphore get the same number.
+    fn schedule(nodes: Vec<Node>, queues: &[usize]) -> Synced {
+        let chains = collect(nodes, |family| queues[family.0]);
+        let mut next = 0;
+        sync(&chains, || {
+            next += 1;
+            (next, next)
+        })
+    }
+
+    fn submission(schedule: &Synced, node: usize) -> &SyncData<usize, usize> {
+        schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|submission| submission.node() == node)
+            .unwrap()
+            .sync()
+    }
+
+    fn queue(schedule: &Synced, node: usize) -> QueueId {
+        schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .find(|queue| queue.iter().any(|submission| submission.node() == node))
+            .unwrap()
+            .id()
+    }
+
+    /// Semaphores signaled by `from` and waited by `to`,
+    /// with flag telling if the wait is for the previous frame.
+    fn semaphores(schedule: &Synced, from: usize, to: usize) -> Vec<bool> {
+        let signals = &submission(schedule, from).signal;
+        submission(schedule, to)
+            .wait
+            .iter()
+            .filter(|wait| {
+                signals
+                    .iter()
+                    .any(|signal| signal.semaphore() == wait.semaphore())
+            })
+            .map(|wait| wait.previous_frame())
+            .collect()
+    }
+
+    fn families(barriers: &BufferBarriers, id: usize) -> Option<Range<QueueFamilyId>> {
+        barriers[&Id(id)].families.clone()
+    }
+
+    #[test]
+    fn test_async_compute_transfer() {
+        // Node 1 can start right away on the async family
+        // while the graphics queue is busy with node 0.
+        let schedule = schedule(
+            vec![
+                node(0, 0, None, vec![], vec![(1, buffer::Access::SHADER_WRITE)]),
+                node(
+                    1,
+                    0,
+                    Some(1),
+                    vec![],
+                    vec![(0, buffer::Access::SHADER_WRITE)],
+                ),
+                node(2, 0, None, vec![1], vec![(0, buffer::Access::SHADER_READ)]),
+            ],
+            &[1, 1],
+        );
+
+        assert_eq!(queue(&schedule, 0).family(), QueueFamilyId(0));
+        assert_eq!(queue(&schedule, 1).family(), QueueFamilyId(1));
+        assert_eq!(queue(&schedule, 2).family(), QueueFamilyId(0));
+
+        // Ownership is transferred to the graphics family after node 1
+        let transfer = Some(QueueFamilyId(1)..QueueFamilyId(0));
+        assert_eq!(
+            families(&submission(&schedule, 1).release.buffers, 0),
+            transfer
+        );
+        assert_eq!(
+            families(&submission(&schedule, 2).acquire.buffers, 0),
+            transfer
+        );
+        assert_eq!(semaphores(&schedule, 1, 2), vec![false]);
+
+        // and back to the async family before node 1 of the next frame.
+        let transfer = Some(QueueFamilyId(0)..QueueFamilyId(1));
+        assert_eq!(
+            families(&submission(&schedule, 2).release.buffers, 0),
+            transfer
+        );
+        assert_eq!(
+            families(&submission(&schedule, 1).acquire.buffers, 0),
+            transfer
+        );
+        assert_eq!(semaphores(&schedule, 2, 1), vec![true]);
+
+        // Node 0 doesn't share resources with others.
+        assert!(submission(&schedule, 0).wait.is_empty());
+        assert!(submission(&schedule, 0).signal.is_empty());
+    }
+
+    #[test]
+    fn test_shared_read_follows_acquire() {
+        // Nodes 1 and 2 read buffer on two queues of the graphics family
+        // after node 0 wrote it on the compute family.
+        let schedule = schedule(
+            vec![
+                node(0, 1, None, vec![], vec![(0, buffer::Access::SHADER_WRITE)]),
+                node(1, 0, None, vec![0], vec![(0, buffer::Access::SHADER_READ)]),
+                node(2, 0, None, vec![0], vec![(0, buffer::Access::SHADER_READ)]),
+            ],
+            &[2, 1],
+        );
+
+        assert_ne!(queue(&schedule, 1), queue(&schedule, 2));
+
+        // Barrier is recorded once, by the reader submitted first.
+        let transfer = Some(QueueFamilyId(1)..QueueFamilyId(0));
+        assert_eq!(
+            families(&submission(&schedule, 1).acquire.buffers, 0),
+            transfer
+        );
+        assert!(submission(&schedule, 2).acquire.buffers.is_empty());
+        assert_eq!(semaphores(&schedule, 0, 1), vec![false]);
+        assert!(semaphores(&schedule, 0, 2).is_empty());
+
+        // The other reader waits for the barrier.
+        // The same semaphore tells the release after reads that node 1 is done.
+        assert_eq!(semaphores(&schedule, 1, 2), vec![false]);
+        let transfer = Some(QueueFamilyId(0)..QueueFamilyId(1));
+        assert_eq!(
+            families(&submission(&schedule, 2).release.buffers, 0),
+            transfer
+        );
+        assert_eq!(semaphores(&schedule, 2, 0), vec![true]);
+    }
+
+    #[test]
+    fn test_wraparound_waits_previous_frame() {
+        // Node 0 reads what node 1 wrote in the previous frame,
+        // node 1 overwrites buffer after node 0 read it.
+        let schedule = schedule(
+            vec![
+                node(0, 0, None, vec![], vec![(0, buffer::Access::SHADER_READ)]),
+                node(1, 0, None, vec![0], vec![(0, buffer::Access::SHADER_WRITE)]),
+            ],
+            &[2],
+        );
+
+        assert_ne!(queue(&schedule, 0), queue(&schedule, 1));
+
+        // Write after read within the frame.
+        assert!(submission(&schedule, 1)
+            .acquire
+            .buffers
+            .contains_key(&Id(0)));
+        assert_eq!(semaphores(&schedule, 0, 1), vec![false]);
+
+        // Read after write of the previous frame.
+        assert!(submission(&schedule, 1)
+            .release
+            .buffers
+            .contains_key(&Id(0)));
+        assert_eq!(semaphores(&schedule, 1, 0), vec![true]);
+    }
+}
@@ -0,0 +1,201 @@
+use {
+    crate::{
+        command::QueueId,
+        resource::{Buffer, Handle, Image},
+        upload::{check_image_upload, BufferState, ImageState, ImageStateOrLayout},
+    },
+    rendy_core::hal::{buffer, image},
+};
+
+/// Buffer update recorded in `UploadBatch`.
+#[derive(Debug)]
+pub(crate) struct BatchBufferUpload<'a, B: rendy_core::hal::Backend> {
+    pub(crate) buffer: &'a Buffer<B>,
+    pub(crate) offset: u64,
+    pub(crate) content: &'a [u8],
+    pub(crate) staging_offset: u64,
+    pub(crate) last: Option<BufferState>,
+    pub(crate) next: BufferState,
+}
+
+/// Image update recorded in `UploadBatch`.
+#[derive(Debug)]
+pub(crate) struct BatchImageUpload<'a, B: rendy_core::hal::Backend> {
+    pub(crate) image: Handle<Image<B>>,
+    pub(crate) data_width: u32,
+    pub(crate) data_height: u32,
+    pub(crate) image_layers: image::SubresourceLayers,
+    pub(crate) image_offset: image::Offset,
+    pub(crate) image_extent: image::Extent,
+    pub(crate) content: &'a [u8],
+    pub(crate) staging_offset: u64,
+    pub(crate) last: ImageStateOrLayout,
+    pub(crate) next: ImageState,
+}
+
+/// Batch of buffer and image updates submitted at once.
+///
+/// Content of all updates is coalesced into single staging buffer.
+/// Copies and barriers are recorded into single command buffer
+/// that is submitted with [`Factory::submit_upload_batch`],
+/// and all updates share single `UploadToken`.
+///
+/// [`Factory::submit_upload_batch`]: struct.Factory.html#method.submit_upload_batch
+#[derive(Debug)]
+pub struct UploadBatch<'a, B: rendy_core::hal::Backend> {
+    queue: QueueId,
+    pub(crate) buffers: Vec<BatchBufferUpload<'a, B>>,
+    pub(crate) images: Vec<BatchImageUpload<'a, B>>,
+    staging_size: u64,
+}
+
+impl<'a, B> UploadBatch<'a, B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Create empty batch of updates to be submitted on the `queue`.
+    pub fn new(queue: QueueId) -> Self {
+        UploadBatch {
+            queue,
+            buffers: Vec::new(),
+            images: Vec::new(),
+            staging_size: 0,
+        }
+    }
+
+    /// Get queue the batch is submitted on.
+    pub fn queue(&self) -> QueueId {
+        self.queue
+    }
+
+    /// Check if batch has no updates.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty() && self.images.is_empty()
+    }
+
+    /// Get size of the staging buffer required for content of all updates.
+    pub fn staging_size(&self) -> u64 {
+        self.staging_size
+    }
+
+    /// Get content of all updates with offsets in the staging buffer.
+    pub(crate) fn contents(&self) -> impl Iterator<Item = (u64, &'a [u8])> + '_ {
+        self.buffers
+            .iter()
+            .map(|upload| (upload.staging_offset, upload.content))
+            .chain(
+                self.images
+                    .iter()
+                    .map(|upload| (upload.staging_offset, upload.content)),
+            )
+    }
+
+    /// Reserve range of staging buffer and return its offset.
+    fn allocate_staging(&mut self, size: u64, align: u64) -> u64 {
+        let offset = (self.staging_size + align - 1) / align * align;
+        self.staging_size = offset + size;
+        offset
+    }
+
+    /// Add buffer range update to the batch.
+    ///
+    /// `last` and `next` have the same meaning as in [`Factory::upload_buffer`].
+    /// `next` state must be on the queue of the batch.
+    ///
+    /// [`Factory::upload_buffer`]: struct.Factory.html#method.upload_buffer
+    pub fn add_buffer<T>(
+        &mut self,
+        buffer: &'a Buffer<B>,
+        offset: u64,
+        content: &'a [T],
+        last: Option<BufferState>,
+        next: BufferState,
+    ) -> &mut Self
+    where
+        T: 'static + Copy,
+    {
+        assert!(buffer.info().usage.contains(buffer::Usage::TRANSFER_DST));
+        assert_eq!(
+            next.queue, self.queue,
+            "Updates must be used on the queue of the batch"
+        );
+
+        let content = unsafe {
+            std::slice::from_raw_parts(
+                content.as_ptr() as *const u8,
+                content.len() * std::mem::size_of::<T>(),
+            )
+        };
+
+        // Buffer copies have no alignment requirements.
+        // Keep 4 bytes alignment for the image updates that follow.
+        let staging_offset = self.allocate_staging(content.len() as u64, 4);
+        self.buffers.push(BatchBufferUpload {
+            buffer,
+            offset,
+            content,
+            staging_offset,
+            last,
+            next,
+        });
+        self
+    }
+
+    /// Add image layers update to the batch.
+    ///
+    /// Arguments have the same meaning as in [`Factory::upload_image`].
+    /// `next` state must be on the queue of the batch.
+    ///
+    /// [`Factory::upload_image`]: struct.Factory.html#method.upload_image
+    pub fn add_image<T>(
+        &mut self,
+        image: Handle<Image<B>>,
+        data_width: u32,
+        data_height: u32,
+        image_layers: image::SubresourceLayers,
+        image_offset: image::Offset,
+        image_extent: image::Extent,
+        content: &'a [T],
+        last: impl Into<ImageStateOrLayout>,
+        next: ImageState,
+    ) -> &mut Self
+    where
+        T: 'static + Copy,
+    {
+        let content = unsafe {
+            std::slice::from_raw_parts(
+                content.as_ptr() as *const u8,
+                content.len() * std::mem::size_of::<T>(),
+            )
+        };
+
+        check_image_upload(&image, &image_layers, image_extent, content.len() as u64);
+        assert_eq!(
+            next.queue, self.queue,
+            "Updates must be used on the queue of the batch"
+        );
+
+        // Copy offset must be multiple of both texel size and 4.
+        let texel_size = (image.format().surface_desc().bits / 8) as u64;
+        let align = match texel_size % 4 {
+            0 => texel_size,
+            2 => texel_size * 2,
+            _ => texel_size * 4,
+        };
+
+        let staging_offset = self.allocate_staging(content.len() as u64, align);
+        self.images.push(BatchImageUpload {
+            image,
+            data_width,
+            data_height,
+            image_layers,
+            image_offset,
+            image_extent,
+            content,
+            staging_offset,
+            last: last.into(),
+            next,
+        });
+        self
+    }
+}
@@ -3,7 +3,7 @@ use std::cmp::min;
 use crate::{
     command::FamilyId,
     core::DeviceId,
-    memory::{DynamicConfig, HeapsConfig, LinearConfig},
+    memory::{CustomAllocator, DynamicConfig, HeapsConfig, LinearConfig},
 };
 
 /// Factory initialization config.
@@ -14,6 +14,7 @@ use crate::{
 /// [`BasicHeapsConfigure`] can be used as sane default.
 /// `queues` - [`QueuesConfigure`] implementation to configure device queues creation.
 /// [`OneGraphicsQueue`] can be used if only one graphics queue will satisfy requirements.
+/// [`GraphicsAndAsyncComputeQueues`] adds a compute queue for async compute.
 ///
 /// [`DeviceConfigure`]: trait.DevicesConfigure.html
 /// [`BasicDevicesConfigure`]: struct.BasicDevicesConfigure.html
@@ -21,6 +22,7 @@ use crate::{
 /// [`BasicHeapsConfigure`]: struct.BasicHeapsConfigure.html
 /// [`QueuesConfigure`]: trait.QueuesConfigure.html
 /// [`OneGraphicsQueue`]: struct.OneGraphicsQueue.html
+/// [`GraphicsAndAsyncComputeQueues`]: struct.GraphicsAndAsyncComputeQueues.html
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Config<D = BasicDevicesConfigure, H = BasicHeapsConfigure, Q = OneGraphicsQueue> {
@@ -91,6 +93,48 @@ unsafe impl QueuesConfigure for OneGraphicsQueue {
     }
 }
 
+/// QueuePicker that picks first graphics queue family
+/// and first family that supports compute but not graphics, if there is one.
+///
+/// Graph nodes that prefer async execution go to the second family
+/// when that lets them overlap with graphics work.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphicsAndAsyncComputeQueues;
+
+unsafe impl QueuesConfigure for GraphicsAndAsyncComputeQueues {
+    type Priorities = [f32; 1];
+    type Families = Vec<(FamilyId, [f32; 1])>;
+    fn configure(
+        &self,
+        device: DeviceId,
+        families: &[impl rendy_core::hal::queue::QueueFamily],
+    ) -> Vec<(FamilyId, [f32; 1])> {
+        let graphics = families
+            .iter()
+            .find(|f| f.queue_type().supports_graphics() && f.max_queues() > 0);
+        let compute = families.iter().find(|f| {
+            f.queue_type().supports_compute()
+                && !f.queue_type().supports_graphics()
+                && f.max_queues() > 0
+        });
+
+        graphics
+            .into_iter()
+            .chain(compute)
+            .map(|f| {
+                (
+                    FamilyId {
+                        device,
+                        index: f.id().0,
+                    },
+                    [1.0],
+                )
+            })
+            .collect()
+    }
+}
+
 /// Saved config for queues.
 /// This config can be loaded from config files
 /// in any format supported by serde ecosystem.
@@ -133,6 +177,18 @@ pub unsafe trait HeapsConfigure {
         &self,
         properties: &rendy_core::hal::adapter::MemoryProperties,
     ) -> (Self::Types, Self::Heaps);
+
+    /// Create custom allocators for the memory type emitted by [`configure`].
+    /// Usages that rate their kind above built-in allocators are served by them.
+    ///
+    /// [`configure`]: trait.HeapsConfigure.html#tymethod.configure
+    fn custom_allocators<B: rendy_core::hal::Backend>(
+        &self,
+        _memory_type: rendy_core::hal::MemoryTypeId,
+        _properties: rendy_core::hal::memory::Properties,
+    ) -> Vec<Box<dyn CustomAllocator<B>>> {
+        Vec::new()
+    }
 }
 
 /// Basic heaps config.
@@ -185,6 +241,7 @@ unsafe impl HeapsConfigure for BasicHeapsConfigure {
                             (properties.memory_heaps[mt.heap_index] / 128).next_power_of_two(),
                         ),
                     }),
+                    tlsf: None,
                 };
 
                 (mt.properties, mt.heap_index as u32, config)
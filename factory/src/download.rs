@@ -0,0 +1,543 @@
+use {
+    crate::{
+        barriers::Barriers,
+        command::{
+            CommandBuffer, CommandPool, Families, Family, IndividualReset, InitialState, OneShot,
+            PendingOnceState, PrimaryLevel, RecordingState, Submission, Transfer,
+        },
+        core::Device,
+        resource::{Buffer, Escape, Handle, Image},
+        upload::{BufferState, ImageState},
+    },
+    rendy_core::hal::device::{Device as _, OomOrDeviceLost, OutOfMemory},
+    std::{
+        collections::VecDeque,
+        iter::once,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Copy of device resource content into host visible staging buffer.
+/// Created by [`Factory::download_buffer`] and [`Factory::download_image`].
+///
+/// Copy operation is submitted to the device upon next
+/// [`Factory::flush_downloads`] or [`Factory::maintain`] call.
+/// Download becomes complete after [`Factory::cleanup`] finds it finished
+/// or after successful [`Factory::wait_download`].
+///
+/// [`Factory::download_buffer`]: struct.Factory.html#method.download_buffer
+/// [`Factory::download_image`]: struct.Factory.html#method.download_image
+/// [`Factory::flush_downloads`]: struct.Factory.html#method.flush_downloads
+/// [`Factory::maintain`]: struct.Factory.html#method.maintain
+/// [`Factory::cleanup`]: struct.Factory.html#method.cleanup
+/// [`Factory::wait_download`]: struct.Factory.html#method.wait_download
+#[derive(Debug)]
+pub struct PendingDownload<B: rendy_core::hal::Backend> {
+    shared: Arc<DownloadShared<B>>,
+}
+
+#[derive(Debug)]
+struct DownloadShared<B: rendy_core::hal::Backend> {
+    family: usize,
+
+    /// Serial number of the batch download was submitted with.
+    /// Zero until submitted.
+    batch: AtomicU64,
+    complete: AtomicBool,
+    size: u64,
+    staging: parking_lot::Mutex<Escape<Buffer<B>>>,
+}
+
+impl<B> PendingDownload<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Check if download is complete and data can be read.
+    pub fn is_complete(&self) -> bool {
+        self.shared.complete.load(Ordering::Acquire)
+    }
+
+    /// Get size of the downloaded data in bytes.
+    pub fn size(&self) -> u64 {
+        self.shared.size
+    }
+
+    /// Take staging buffer that contains downloaded data.
+    /// Data is tightly packed starting from offset 0.
+    ///
+    /// Returns `Err(self)` if download is not complete yet.
+    pub fn into_staging(self) -> Result<Escape<Buffer<B>>, Self> {
+        if !self.is_complete() {
+            return Err(self);
+        }
+
+        match Arc::try_unwrap(self.shared) {
+            Ok(shared) => Ok(shared.staging.into_inner()),
+            Err(shared) => Err(PendingDownload { shared }),
+        }
+    }
+
+    /// Copy downloaded data into vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if download is not complete.
+    pub(crate) fn read(
+        &self,
+        device: &Device<B>,
+    ) -> Result<Vec<u8>, rendy_core::hal::device::MapError> {
+        assert!(self.is_complete(), "Download is not complete");
+
+        let mut staging = self.shared.staging.lock();
+        let mut mapped = staging.map(device, 0..self.shared.size)?;
+        let data = unsafe {
+            // Device finished writing to the staging buffer.
+            mapped.read::<u8>(device, 0..self.shared.size)?.to_vec()
+        };
+        Ok(data)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Downloader<B: rendy_core::hal::Backend> {
+    family_downloads: Vec<Option<parking_lot::Mutex<FamilyDownloads<B>>>>,
+}
+
+impl<B> Downloader<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// # Safety
+    ///
+    /// `families` must belong to the `device`
+    pub(crate) unsafe fn new(
+        device: &Device<B>,
+        families: &Families<B>,
+    ) -> Result<Self, OutOfMemory> {
+        let mut family_downloads = Vec::new();
+        for family in families.as_slice() {
+            while family_downloads.len() <= family.id().index {
+                family_downloads.push(None);
+            }
+
+            family_downloads[family.id().index] = Some(parking_lot::Mutex::new(FamilyDownloads {
+                fences: Vec::new(),
+                pool: family
+                    .create_pool(device)
+                    .map(|pool| pool.with_capability().unwrap())?,
+                next: Vec::new(),
+                pending: VecDeque::new(),
+                command_buffers: Vec::new(),
+                barriers: Barriers::new(
+                    rendy_core::hal::pso::PipelineStage::TRANSFER,
+                    rendy_core::hal::buffer::Access::TRANSFER_READ
+                        | rendy_core::hal::buffer::Access::TRANSFER_WRITE,
+                    rendy_core::hal::image::Access::TRANSFER_READ,
+                ),
+                last_batch: 0,
+            }));
+        }
+
+        Ok(Downloader { family_downloads })
+    }
+
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Downloader`.
+    /// `buffer` and `staging` must belong to the `device`.
+    ///
+    pub(crate) unsafe fn download_buffer(
+        &self,
+        device: &Device<B>,
+        buffer: &Buffer<B>,
+        offset: u64,
+        staging: Escape<Buffer<B>>,
+        last: BufferState,
+        next: BufferState,
+    ) -> Result<PendingDownload<B>, OutOfMemory> {
+        debug_assert_eq!(last.queue, next.queue, "Checked by the factory");
+
+        let mut family_downloads = self.family_downloads[next.queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        family_downloads
+            .barriers
+            .add_buffer(last.stage, last.access, next.stage, next.access);
+        family_downloads.add_host_read();
+
+        let next_download = family_downloads.next_download(device, next.queue.index)?;
+        let mut encoder = next_download.command_buffer.encoder();
+        encoder.copy_buffer(
+            buffer.raw(),
+            staging.raw(),
+            Some(rendy_core::hal::command::BufferCopy {
+                src: offset,
+                dst: 0,
+                size: staging.size(),
+            }),
+        );
+
+        Ok(next_download.push(next.queue.family.index, staging))
+    }
+
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Downloader`.
+    /// `image` and `staging` must belong to the `device`.
+    ///
+    pub(crate) unsafe fn download_image(
+        &self,
+        device: &Device<B>,
+        image: Handle<Image<B>>,
+        image_layers: rendy_core::hal::image::SubresourceLayers,
+        image_offset: rendy_core::hal::image::Offset,
+        image_extent: rendy_core::hal::image::Extent,
+        staging: Escape<Buffer<B>>,
+        last: ImageState,
+        next: ImageState,
+    ) -> Result<PendingDownload<B>, OutOfMemory> {
+        use rendy_core::hal::image::Layout;
+
+        debug_assert_eq!(last.queue, next.queue, "Checked by the factory");
+
+        let mut family_downloads = self.family_downloads[next.queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        let image_range = rendy_core::hal::image::SubresourceRange {
+            aspects: image_layers.aspects,
+            levels: image_layers.level..image_layers.level + 1,
+            layers: image_layers.layers.clone(),
+        };
+
+        let target_layout = match last.layout {
+            Layout::General => Layout::General,
+            _ => Layout::TransferSrcOptimal,
+        };
+
+        family_downloads.barriers.add_image(
+            image.clone(),
+            image_range,
+            last.stage,
+            last.access,
+            last.layout,
+            target_layout,
+            next.stage,
+            next.access,
+            next.layout,
+        );
+        family_downloads.add_host_read();
+
+        let next_download = family_downloads.next_download(device, next.queue.index)?;
+        let mut encoder = next_download.command_buffer.encoder();
+        encoder.copy_image_to_buffer(
+            image.raw(),
+            target_layout,
+            staging.raw(),
+            Some(rendy_core::hal::command::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers,
+                image_offset,
+                image_extent,
+            }),
+        );
+
+        Ok(next_download.push(next.queue.family.index, staging))
+    }
+
+    /// Wait for the download to complete.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Downloader`.
+    ///
+    pub(crate) unsafe fn wait(
+        &self,
+        device: &Device<B>,
+        download: &PendingDownload<B>,
+        timeout_ns: u64,
+    ) -> Result<bool, OomOrDeviceLost> {
+        if download.is_complete() {
+            return Ok(true);
+        }
+
+        let mut family_downloads = self.family_downloads[download.shared.family]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        let batch = download.shared.batch.load(Ordering::Acquire);
+        let pending = family_downloads
+            .pending
+            .iter_mut()
+            .find(|pending| pending.batch == batch);
+
+        match pending {
+            Some(pending) => {
+                if device.wait_for_fence(&pending.fence, timeout_ns)? {
+                    pending.mark_complete();
+                }
+            }
+            None => {
+                // Either not submitted yet or completed before the lock was taken.
+            }
+        }
+
+        Ok(download.is_complete())
+    }
+
+    /// Cleanup finished downloads.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Downloader`.
+    ///
+    pub(crate) unsafe fn cleanup(&mut self, device: &Device<B>) {
+        for downloader in self.family_downloads.iter_mut() {
+            if let Some(downloader) = downloader {
+                downloader.get_mut().cleanup(device);
+            }
+        }
+    }
+
+    /// Flush new downloads.
+    ///
+    /// # Safety
+    ///
+    /// `families` must be the same that was used to create this `Downloader`.
+    ///
+    pub(crate) unsafe fn flush(&mut self, families: &mut Families<B>) {
+        for family in families.as_slice_mut() {
+            let downloader = self.family_downloads[family.id().index]
+                .as_mut()
+                .expect("Downloader must be initialized for all families");
+            downloader.get_mut().flush(family);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Downloader`.
+    /// `device` must be idle.
+    ///
+    pub(crate) unsafe fn dispose(&mut self, device: &Device<B>) {
+        self.family_downloads.drain(..).for_each(|fd| {
+            fd.map(|fd| fd.into_inner().dispose(device));
+        });
+    }
+}
+
+#[derive(Debug)]
+struct FamilyDownloads<B: rendy_core::hal::Backend> {
+    pool: CommandPool<B, Transfer, IndividualReset>,
+    command_buffers:
+        Vec<[CommandBuffer<B, Transfer, InitialState, PrimaryLevel, IndividualReset>; 2]>,
+    next: Vec<Option<NextDownloads<B>>>,
+    pending: VecDeque<PendingDownloads<B>>,
+    fences: Vec<B::Fence>,
+    barriers: Barriers<B>,
+    last_batch: u64,
+}
+
+#[derive(Debug)]
+struct PendingDownloads<B: rendy_core::hal::Backend> {
+    barrier_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
+    command_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
+    downloads: Vec<Arc<DownloadShared<B>>>,
+    fence: B::Fence,
+    batch: u64,
+}
+
+impl<B> PendingDownloads<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Mark downloads complete and release them,
+    /// so that their staging buffers can be taken.
+    fn mark_complete(&mut self) {
+        for download in self.downloads.drain(..) {
+            download.complete.store(true, Ordering::Release);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NextDownloads<B: rendy_core::hal::Backend> {
+    barrier_buffer:
+        CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
+    command_buffer:
+        CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
+    downloads: Vec<Arc<DownloadShared<B>>>,
+    fence: B::Fence,
+}
+
+impl<B> NextDownloads<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    fn push(&mut self, family: usize, staging: Escape<Buffer<B>>) -> PendingDownload<B> {
+        let shared = Arc::new(DownloadShared {
+            family,
+            batch: AtomicU64::new(0),
+            complete: AtomicBool::new(false),
+            size: staging.size(),
+            staging: parking_lot::Mutex::new(staging),
+        });
+        self.downloads.push(shared.clone());
+        PendingDownload { shared }
+    }
+}
+
+impl<B> FamilyDownloads<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Make transfer writes to staging buffers visible to the host.
+    fn add_host_read(&mut self) {
+        self.barriers.add_buffer(
+            rendy_core::hal::pso::PipelineStage::empty(),
+            rendy_core::hal::buffer::Access::empty(),
+            rendy_core::hal::pso::PipelineStage::HOST,
+            rendy_core::hal::buffer::Access::HOST_READ,
+        );
+    }
+
+    unsafe fn flush(&mut self, family: &mut Family<B>) {
+        for (queue, mut next) in self
+            .next
+            .drain(..)
+            .enumerate()
+            .filter_map(|(i, x)| x.map(|x| (i, x)))
+        {
+            let mut barriers_encoder = next.barrier_buffer.encoder();
+            let mut encoder = next.command_buffer.encoder();
+
+            self.barriers.encode_before(&mut barriers_encoder);
+            self.barriers.encode_after(&mut encoder);
+
+            let (barriers_submit, barrier_buffer) = next.barrier_buffer.finish().submit_once();
+            let (submit, command_buffer) = next.command_buffer.finish().submit_once();
+
+            family.queue_mut(queue).submit_raw_fence(
+                Some(Submission::new().submits(once(barriers_submit).chain(once(submit)))),
+                Some(&next.fence),
+            );
+
+            self.last_batch += 1;
+            for download in &next.downloads {
+                download.batch.store(self.last_batch, Ordering::Release);
+            }
+
+            self.pending.push_back(PendingDownloads {
+                barrier_buffer,
+                command_buffer,
+                downloads: next.downloads,
+                fence: next.fence,
+                batch: self.last_batch,
+            });
+        }
+    }
+
+    unsafe fn next_download(
+        &mut self,
+        device: &Device<B>,
+        queue: usize,
+    ) -> Result<&mut NextDownloads<B>, OutOfMemory> {
+        while self.next.len() <= queue {
+            self.next.push(None);
+        }
+
+        let pool = &mut self.pool;
+
+        match &mut self.next[queue] {
+            Some(next) => Ok(next),
+            slot @ None => {
+                let [buf_a, buf_b] = self.command_buffers.pop().unwrap_or_else(|| {
+                    let mut bufs = pool.allocate_buffers(2);
+                    [bufs.remove(1), bufs.remove(0)]
+                });
+                let fence = self
+                    .fences
+                    .pop()
+                    .map_or_else(|| device.create_fence(false), Ok)?;
+                *slot = Some(NextDownloads {
+                    barrier_buffer: buf_a.begin(OneShot, ()),
+                    command_buffer: buf_b.begin(OneShot, ()),
+                    downloads: Vec::new(),
+                    fence,
+                });
+
+                Ok(slot.as_mut().unwrap())
+            }
+        }
+    }
+
+    /// Cleanup finished downloads.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used with other methods of this instance.
+    ///
+    unsafe fn cleanup(&mut self, device: &Device<B>) {
+        while let Some(mut pending) = self.pending.pop_front() {
+            match device.get_fence_status(&pending.fence) {
+                Ok(false) => {
+                    self.pending.push_front(pending);
+                    return;
+                }
+                Err(rendy_core::hal::device::DeviceLost) => {
+                    panic!("Device lost error is not handled yet");
+                }
+                Ok(true) => {
+                    pending.mark_complete();
+                    device
+                        .reset_fence(&pending.fence)
+                        .expect("Can always reset signalled fence");
+                    self.fences.push(pending.fence);
+                    self.command_buffers.push([
+                        pending.command_buffer.mark_complete().reset(),
+                        pending.barrier_buffer.mark_complete().reset(),
+                    ]);
+                }
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Device must be idle.
+    ///
+    unsafe fn dispose(mut self, device: &Device<B>) {
+        let pool = &mut self.pool;
+        self.pending.drain(..).for_each(|mut pending| {
+            pending.mark_complete();
+            device.destroy_fence(pending.fence);
+            pool.free_buffers(Some(pending.command_buffer.mark_complete()));
+            pool.free_buffers(Some(pending.barrier_buffer.mark_complete()));
+        });
+
+        self.fences
+            .drain(..)
+            .for_each(|fence| device.destroy_fence(fence));
+        pool.free_buffers(
+            self.command_buffers
+                .drain(..)
+                .flat_map(|[a, b]| once(a).chain(once(b))),
+        );
+
+        pool.free_buffers(self.next.drain(..).filter_map(|n| n).flat_map(|next| {
+            device.destroy_fence(next.fence);
+            once(next.command_buffer).chain(once(next.barrier_buffer))
+        }));
+        drop(pool);
+        self.pool.dispose(device);
+    }
+}
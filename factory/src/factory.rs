@@ -1,15 +1,24 @@
 use {
     crate::{
+        batch::UploadBatch,
         blitter::Blitter,
         command::{
-            families_from_device, CommandPool, Families, Family, FamilyId, Fence, QueueType, Reset,
+            families_from_device, CommandPool, Encoder, Families, Family, FamilyId, Fence, QueueId,
+            QueueType, Reset, Supports, Transfer,
         },
         config::{Config, DevicesConfigure, HeapsConfigure, QueuesConfigure},
         core::{rendy_with_slow_safety_checks, Device, DeviceId, Instance, InstanceId},
         descriptor::DescriptorAllocator,
-        memory::{self, Heaps, MemoryUsage, TotalMemoryUtilization, Write},
+        download::{Downloader, PendingDownload},
+        memory::{
+            self, AllocationReport, Block, HeapBudget, Heaps, HeapsError, MemoryBlock,
+            MemoryHeapUtilization, MemoryUsage, TotalMemoryUtilization, Write,
+        },
         resource::*,
-        upload::{BufferState, ImageState, ImageStateOrLayout, Uploader},
+        upload::{
+            check_image_upload, BufferState, ImageState, ImageStateOrLayout, StagingBuffer,
+            StagingPoolConfig, UploadToken, Uploader,
+        },
         wsi::{Surface, SwapchainError, Target},
     },
     rendy_core::{
@@ -21,7 +30,10 @@ use {
                 OutOfMemory, WaitFor,
             },
             format, image,
-            pso::DescriptorSetLayoutBinding,
+            pso::{
+                self, ComputePipelineDesc, DescriptorSetLayoutBinding, GraphicsPipelineDesc,
+                ShaderStageFlags,
+            },
             window::{Extent2D, InitError, Surface as GfxSurface},
             Backend, Features, Instance as _, Limits,
         },
@@ -41,6 +53,11 @@ struct ResourceHub<B: Backend> {
     sets: ResourceTracker<DescriptorSet<B>>,
     samplers: ResourceTracker<Sampler<B>>,
     samplers_cache: parking_lot::RwLock<SamplerCache<B>>,
+    pipeline_layouts: ResourceTracker<PipelineLayout<B>>,
+    layouts_cache: parking_lot::RwLock<DescriptorSetLayoutCache<B>>,
+    pipeline_layouts_cache: parking_lot::RwLock<PipelineLayoutCache<B>>,
+    graphics_pipelines: ResourceTracker<GraphicsPipeline<B>>,
+    compute_pipelines: ResourceTracker<ComputePipeline<B>>,
 }
 
 impl<B> Default for ResourceHub<B>
@@ -56,6 +73,11 @@ where
             sets: ResourceTracker::default(),
             samplers: ResourceTracker::default(),
             samplers_cache: parking_lot::RwLock::new(SamplerCache::default()),
+            pipeline_layouts: ResourceTracker::default(),
+            layouts_cache: parking_lot::RwLock::new(DescriptorSetLayoutCache::default()),
+            pipeline_layouts_cache: parking_lot::RwLock::new(PipelineLayoutCache::default()),
+            graphics_pipelines: ResourceTracker::default(),
+            compute_pipelines: ResourceTracker::default(),
         }
     }
 }
@@ -72,6 +94,12 @@ where
         next: Epochs,
         complete: Epochs,
     ) {
+        self.graphics_pipelines
+            .cleanup(|p| p.dispose(device), &next, &complete);
+        self.compute_pipelines
+            .cleanup(|p| p.dispose(device), &next, &complete);
+        self.pipeline_layouts
+            .cleanup(|l| l.dispose(device), &next, &complete);
         self.sets
             .cleanup(|s| s.dispose(allocator), &next, &complete);
         self.views.cleanup(|v| v.dispose(device), &next, &complete);
@@ -92,6 +120,11 @@ where
         allocator: &mut DescriptorAllocator<B>,
     ) {
         drop(self.samplers_cache);
+        drop(self.pipeline_layouts_cache);
+        drop(self.layouts_cache);
+        self.graphics_pipelines.dispose(|p| p.dispose(device));
+        self.compute_pipelines.dispose(|p| p.dispose(device));
+        self.pipeline_layouts.dispose(|l| l.dispose(device));
         self.sets.dispose(|s| s.dispose(allocator));
         self.views.dispose(|v| v.dispose(device));
         self.layouts.dispose(|l| l.dispose(device));
@@ -132,6 +165,71 @@ impl std::error::Error for UploadError {
     }
 }
 
+/// Failure downloading a buffer or an image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadError {
+    /// Failed to create the staging buffer.
+    Create(BufferCreationError),
+    /// Failed to record the download.
+    Download(OutOfMemory),
+    /// Resource is used on different queues before and after the download.
+    /// Ownership transfer is not supported for downloads.
+    QueueMismatch(QueueId, QueueId),
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Create(err) => write!(fmt, "Download failed: {:?}", err),
+            DownloadError::Download(err) => write!(fmt, "Download failed: {:?}", err),
+            DownloadError::QueueMismatch(last, next) => write!(
+                fmt,
+                "Download failed: resource is used on {:?} before and on {:?} after",
+                last, next
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DownloadError::Create(err) => Some(err),
+            DownloadError::Download(err) => Some(err),
+            DownloadError::QueueMismatch(_, _) => None,
+        }
+    }
+}
+
+/// Failure saving or loading pipeline cache.
+#[derive(Debug)]
+pub enum PipelineCacheError {
+    /// Failed to read or write the file.
+    Io(std::io::Error),
+    /// Failed to create or read the pipeline cache.
+    OutOfMemory(OutOfMemory),
+}
+
+impl std::fmt::Display for PipelineCacheError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineCacheError::Io(err) => write!(fmt, "Pipeline cache IO failed: {}", err),
+            PipelineCacheError::OutOfMemory(err) => {
+                write!(fmt, "Pipeline cache IO failed: {:?}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PipelineCacheError::Io(err) => Some(err),
+            PipelineCacheError::OutOfMemory(err) => Some(err),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum InstanceOrId<B: Backend> {
     Instance(Instance<B>),
@@ -164,8 +262,10 @@ pub struct Factory<B: Backend> {
     descriptor_allocator: ManuallyDrop<parking_lot::Mutex<DescriptorAllocator<B>>>,
     heaps: ManuallyDrop<parking_lot::Mutex<Heaps<B>>>,
     resources: ManuallyDrop<ResourceHub<B>>,
+    pipeline_cache: ManuallyDrop<PipelineCache<B>>,
     epochs: Vec<parking_lot::RwLock<Vec<u64>>>,
     uploader: Uploader<B>,
+    downloader: Downloader<B>,
     blitter: Blitter<B>,
     families_indices: Vec<usize>,
     device: Device<B>,
@@ -191,6 +291,8 @@ where
             // Device is idle.
             self.uploader.dispose(&self.device);
             log::trace!("Uploader disposed");
+            self.downloader.dispose(&self.device);
+            log::trace!("Downloader disposed");
             self.blitter.dispose(&self.device);
             log::trace!("Blitter disposed");
             std::ptr::read(&mut *self.resources).dispose(
@@ -200,6 +302,8 @@ where
             );
 
             log::trace!("Resources disposed");
+            std::ptr::read(&mut *self.pipeline_cache).dispose(&self.device);
+            log::trace!("Pipeline cache disposed");
         }
 
         unsafe {
@@ -324,6 +428,129 @@ where
         Ok(self.resources.images.escape(image))
     }
 
+    /// Creates a buffer without memory bound to it.
+    /// Returns raw buffer and its memory requirements.
+    ///
+    /// Raw buffer must be either bound using [`bind_aliased_buffer`]
+    /// or destroyed.
+    ///
+    /// [`bind_aliased_buffer`]: #method.bind_aliased_buffer
+    pub unsafe fn create_unbound_buffer(
+        &self,
+        info: BufferInfo,
+    ) -> Result<(B::Buffer, rendy_core::hal::memory::Requirements), BufferCreationError> {
+        profile_scope!("create_unbound_buffer");
+
+        Buffer::create_unbound(&self.device, info)
+    }
+
+    /// Bind buffer created with [`create_unbound_buffer`] to the region of memory block.
+    /// Memory block is not owned by the buffer and can be shared
+    /// with other resources that are never used at the same time.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be allocated by this `Factory`,
+    /// satisfy buffer requirements at `offset`
+    /// and must not be freed while the buffer is in use.
+    ///
+    /// [`create_unbound_buffer`]: #method.create_unbound_buffer
+    pub unsafe fn bind_aliased_buffer(
+        &self,
+        info: BufferInfo,
+        raw: B::Buffer,
+        block: &MemoryBlock<B>,
+        offset: u64,
+    ) -> Result<Escape<Buffer<B>>, BufferCreationError> {
+        let buffer = Buffer::bind_aliased(
+            &self.device,
+            info,
+            raw,
+            block.memory(),
+            block.range().start + offset,
+        )?;
+        Ok(self.resources.buffers.escape(buffer))
+    }
+
+    /// Creates an image without memory bound to it.
+    /// Returns raw image and its memory requirements.
+    ///
+    /// Raw image must be either bound using [`bind_aliased_image`]
+    /// or destroyed.
+    ///
+    /// [`bind_aliased_image`]: #method.bind_aliased_image
+    pub unsafe fn create_unbound_image(
+        &self,
+        info: ImageInfo,
+    ) -> Result<(B::Image, rendy_core::hal::memory::Requirements), ImageCreationError> {
+        profile_scope!("create_unbound_image");
+
+        Image::create_unbound(&self.device, info)
+    }
+
+    /// Bind image created with [`create_unbound_image`] to the region of memory block.
+    /// Memory block is not owned by the image and can be shared
+    /// with other resources that are never used at the same time.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be allocated by this `Factory`,
+    /// satisfy image requirements at `offset`
+    /// and must not be freed while the image is in use.
+    ///
+    /// [`create_unbound_image`]: #method.create_unbound_image
+    pub unsafe fn bind_aliased_image(
+        &self,
+        info: ImageInfo,
+        raw: B::Image,
+        block: &MemoryBlock<B>,
+        offset: u64,
+    ) -> Result<Escape<Image<B>>, ImageCreationError> {
+        let image = Image::bind_aliased(
+            &self.device,
+            info,
+            raw,
+            block.memory(),
+            block.range().start + offset,
+        )?;
+        Ok(self.resources.images.escape(image))
+    }
+
+    /// Allocate memory block
+    /// from one of memory types specified by `mask`,
+    /// for intended `usage`,
+    /// with `size`
+    /// and `align` requirements.
+    ///
+    /// Block must be freed with [`free_block`].
+    ///
+    /// [`free_block`]: #method.free_block
+    pub fn allocate_block(
+        &self,
+        mask: u32,
+        usage: impl MemoryUsage,
+        size: u64,
+        align: u64,
+    ) -> Result<MemoryBlock<B>, HeapsError> {
+        profile_scope!("allocate_block");
+
+        self.heaps
+            .lock()
+            .allocate(&self.device, mask, usage, size, align)
+    }
+
+    /// Free memory block allocated with [`allocate_block`].
+    ///
+    /// # Safety
+    ///
+    /// Block must be allocated by this `Factory`.
+    /// Memory must not be used by any pending commands.
+    ///
+    /// [`allocate_block`]: #method.allocate_block
+    pub unsafe fn free_block(&self, block: MemoryBlock<B>) {
+        self.heaps.lock().free(&self.device, block);
+    }
+
     /// Fetch image format details for a particular `ImageInfo`.
     pub fn image_format_properties(&self, info: ImageInfo) -> Option<FormatProperties> {
         self.physical().image_format_properties(
@@ -478,6 +705,19 @@ where
         Ok(())
     }
 
+    /// Get staging buffer for upload from the pool or create new one.
+    fn staging_buffer(&self, size: u64) -> Result<StagingBuffer<B>, BufferCreationError> {
+        self.uploader.staging_buffer(size, |size| {
+            self.create_buffer(
+                BufferInfo {
+                    size,
+                    usage: buffer::Usage::TRANSFER_SRC,
+                },
+                memory::Upload,
+            )
+        })
+    }
+
     /// Update buffer range content with provided data.
     ///
     /// Update operation will actually be submitted to the graphics device queue
@@ -506,7 +746,7 @@ where
         content: &[T],
         last: Option<BufferState>,
         next: BufferState,
-    ) -> Result<(), UploadError>
+    ) -> Result<UploadToken, UploadError>
     where
         T: 'static + Copy,
     {
@@ -514,16 +754,10 @@ where
 
         let content_size = content.len() as u64 * std::mem::size_of::<T>() as u64;
         let mut staging = self
-            .create_buffer(
-                BufferInfo {
-                    size: content_size,
-                    usage: buffer::Usage::TRANSFER_SRC,
-                },
-                memory::Upload,
-            )
+            .staging_buffer(content_size)
             .map_err(UploadError::Create)?;
 
-        self.upload_visible_buffer(&mut staging, 0, content)
+        self.upload_visible_buffer(&mut staging.buffer, 0, content)
             .map_err(UploadError::Map)?;
 
         self.uploader
@@ -552,11 +786,17 @@ where
         staging: Escape<Buffer<B>>,
         last: Option<BufferState>,
         next: BufferState,
-    ) -> Result<(), OutOfMemory> {
+    ) -> Result<UploadToken, OutOfMemory> {
         assert!(buffer.info().usage.contains(buffer::Usage::TRANSFER_DST));
         assert!(staging.info().usage.contains(buffer::Usage::TRANSFER_SRC));
-        self.uploader
-            .upload_buffer(&self.device, buffer, offset, staging, last, next)
+        self.uploader.upload_buffer(
+            &self.device,
+            buffer,
+            offset,
+            StagingBuffer::unpooled(staging),
+            last,
+            next,
+        )
     }
 
     /// Update image layers content with provided data.
@@ -616,55 +856,246 @@ where
         content: &[T],
         last: impl Into<ImageStateOrLayout>,
         next: ImageState,
-    ) -> Result<(), UploadError>
+    ) -> Result<UploadToken, UploadError>
     where
         T: 'static + Copy,
     {
-        assert!(image.info().usage.contains(image::Usage::TRANSFER_DST));
-        assert_eq!(image.format().surface_desc().aspects, image_layers.aspects);
+        let content_size = content.len() as u64 * std::mem::size_of::<T>() as u64;
+        check_image_upload(&image, &image_layers, image_extent, content_size);
+
+        let mut staging = self
+            .staging_buffer(content_size)
+            .map_err(UploadError::Create)?;
+
+        self.upload_visible_buffer(&mut staging.buffer, 0, content)
+            .map_err(UploadError::Map)?;
+
+        self.uploader
+            .upload_image(
+                &self.device,
+                image,
+                data_width,
+                data_height,
+                image_layers,
+                image_offset,
+                image_extent,
+                staging,
+                last.into(),
+                next,
+            )
+            .map_err(UploadError::Upload)
+    }
+
+    /// Submit batch of buffer and image updates.
+    ///
+    /// Content of all updates is written into single staging buffer,
+    /// and copies are submitted at once in single command buffer
+    /// to the queue of the batch.
+    /// Unlike other uploads the batch is submitted immediately,
+    /// so it is not ordered after uploads not yet flushed by [`flush_uploads`].
+    ///
+    /// # Safety
+    ///
+    /// Resources of the batch must be created by this `Factory`.
+    /// States of updates must satisfy the same requirements as in [`upload_buffer`] and [`upload_image`].
+    ///
+    /// [`flush_uploads`]: #method.flush_uploads
+    /// [`upload_buffer`]: #method.upload_buffer
+    /// [`upload_image`]: #method.upload_image
+    pub unsafe fn submit_upload_batch(
+        &self,
+        families: &mut Families<B>,
+        batch: UploadBatch<'_, B>,
+    ) -> Result<UploadToken, UploadError> {
+        profile_scope!("submit_upload_batch");
+
+        let staging = if batch.is_empty() {
+            None
+        } else {
+            let mut staging = self
+                .staging_buffer(batch.staging_size())
+                .map_err(UploadError::Create)?;
+
+            let mut mapped = staging
+                .buffer
+                .map(&self.device, 0..batch.staging_size())
+                .map_err(UploadError::Map)?;
+
+            for (offset, content) in batch.contents().filter(|(_, content)| !content.is_empty()) {
+                mapped
+                    .write(&self.device, offset..offset + content.len() as u64)
+                    .map_err(UploadError::Map)?
+                    .write(content);
+            }
+            drop(mapped);
+            Some(staging)
+        };
+
+        self.uploader
+            .submit_batch(
+                &self.device,
+                families.family_mut(batch.queue().family),
+                batch,
+                staging,
+            )
+            .map_err(UploadError::Upload)
+    }
+
+    /// Wait for the upload to complete.
+    /// Returns `false` if timeout expired or upload was not flushed yet.
+    pub fn wait_upload(
+        &self,
+        token: &UploadToken,
+        timeout_ns: u64,
+    ) -> Result<bool, OomOrDeviceLost> {
+        profile_scope!("wait_upload");
+
+        match unsafe { self.uploader.wait(&self.device, token, timeout_ns) }? {
+            Some(epoch) => {
+                let family_index = self.families_indices[epoch.queue.family.index];
+                let mut lock = self.epochs[family_index].write();
+                let queue_epoch = &mut lock[epoch.queue.index];
+                *queue_epoch = max(*queue_epoch, epoch.epoch);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Copy buffer range content into host visible staging buffer.
+    ///
+    /// Copy operation will actually be submitted to the graphics device queue
+    /// upon next [`flush_downloads`] or [`maintain`] call to this `Factory`, and
+    /// is guaranteed to take place after all previous operations that have been
+    /// submitted to the same graphics queue on this `Factory` since last
+    /// [`flush_downloads`] or [`maintain`] call.
+    ///
+    /// Returned download can be polled with [`PendingDownload::is_complete`]
+    /// or waited for with [`wait_download`].
+    ///
+    /// # Safety
+    ///
+    /// `last` state must match the last usage state of the buffer before downloading happen.
+    /// `next` must match buffer usage state in the next device operation.
+    /// Both states must be on the same queue, otherwise `DownloadError::QueueMismatch` is returned.
+    ///
+    /// [`flush_downloads`]: #method.flush_downloads
+    /// [`maintain`]: #method.maintain
+    /// [`wait_download`]: #method.wait_download
+    /// [`PendingDownload::is_complete`]: struct.PendingDownload.html#method.is_complete
+    pub unsafe fn download_buffer(
+        &self,
+        buffer: &Buffer<B>,
+        range: std::ops::Range<u64>,
+        last: BufferState,
+        next: BufferState,
+    ) -> Result<PendingDownload<B>, DownloadError> {
+        assert!(buffer.info().usage.contains(buffer::Usage::TRANSFER_SRC));
+        assert!(range.start < range.end && range.end <= buffer.size());
+        if last.queue != next.queue {
+            return Err(DownloadError::QueueMismatch(last.queue, next.queue));
+        }
+
+        let staging = self
+            .create_buffer(
+                BufferInfo {
+                    size: range.end - range.start,
+                    usage: buffer::Usage::TRANSFER_DST,
+                },
+                memory::Download,
+            )
+            .map_err(DownloadError::Create)?;
+
+        self.downloader
+            .download_buffer(&self.device, buffer, range.start, staging, last, next)
+            .map_err(DownloadError::Download)
+    }
+
+    /// Copy image layers content into host visible staging buffer.
+    /// Texels are tightly packed in the staging buffer.
+    ///
+    /// Copy operation will actually be submitted to the graphics device queue
+    /// upon next [`flush_downloads`] or [`maintain`] call to this `Factory`, and
+    /// is guaranteed to take place after all previous operations that have been
+    /// submitted to the same graphics queue on this `Factory` since last
+    /// [`flush_downloads`] or [`maintain`] call.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created by this `Factory`.
+    /// `last` state must match the last usage state of the image before downloading happen.
+    /// `next` must match image usage state in the next device operation.
+    /// Both states must be on the same queue, otherwise `DownloadError::QueueMismatch` is returned.
+    ///
+    /// [`flush_downloads`]: #method.flush_downloads
+    /// [`maintain`]: #method.maintain
+    pub unsafe fn download_image(
+        &self,
+        image: Handle<Image<B>>,
+        image_layers: SubresourceLayers,
+        image_offset: image::Offset,
+        image_extent: Extent,
+        last: ImageState,
+        next: ImageState,
+    ) -> Result<PendingDownload<B>, DownloadError> {
+        assert!(image.info().usage.contains(image::Usage::TRANSFER_SRC));
         assert!(image_layers.layers.start <= image_layers.layers.end);
         assert!(image_layers.layers.end <= image.kind().num_layers());
-        assert!(image_layers.level <= image.info().levels);
+        assert!(image_layers.level < image.info().levels);
+        if last.queue != next.queue {
+            return Err(DownloadError::QueueMismatch(last.queue, next.queue));
+        }
 
-        let content_size = content.len() as u64 * std::mem::size_of::<T>() as u64;
         let format_desc = image.format().surface_desc();
         let texels_count = (image_extent.width / format_desc.dim.0 as u32) as u64
             * (image_extent.height / format_desc.dim.1 as u32) as u64
             * image_extent.depth as u64
             * (image_layers.layers.end - image_layers.layers.start) as u64;
         let total_bytes = (format_desc.bits as u64 / 8) * texels_count;
-        assert_eq!(
-            total_bytes, content_size,
-            "Size of must match size of the image region"
-        );
 
-        let mut staging = self
+        let staging = self
             .create_buffer(
                 BufferInfo {
-                    size: content_size,
-                    usage: buffer::Usage::TRANSFER_SRC,
+                    size: total_bytes,
+                    usage: buffer::Usage::TRANSFER_DST,
                 },
-                memory::Upload,
+                memory::Download,
             )
-            .map_err(UploadError::Create)?;
+            .map_err(DownloadError::Create)?;
 
-        self.upload_visible_buffer(&mut staging, 0, content)
-            .map_err(UploadError::Map)?;
-
-        self.uploader
-            .upload_image(
+        self.downloader
+            .download_image(
                 &self.device,
                 image,
-                data_width,
-                data_height,
                 image_layers,
                 image_offset,
                 image_extent,
                 staging,
-                last.into(),
+                last,
                 next,
             )
-            .map_err(UploadError::Upload)
+            .map_err(DownloadError::Download)
+    }
+
+    /// Wait for the download to complete.
+    /// Returns `false` if timeout expired or download was not flushed yet.
+    pub fn wait_download(
+        &self,
+        download: &PendingDownload<B>,
+        timeout_ns: u64,
+    ) -> Result<bool, OomOrDeviceLost> {
+        profile_scope!("wait_download");
+
+        unsafe { self.downloader.wait(&self.device, download, timeout_ns) }
+    }
+
+    /// Read downloaded data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if download is not complete.
+    pub fn read_download(&self, download: &PendingDownload<B>) -> Result<Vec<u8>, MapError> {
+        download.read(&self.device)
     }
 
     /// Get blitter instance
@@ -1043,7 +1474,15 @@ where
         let next = self.next_epochs(families);
         let complete = self.complete_epochs();
         unsafe {
-            self.uploader.cleanup(&self.device);
+            let epochs = &self.epochs;
+            let families_indices = &self.families_indices;
+            self.uploader.cleanup(&self.device, |epoch| {
+                let family_index = families_indices[epoch.queue.family.index];
+                let mut lock = epochs[family_index].write();
+                let queue_epoch = &mut lock[epoch.queue.index];
+                *queue_epoch = max(*queue_epoch, epoch.epoch);
+            });
+            self.downloader.cleanup(&self.device);
             self.blitter.cleanup(&self.device);
             self.resources.cleanup(
                 &self.device,
@@ -1057,11 +1496,35 @@ where
         }
     }
 
+    /// Set queue of dedicated transfer family to record uploads on.
+    ///
+    /// Resources that are not yet used by the device
+    /// (`last` state is `None` for buffers or just a layout for images)
+    /// are uploaded on this queue and their ownership is transferred
+    /// to the family of the `next` state queue.
+    /// Other uploads are still recorded on the `next` state queue.
+    ///
+    /// Should be called when there are no unflushed uploads.
+    pub fn set_upload_transfer_queue(&mut self, queue: Option<QueueId>) {
+        self.uploader.set_transfer_queue(queue);
+    }
+
+    /// Set config of the pool of staging buffers reused by uploads.
+    /// Free staging buffers kept by the pool are released.
+    pub fn set_staging_pool_config(&mut self, config: StagingPoolConfig) {
+        self.uploader.set_staging_pool_config(config);
+    }
+
     /// Flush uploads
     pub fn flush_uploads(&mut self, families: &mut Families<B>) {
         unsafe { self.uploader.flush(families) }
     }
 
+    /// Flush downloads
+    pub fn flush_downloads(&mut self, families: &mut Families<B>) {
+        unsafe { self.downloader.flush(families) }
+    }
+
     /// Flush blits
     pub fn flush_blits(&mut self, families: &mut Families<B>) {
         unsafe { self.blitter.flush(families) }
@@ -1070,6 +1533,7 @@ where
     /// Flush uploads and cleanup unused resources.
     pub fn maintain(&mut self, families: &mut Families<B>) {
         self.flush_uploads(families);
+        self.flush_downloads(families);
         self.flush_blits(families);
         self.cleanup(families);
     }
@@ -1091,6 +1555,24 @@ where
         Ok(self.resources.layouts.escape(layout))
     }
 
+    /// Get cached descriptor set layout with specified bindings or create new one.
+    /// Order of bindings doesn't matter.
+    /// User should prefer this function to [`create_descriptor_set_layout`]
+    /// when the same layout is requested many times.
+    ///
+    /// [`create_descriptor_set_layout`]: #method.create_descriptor_set_layout
+    pub fn get_descriptor_set_layout(
+        &self,
+        bindings: Vec<DescriptorSetLayoutBinding>,
+    ) -> Result<Handle<DescriptorSetLayout<B>>, OutOfMemory> {
+        DescriptorSetLayoutCache::get_with_upgradable_lock(
+            self.resources.layouts_cache.upgradable_read(),
+            parking_lot::RwLockUpgradableReadGuard::upgrade,
+            &bindings,
+            || Ok(self.create_descriptor_set_layout(bindings.clone())?.into()),
+        )
+    }
+
     /// Create descriptor sets with specified layout.
     pub fn create_relevant_descriptor_set(
         &self,
@@ -1144,11 +1626,479 @@ where
             .collect())
     }
 
+    /// Create pipeline layout with specified descriptor set layouts and push constants.
+    ///
+    /// This function returns relevant value, that is, the value cannot be dropped.
+    /// However pipeline layout can be destroyed using [`destroy_relevant_pipeline_layout`] function.
+    ///
+    /// [`destroy_relevant_pipeline_layout`]: #method.destroy_relevant_pipeline_layout
+    pub fn create_relevant_pipeline_layout(
+        &self,
+        sets: Vec<Handle<DescriptorSetLayout<B>>>,
+        push_constants: Vec<(ShaderStageFlags, std::ops::Range<u32>)>,
+    ) -> Result<PipelineLayout<B>, OutOfMemory> {
+        // TODO: Check `sets` belong to this factory.
+        unsafe {
+            PipelineLayout::create(
+                &self.device,
+                PipelineLayoutInfo {
+                    sets,
+                    push_constants,
+                },
+            )
+        }
+    }
+
+    /// Destroy pipeline layout.
+    /// If pipeline layout was created using [`create_pipeline_layout`] it must be unescaped first.
+    ///
+    /// # Safety
+    ///
+    /// Pipeline layout must not be used by any pending commands or referenced anywhere.
+    ///
+    /// [`create_pipeline_layout`]: #method.create_pipeline_layout
+    pub unsafe fn destroy_relevant_pipeline_layout(&self, layout: PipelineLayout<B>) {
+        layout.dispose(&self.device);
+    }
+
+    /// Create pipeline layout with specified descriptor set layouts and push constants.
+    ///
+    /// This function (unlike [`create_relevant_pipeline_layout`]) returns value that can be dropped.
+    ///
+    /// [`create_relevant_pipeline_layout`]: #method.create_relevant_pipeline_layout
+    pub fn create_pipeline_layout(
+        &self,
+        sets: Vec<Handle<DescriptorSetLayout<B>>>,
+        push_constants: Vec<(ShaderStageFlags, std::ops::Range<u32>)>,
+    ) -> Result<Escape<PipelineLayout<B>>, OutOfMemory> {
+        let layout = self.create_relevant_pipeline_layout(sets, push_constants)?;
+        Ok(self.resources.pipeline_layouts.escape(layout))
+    }
+
+    /// Get cached pipeline layout with specified descriptor set layouts and push constants
+    /// or create new one.
+    /// Descriptor set layouts are compared by identity,
+    /// so they should be acquired with [`get_descriptor_set_layout`] too.
+    ///
+    /// [`get_descriptor_set_layout`]: #method.get_descriptor_set_layout
+    pub fn get_pipeline_layout(
+        &self,
+        sets: Vec<Handle<DescriptorSetLayout<B>>>,
+        push_constants: Vec<(ShaderStageFlags, std::ops::Range<u32>)>,
+    ) -> Result<Handle<PipelineLayout<B>>, OutOfMemory> {
+        PipelineLayoutCache::get_with_upgradable_lock(
+            self.resources.pipeline_layouts_cache.upgradable_read(),
+            parking_lot::RwLockUpgradableReadGuard::upgrade,
+            &sets,
+            &push_constants,
+            || {
+                Ok(self
+                    .create_pipeline_layout(sets.clone(), push_constants.clone())?
+                    .into())
+            },
+        )
+    }
+
+    /// Create graphics pipeline using pipeline cache of this `Factory`.
+    /// `desc.layout` must be the raw layout of `layout`.
+    ///
+    /// This function returns relevant value, that is, the value cannot be dropped.
+    /// However pipeline can be destroyed using [`destroy_relevant_graphics_pipeline`] function.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` must be created by this `Factory`.
+    ///
+    /// [`destroy_relevant_graphics_pipeline`]: #method.destroy_relevant_graphics_pipeline
+    pub unsafe fn create_relevant_graphics_pipeline(
+        &self,
+        desc: &GraphicsPipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+    ) -> Result<GraphicsPipeline<B>, pso::CreationError> {
+        GraphicsPipeline::create(&self.device, desc, layout, Some(&*self.pipeline_cache))
+    }
+
+    /// Destroy graphics pipeline.
+    /// If pipeline was created using [`create_graphics_pipeline`] it must be unescaped first.
+    ///
+    /// # Safety
+    ///
+    /// Pipeline must not be used by any pending commands or referenced anywhere.
+    ///
+    /// [`create_graphics_pipeline`]: #method.create_graphics_pipeline
+    pub unsafe fn destroy_relevant_graphics_pipeline(&self, pipeline: GraphicsPipeline<B>) {
+        pipeline.dispose(&self.device);
+    }
+
+    /// Create graphics pipeline using pipeline cache of this `Factory`.
+    /// `desc.layout` must be the raw layout of `layout`.
+    ///
+    /// This function (unlike [`create_relevant_graphics_pipeline`]) returns value that can be dropped.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` must be created by this `Factory`.
+    ///
+    /// [`create_relevant_graphics_pipeline`]: #method.create_relevant_graphics_pipeline
+    pub unsafe fn create_graphics_pipeline(
+        &self,
+        desc: &GraphicsPipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+    ) -> Result<Escape<GraphicsPipeline<B>>, pso::CreationError> {
+        let pipeline = self.create_relevant_graphics_pipeline(desc, layout)?;
+        Ok(self.resources.graphics_pipelines.escape(pipeline))
+    }
+
+    /// Create compute pipeline using pipeline cache of this `Factory`.
+    /// `desc.layout` must be the raw layout of `layout`.
+    ///
+    /// This function returns relevant value, that is, the value cannot be dropped.
+    /// However pipeline can be destroyed using [`destroy_relevant_compute_pipeline`] function.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` must be created by this `Factory`.
+    ///
+    /// [`destroy_relevant_compute_pipeline`]: #method.destroy_relevant_compute_pipeline
+    pub unsafe fn create_relevant_compute_pipeline(
+        &self,
+        desc: &ComputePipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+    ) -> Result<ComputePipeline<B>, pso::CreationError> {
+        ComputePipeline::create(&self.device, desc, layout, Some(&*self.pipeline_cache))
+    }
+
+    /// Destroy compute pipeline.
+    /// If pipeline was created using [`create_compute_pipeline`] it must be unescaped first.
+    ///
+    /// # Safety
+    ///
+    /// Pipeline must not be used by any pending commands or referenced anywhere.
+    ///
+    /// [`create_compute_pipeline`]: #method.create_compute_pipeline
+    pub unsafe fn destroy_relevant_compute_pipeline(&self, pipeline: ComputePipeline<B>) {
+        pipeline.dispose(&self.device);
+    }
+
+    /// Create compute pipeline using pipeline cache of this `Factory`.
+    /// `desc.layout` must be the raw layout of `layout`.
+    ///
+    /// This function (unlike [`create_relevant_compute_pipeline`]) returns value that can be dropped.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` must be created by this `Factory`.
+    ///
+    /// [`create_relevant_compute_pipeline`]: #method.create_relevant_compute_pipeline
+    pub unsafe fn create_compute_pipeline(
+        &self,
+        desc: &ComputePipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+    ) -> Result<Escape<ComputePipeline<B>>, pso::CreationError> {
+        let pipeline = self.create_relevant_compute_pipeline(desc, layout)?;
+        Ok(self.resources.compute_pipelines.escape(pipeline))
+    }
+
+    /// Get pipeline cache used to create pipelines.
+    pub fn pipeline_cache(&self) -> &PipelineCache<B> {
+        &self.pipeline_cache
+    }
+
+    /// Write content of the pipeline cache to the file.
+    pub fn save_pipeline_cache(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), PipelineCacheError> {
+        let data = self
+            .pipeline_cache
+            .data(&self.device)
+            .map_err(PipelineCacheError::OutOfMemory)?;
+        std::fs::write(path, data).map_err(PipelineCacheError::Io)
+    }
+
+    /// Merge content of the file written by [`save_pipeline_cache`] into the pipeline cache.
+    /// Data saved with another device or driver version is ignored.
+    ///
+    /// [`save_pipeline_cache`]: #method.save_pipeline_cache
+    pub fn load_pipeline_cache(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), PipelineCacheError> {
+        let data = std::fs::read(path).map_err(PipelineCacheError::Io)?;
+        let loaded = PipelineCache::create(&self.device, Some(&data))
+            .map_err(PipelineCacheError::OutOfMemory)?;
+        let result = self.pipeline_cache.merge(&self.device, Some(&loaded));
+        unsafe {
+            loaded.dispose(&self.device);
+        }
+        result.map_err(PipelineCacheError::OutOfMemory)
+    }
+
     /// Query memory utilization.
     pub fn memory_utilization(&self) -> TotalMemoryUtilization {
         self.heaps.lock().utilization()
     }
 
+    /// Get report of live memory allocations grouped by tag.
+    /// Wrap memory usage into `memory::Tagged` when creating resources to tag allocations.
+    pub fn memory_allocation_report(&self) -> AllocationReport {
+        self.heaps.lock().allocation_report()
+    }
+
+    /// Set soft and hard limits of memory allocated from the heap.
+    /// See `Heaps::set_budget`.
+    pub fn set_memory_budget(&self, heap_index: usize, budget: HeapBudget) {
+        self.heaps.lock().set_budget(heap_index, budget)
+    }
+
+    /// Set callback invoked when allocation pushes heap usage over soft limit.
+    /// See `Heaps::set_soft_limit_callback`.
+    ///
+    /// **The callback runs while memory heaps are locked.
+    /// It must not call any method of this `Factory`, otherwise it deadlocks.**
+    pub fn set_memory_soft_limit_callback(
+        &self,
+        callback: impl FnMut(usize, MemoryHeapUtilization) + Send + 'static,
+    ) {
+        self.heaps.lock().set_soft_limit_callback(callback)
+    }
+
+    /// Query how many bytes can be allocated for `usage` without exceeding the budget.
+    pub fn memory_headroom(&self, usage: impl MemoryUsage) -> u64 {
+        self.heaps.lock().headroom(usage)
+    }
+
+    /// Create ring allocator for transient per-frame data.
+    /// See `Heaps::create_ring_allocator`.
+    pub fn create_ring_allocator(
+        &self,
+        usage: impl MemoryUsage,
+        config: memory::RingConfig,
+    ) -> Result<memory::RingAllocator<B>, HeapsError> {
+        self.heaps.lock().create_ring_allocator(!0, usage, config)
+    }
+
+    /// Allocate block from the ring for the frame with specified index.
+    /// See `Heaps::allocate_from_ring`.
+    pub fn allocate_from_ring(
+        &self,
+        ring: &mut memory::RingAllocator<B>,
+        frame: u64,
+        size: u64,
+        align: u64,
+    ) -> Result<memory::RingBlock<B>, HeapsError> {
+        self.heaps
+            .lock()
+            .allocate_from_ring(&self.device, ring, frame, size, align)
+    }
+
+    /// Reclaim blocks of the ring allocated for frames with index less than `upper_bound`.
+    /// See `Heaps::complete_ring`.
+    pub fn complete_ring(&self, ring: &mut memory::RingAllocator<B>, upper_bound: u64) {
+        self.heaps
+            .lock()
+            .complete_ring(&self.device, ring, upper_bound)
+    }
+
+    /// Free all memory of the ring allocator.
+    /// See `Heaps::dispose_ring_allocator`.
+    pub fn dispose_ring_allocator(&self, ring: memory::RingAllocator<B>) {
+        self.heaps.lock().dispose_ring_allocator(&self.device, ring)
+    }
+
+    /// Start memory defragmentation pass.
+    /// Returns total size of memory that would be freed if all movable resources are relocated.
+    /// See `Heaps::begin_defragmentation`.
+    pub fn begin_defragmentation(&self, threshold: f32) -> u64 {
+        self.heaps.lock().begin_defragmentation(threshold)
+    }
+
+    /// Check if buffer should be relocated by defragmentation pass.
+    pub fn is_buffer_movable(&self, buffer: &Buffer<B>) -> bool {
+        buffer
+            .block()
+            .map_or(false, |block| self.heaps.lock().is_movable(block))
+    }
+
+    /// Check if image should be relocated by defragmentation pass.
+    pub fn is_image_movable(&self, image: &Image<B>) -> bool {
+        image
+            .block()
+            .map_or(false, |block| self.heaps.lock().is_movable(block))
+    }
+
+    /// Get memory usage to allocate relocated resource with.
+    /// It keeps memory properties, allocator kind and tag of the old `block`.
+    fn relocation(&self, block: Option<&MemoryBlock<B>>) -> Relocation {
+        match block {
+            Some(block) => {
+                let heaps = self.heaps.lock();
+                Relocation {
+                    properties: block.properties(),
+                    kind: Some(heaps.block_kind(block)),
+                    tag: heaps.block_tag(block).cloned(),
+                }
+            }
+            None => Relocation {
+                properties: rendy_core::hal::memory::Properties::empty(),
+                kind: None,
+                tag: None,
+            },
+        }
+    }
+
+    /// Create new buffer with same info, memory properties and usage as `buffer`
+    /// and record copying of its content with `encoder`.
+    /// The new buffer is left in the same `state`.
+    /// `state.queue` is not used, `encoder` must be recorded for the queue that uses the buffer.
+    ///
+    /// Caller is responsible to replace old buffer with returned one
+    /// and to drop the old one after recorded commands are submitted.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be accessed in the `state` by commands submitted before those recorded.
+    pub unsafe fn relocate_buffer<C, L>(
+        &self,
+        encoder: &mut Encoder<'_, B, C, L>,
+        buffer: &Buffer<B>,
+        state: BufferState,
+    ) -> Result<Escape<Buffer<B>>, BufferCreationError>
+    where
+        C: Supports<Transfer>,
+    {
+        let usage = self.relocation(buffer.block());
+        let new = self.create_buffer(*buffer.info(), usage)?;
+
+        encoder.pipeline_barrier(
+            state.stage..rendy_core::hal::pso::PipelineStage::TRANSFER,
+            rendy_core::hal::memory::Dependencies::empty(),
+            Some(rendy_core::hal::memory::Barrier::AllBuffers(
+                state.access..buffer::Access::TRANSFER_READ,
+            )),
+        );
+        encoder.copy_buffer(
+            buffer.raw(),
+            new.raw(),
+            Some(rendy_core::hal::command::BufferCopy {
+                src: 0,
+                dst: 0,
+                size: buffer.size(),
+            }),
+        );
+        encoder.pipeline_barrier(
+            rendy_core::hal::pso::PipelineStage::TRANSFER..state.stage,
+            rendy_core::hal::memory::Dependencies::empty(),
+            Some(rendy_core::hal::memory::Barrier::AllBuffers(
+                buffer::Access::TRANSFER_WRITE..state.access,
+            )),
+        );
+
+        Ok(new)
+    }
+
+    /// Create new image with same info, memory properties and usage as `image`
+    /// and record copying of all its levels and layers with `encoder`.
+    /// The new image is left in the same `state`.
+    /// `state.queue` is not used, `encoder` must be recorded for the queue that uses the image.
+    ///
+    /// Caller is responsible to replace old image with returned one
+    /// (recreating views of it)
+    /// and to drop the old one after recorded commands are submitted.
+    ///
+    /// # Safety
+    ///
+    /// `image` must be accessed in the `state` by commands submitted before those recorded.
+    pub unsafe fn relocate_image<C, L>(
+        &self,
+        encoder: &mut Encoder<'_, B, C, L>,
+        image: &Image<B>,
+        state: ImageState,
+    ) -> Result<Escape<Image<B>>, ImageCreationError>
+    where
+        C: Supports<Transfer>,
+    {
+        let usage = self.relocation(image.block());
+        let new = self.create_image(*image.info(), usage)?;
+
+        let aspects = image.format().surface_desc().aspects;
+        let range = image::SubresourceRange {
+            aspects,
+            levels: 0..image.levels(),
+            layers: 0..image.layers(),
+        };
+
+        encoder.pipeline_barrier(
+            state.stage..rendy_core::hal::pso::PipelineStage::TRANSFER,
+            rendy_core::hal::memory::Dependencies::empty(),
+            vec![
+                rendy_core::hal::memory::Barrier::Image {
+                    states: (state.access, state.layout)
+                        ..(
+                            image::Access::TRANSFER_READ,
+                            image::Layout::TransferSrcOptimal,
+                        ),
+                    target: image.raw(),
+                    families: None,
+                    range: range.clone(),
+                },
+                rendy_core::hal::memory::Barrier::Image {
+                    states: (image::Access::empty(), image::Layout::Undefined)
+                        ..(
+                            image::Access::TRANSFER_WRITE,
+                            image::Layout::TransferDstOptimal,
+                        ),
+                    target: new.raw(),
+                    families: None,
+                    range: range.clone(),
+                },
+            ],
+        );
+        encoder.copy_image(
+            image.raw(),
+            image::Layout::TransferSrcOptimal,
+            new.raw(),
+            image::Layout::TransferDstOptimal,
+            (0..image.levels()).map(|level| {
+                let layers = image::SubresourceLayers {
+                    aspects,
+                    level,
+                    layers: 0..image.layers(),
+                };
+                rendy_core::hal::command::ImageCopy {
+                    src_subresource: layers.clone(),
+                    src_offset: image::Offset::ZERO,
+                    dst_subresource: layers,
+                    dst_offset: image::Offset::ZERO,
+                    extent: image.kind().level_extent(level),
+                }
+            }),
+        );
+        encoder.pipeline_barrier(
+            rendy_core::hal::pso::PipelineStage::TRANSFER..state.stage,
+            rendy_core::hal::memory::Dependencies::empty(),
+            Some(rendy_core::hal::memory::Barrier::Image {
+                states: (
+                    image::Access::TRANSFER_WRITE,
+                    image::Layout::TransferDstOptimal,
+                )..(state.access, state.layout),
+                target: new.raw(),
+                families: None,
+                range,
+            }),
+        );
+
+        Ok(new)
+    }
+
+    /// Finish memory defragmentation pass.
+    /// Call it after relocated resources are destroyed,
+    /// that is after `maintain` is called for frames that used them.
+    /// See `Heaps::end_defragmentation`.
+    pub fn end_defragmentation(&self) {
+        self.heaps.lock().end_defragmentation()
+    }
+
     /// Get Factory's instance id.
     pub fn instance_id(&self) -> InstanceId {
         self.device.id().instance
@@ -1288,7 +2238,19 @@ where
 
     log::debug!("Heaps: {:#?}\nTypes: {:#?}", heaps, types);
 
-    let heaps = unsafe { Heaps::new(types, heaps) };
+    let properties = types
+        .iter()
+        .map(|&(properties, _, _)| properties)
+        .collect::<SmallVec<[_; 32]>>();
+    let mut heaps = unsafe { Heaps::new(types, heaps) };
+    for (index, properties) in properties.into_iter().enumerate() {
+        for allocator in config
+            .heaps
+            .custom_allocators::<B>(rendy_core::hal::MemoryTypeId(index), properties)
+        {
+            heaps.add_custom_allocator(index as u32, allocator);
+        }
+    }
 
     let epochs = families
         .as_slice()
@@ -1304,8 +2266,14 @@ where
         resources: ManuallyDrop::new(ResourceHub::default()),
         uploader: unsafe { Uploader::new(&device, &families) }
             .map_err(rendy_core::hal::device::CreationError::OutOfMemory)?,
+        downloader: unsafe { Downloader::new(&device, &families) }
+            .map_err(rendy_core::hal::device::CreationError::OutOfMemory)?,
         blitter: unsafe { Blitter::new(&device, &families) }
             .map_err(rendy_core::hal::device::CreationError::OutOfMemory)?,
+        pipeline_cache: ManuallyDrop::new(
+            PipelineCache::create(&device, None)
+                .map_err(rendy_core::hal::device::CreationError::OutOfMemory)?,
+        ),
         families_indices: families.indices().into(),
         epochs,
         device,
@@ -1315,3 +2283,43 @@ where
 
     Ok((factory, families))
 }
+
+/// Memory usage for relocated resources.
+/// Prefers memory type with exactly the same properties
+/// and same kind of allocator as old one,
+/// and keeps its tag.
+#[derive(Clone, Debug)]
+struct Relocation {
+    properties: rendy_core::hal::memory::Properties,
+    kind: Option<memory::Kind>,
+    tag: Option<memory::AllocationTag>,
+}
+
+impl MemoryUsage for Relocation {
+    fn properties_required(&self) -> rendy_core::hal::memory::Properties {
+        self.properties
+    }
+
+    fn memory_fitness(&self, properties: rendy_core::hal::memory::Properties) -> u32 {
+        assert!(properties.contains(self.properties));
+        (properties == self.properties) as u32
+    }
+
+    fn allocator_fitness(&self, kind: memory::Kind) -> u32 {
+        if self.kind == Some(kind) {
+            return 4;
+        }
+        match kind {
+            memory::Kind::Dedicated => 1,
+            memory::Kind::Dynamic => 2,
+            memory::Kind::Linear => 0,
+            memory::Kind::Tlsf => 3,
+            memory::Kind::Custom(_) => 0,
+            memory::Kind::Ring => 0,
+        }
+    }
+
+    fn tag(&self) -> Option<&memory::AllocationTag> {
+        self.tag.as_ref()
+    }
+}
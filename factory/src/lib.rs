@@ -18,9 +18,14 @@ use rendy_resource as resource;
 use rendy_wsi as wsi;
 
 mod barriers;
+mod batch;
 mod blitter;
 mod config;
+mod download;
 mod factory;
 mod upload;
 
-pub use crate::{barriers::*, blitter::*, config::*, factory::*, upload::*};
+pub use crate::{
+    barriers::*, batch::UploadBatch, blitter::*, config::*, download::PendingDownload, factory::*,
+    upload::*,
+};
@@ -1,15 +1,29 @@
 use {
     crate::{
         barriers::Barriers,
+        batch::UploadBatch,
         command::{
-            CommandBuffer, CommandPool, Families, Family, IndividualReset, InitialState, OneShot,
-            PendingOnceState, PrimaryLevel, QueueId, RecordingState, Submission, Transfer,
+            CommandBuffer, CommandPool, Families, Family, Fence, FenceEpoch, IndividualReset,
+            InitialState, OneShot, PendingOnceState, PrimaryLevel, QueueId, RecordingState,
+            Submission, Transfer,
         },
         core::Device,
         resource::{Buffer, Escape, Handle, Image},
     },
-    rendy_core::hal::device::{Device as _, OutOfMemory},
-    std::{collections::VecDeque, iter::once},
+    rendy_core::hal::{
+        device::{Device as _, OomOrDeviceLost, OutOfMemory},
+        memory::{Barrier, Dependencies},
+        pso::PipelineStage,
+        queue::QueueFamilyId,
+    },
+    std::{
+        collections::VecDeque,
+        iter::once,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+    },
 };
 
 /// State of the buffer on device.
@@ -120,9 +134,231 @@ impl From<rendy_core::hal::image::Layout> for ImageStateOrLayout {
     }
 }
 
+/// Token of the upload operation.
+/// All uploads submitted together share the same token.
+///
+/// Upload becomes complete after [`Factory::cleanup`] finds it finished
+/// or after successful [`Factory::wait_upload`].
+///
+/// [`Factory::cleanup`]: struct.Factory.html#method.cleanup
+/// [`Factory::wait_upload`]: struct.Factory.html#method.wait_upload
+#[derive(Clone, Debug)]
+pub struct UploadToken {
+    batch: Arc<BatchState>,
+}
+
+/// State shared by tokens of uploads submitted together.
+#[derive(Debug)]
+struct BatchState {
+    queue: QueueId,
+
+    /// Epoch of the fence submitted with uploads.
+    /// `NOT_SUBMITTED` until uploads are flushed.
+    epoch: AtomicU64,
+    complete: AtomicBool,
+}
+
+const NOT_SUBMITTED: u64 = !0;
+
+impl BatchState {
+    fn new(queue: QueueId) -> Self {
+        BatchState {
+            queue,
+            epoch: AtomicU64::new(NOT_SUBMITTED),
+            complete: AtomicBool::new(false),
+        }
+    }
+}
+
+impl UploadToken {
+    /// Check if upload is complete and uploaded content is available to the device.
+    pub fn is_complete(&self) -> bool {
+        self.batch.complete.load(Ordering::Acquire)
+    }
+
+    /// Get epoch of the fence submitted with upload.
+    /// Returns `None` if upload is not flushed yet.
+    pub fn epoch(&self) -> Option<FenceEpoch> {
+        match self.batch.epoch.load(Ordering::Acquire) {
+            NOT_SUBMITTED => None,
+            epoch => Some(FenceEpoch {
+                queue: self.batch.queue,
+                epoch,
+            }),
+        }
+    }
+}
+
+/// Config for the pool of staging buffers used by uploads.
+#[derive(Clone, Copy, Debug)]
+pub struct StagingPoolConfig {
+    /// Size of the smallest pooled staging buffer.
+    /// Staging buffers are rounded up to the power of two not less than this value.
+    pub min_size: u64,
+
+    /// Size of the largest pooled staging buffer.
+    /// Bigger staging buffers are created for single upload and are not reused.
+    pub max_size: u64,
+
+    /// Maximum total size of free staging buffers kept in the pool.
+    pub max_free: u64,
+}
+
+impl Default for StagingPoolConfig {
+    fn default() -> Self {
+        StagingPoolConfig {
+            min_size: 4 * 1024,
+            max_size: 16 * 1024 * 1024,
+            max_free: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl StagingPoolConfig {
+    /// Get index of size class for the size.
+    /// Returns `None` if buffers of this size are not pooled.
+    fn class(&self, size: u64) -> Option<usize> {
+        if size > self.max_size {
+            None
+        } else {
+            let size = size.max(self.min_size).next_power_of_two();
+            Some((size.trailing_zeros() - self.min_size.trailing_zeros()) as usize)
+        }
+    }
+
+    fn class_size(&self, class: usize) -> u64 {
+        self.min_size << class
+    }
+}
+
+/// Staging buffer with content to upload.
+#[derive(Debug)]
+pub(crate) struct StagingBuffer<B: rendy_core::hal::Backend> {
+    pub(crate) buffer: Escape<Buffer<B>>,
+
+    /// Size of the content. Pooled buffers can be bigger.
+    pub(crate) size: u64,
+    pooled: bool,
+}
+
+impl<B> StagingBuffer<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Wrap staging buffer that won't be returned to the pool.
+    pub(crate) fn unpooled(buffer: Escape<Buffer<B>>) -> Self {
+        StagingBuffer {
+            size: buffer.size(),
+            buffer,
+            pooled: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StagingClass<B: rendy_core::hal::Backend> {
+    free: Vec<Escape<Buffer<B>>>,
+    used: usize,
+
+    /// Recent peak of buffers in use. Decays on each trim.
+    demand: usize,
+}
+
+/// Staging buffers grouped by power of two size classes.
+#[derive(Debug)]
+struct StagingPool<B: rendy_core::hal::Backend> {
+    config: StagingPoolConfig,
+    classes: Vec<StagingClass<B>>,
+}
+
+impl<B> StagingPool<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    fn new(config: StagingPoolConfig) -> Self {
+        assert!(config.min_size.is_power_of_two());
+        assert!(config.min_size <= config.max_size);
+
+        StagingPool {
+            config,
+            classes: Vec::new(),
+        }
+    }
+
+    fn class(&self, size: u64) -> Option<usize> {
+        self.config.class(size)
+    }
+
+    fn class_size(&self, class: usize) -> u64 {
+        self.config.class_size(class)
+    }
+
+    /// Take free buffer of the class and mark it used.
+    fn acquire(&mut self, class: usize) -> Option<Escape<Buffer<B>>> {
+        while self.classes.len() <= class {
+            self.classes.push(StagingClass {
+                free: Vec::new(),
+                used: 0,
+                demand: 0,
+            });
+        }
+
+        let class = &mut self.classes[class];
+        class.used += 1;
+        class.demand = class.demand.max(class.used);
+        class.free.pop()
+    }
+
+    fn recycle(&mut self, buffer: Escape<Buffer<B>>) {
+        let size = buffer.size();
+        match self.class(size) {
+            Some(index) if self.class_size(index) == size && index < self.classes.len() => {
+                let class = &mut self.classes[index];
+                class.used = class.used.saturating_sub(1);
+                class.free.push(buffer);
+            }
+            _ => {
+                // Acquired from the pool with another config.
+            }
+        }
+    }
+
+    /// Drop free buffers not needed to satisfy recent demand
+    /// or exceeding total size cap, biggest first.
+    fn trim(&mut self) {
+        let mut total = 0;
+        for index in (0..self.classes.len()).rev() {
+            let size = self.class_size(index);
+            let class = &mut self.classes[index];
+            let keep = free_to_keep(
+                class.free.len(),
+                class.used,
+                class.demand,
+                size,
+                self.config.max_free.saturating_sub(total),
+            );
+            class.free.truncate(keep);
+            total += size * keep as u64;
+            class.demand = class.used.max(class.demand / 2);
+        }
+    }
+}
+
+/// Number of free buffers of `size` to keep so that
+/// free and used buffers don't exceed `demand`
+/// and free buffers fit into `budget`.
+fn free_to_keep(free: usize, used: usize, demand: usize, size: u64, budget: u64) -> usize {
+    free.min(demand.saturating_sub(used))
+        .min((budget / size).min(usize::max_value() as u64) as usize)
+}
+
 #[derive(Debug)]
 pub(crate) struct Uploader<B: rendy_core::hal::Backend> {
     family_uploads: Vec<Option<parking_lot::Mutex<FamilyUploads<B>>>>,
+    staging_pool: parking_lot::Mutex<StagingPool<B>>,
+
+    /// Queue of dedicated transfer family used to upload resources owned by other families.
+    transfer: Option<QueueId>,
 }
 
 impl<B> Uploader<B>
@@ -158,7 +394,93 @@ where
             }));
         }
 
-        Ok(Uploader { family_uploads })
+        Ok(Uploader {
+            family_uploads,
+            staging_pool: parking_lot::Mutex::new(StagingPool::new(StagingPoolConfig::default())),
+            transfer: None,
+        })
+    }
+
+    /// Replace config of the staging buffer pool.
+    /// Free buffers are dropped, buffers in use are not returned to the pool.
+    pub(crate) fn set_staging_pool_config(&mut self, config: StagingPoolConfig) {
+        *self.staging_pool.get_mut() = StagingPool::new(config);
+    }
+
+    /// Get staging buffer that fits `size` bytes.
+    /// Takes buffer from the pool or calls `create` with size of the new buffer.
+    pub(crate) fn staging_buffer<E>(
+        &self,
+        size: u64,
+        create: impl FnOnce(u64) -> Result<Escape<Buffer<B>>, E>,
+    ) -> Result<StagingBuffer<B>, E> {
+        let mut pool = self.staging_pool.lock();
+        match pool.class(size) {
+            Some(class) => {
+                let buffer = pool.acquire(class);
+                let class_size = pool.class_size(class);
+                drop(pool);
+
+                let buffer = match buffer {
+                    Some(buffer) => buffer,
+                    None => create(class_size).map_err(|err| {
+                        self.staging_pool.lock().classes[class].used -= 1;
+                        err
+                    })?,
+                };
+
+                Ok(StagingBuffer {
+                    buffer,
+                    size,
+                    pooled: true,
+                })
+            }
+            None => {
+                drop(pool);
+                create(size).map(StagingBuffer::unpooled)
+            }
+        }
+    }
+
+    /// Set queue to record uploads on
+    /// when resource is not yet used by the device.
+    /// Resource ownership is transferred to the family of the target queue afterwards.
+    pub(crate) fn set_transfer_queue(&mut self, queue: Option<QueueId>) {
+        if let Some(queue) = queue {
+            assert!(
+                self.family_uploads
+                    .get(queue.family.index)
+                    .map_or(false, Option::is_some),
+                "Uploader must be initialized for transfer family"
+            );
+        }
+        self.transfer = queue;
+    }
+
+    fn transfer_queue(&self, queue: QueueId) -> Option<QueueId> {
+        self.transfer
+            .filter(|transfer| transfer.family != queue.family)
+    }
+
+    /// Lock uploads of the transfer family and the family of the target queue.
+    /// Transfer family is always locked first.
+    fn lock_with_transfer(
+        &self,
+        transfer: QueueId,
+        queue: QueueId,
+    ) -> (
+        parking_lot::MutexGuard<'_, FamilyUploads<B>>,
+        parking_lot::MutexGuard<'_, FamilyUploads<B>>,
+    ) {
+        let transfer_uploads = self.family_uploads[transfer.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+        let family_uploads = self.family_uploads[queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+        (transfer_uploads, family_uploads)
     }
 
     /// # Safety
@@ -171,43 +493,96 @@ where
         device: &Device<B>,
         buffer: &Buffer<B>,
         offset: u64,
-        staging: Escape<Buffer<B>>,
+        staging: StagingBuffer<B>,
         last: Option<BufferState>,
         next: BufferState,
-    ) -> Result<(), OutOfMemory> {
+    ) -> Result<UploadToken, OutOfMemory> {
+        if let (Some(transfer), None) = (self.transfer_queue(next.queue), last) {
+            return self
+                .upload_buffer_with_transfer(device, transfer, buffer, offset, staging, next);
+        }
+
         let mut family_uploads = self.family_uploads[next.queue.family.index]
             .as_ref()
             .unwrap()
             .lock();
 
-        if let Some(last) = last {
-            if last.queue != next.queue {
-                unimplemented!("Can't sync resources across queues");
-            }
-        }
-
-        family_uploads.barriers.add_buffer(
-            last.map_or(rendy_core::hal::pso::PipelineStage::empty(), |l| l.stage),
-            rendy_core::hal::buffer::Access::empty(),
-            next.stage,
-            next.access,
-        );
+        add_buffer_upload_barriers(&mut family_uploads.barriers, last, next);
 
         let next_upload = family_uploads.next_upload(device, next.queue.index)?;
         let mut encoder = next_upload.command_buffer.encoder();
         encoder.copy_buffer(
-            staging.raw(),
+            staging.buffer.raw(),
             buffer.raw(),
             Some(rendy_core::hal::command::BufferCopy {
                 src: 0,
                 dst: offset,
-                size: staging.size(),
+                size: staging.size,
             }),
         );
 
         next_upload.staging_buffers.push(staging);
 
-        Ok(())
+        Ok(next_upload.token())
+    }
+
+    /// Record copy and ownership release on transfer queue
+    /// and ownership acquire on the target queue.
+    unsafe fn upload_buffer_with_transfer(
+        &self,
+        device: &Device<B>,
+        transfer: QueueId,
+        buffer: &Buffer<B>,
+        offset: u64,
+        staging: StagingBuffer<B>,
+        next: BufferState,
+    ) -> Result<UploadToken, OutOfMemory> {
+        use rendy_core::hal::buffer::Access;
+
+        let (mut transfer_uploads, mut family_uploads) =
+            self.lock_with_transfer(transfer, next.queue);
+
+        let families =
+            Some(QueueFamilyId::from(transfer.family)..QueueFamilyId::from(next.queue.family));
+        let range = Some(offset)..Some(offset + staging.size);
+
+        let transfer_upload = transfer_uploads.next_upload(device, transfer.index)?;
+        transfer_upload.release_to(device, next.queue)?;
+        let mut encoder = transfer_upload.command_buffer.encoder();
+        encoder.copy_buffer(
+            staging.buffer.raw(),
+            buffer.raw(),
+            Some(rendy_core::hal::command::BufferCopy {
+                src: 0,
+                dst: offset,
+                size: staging.size,
+            }),
+        );
+        encoder.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+            Dependencies::empty(),
+            Some(Barrier::Buffer {
+                states: Access::TRANSFER_WRITE..Access::empty(),
+                target: buffer.raw(),
+                families: families.clone(),
+                range: range.clone(),
+            }),
+        );
+        transfer_upload.staging_buffers.push(staging);
+
+        let next_upload = family_uploads.next_upload(device, next.queue.index)?;
+        next_upload.barrier_buffer.encoder().pipeline_barrier(
+            PipelineStage::TOP_OF_PIPE..next.stage,
+            Dependencies::empty(),
+            Some(Barrier::Buffer {
+                states: Access::empty()..next.access,
+                target: buffer.raw(),
+                families,
+                range,
+            }),
+        );
+
+        Ok(next_upload.token())
     }
 
     /// # Safety
@@ -273,85 +648,104 @@ where
         image_layers: rendy_core::hal::image::SubresourceLayers,
         image_offset: rendy_core::hal::image::Offset,
         image_extent: rendy_core::hal::image::Extent,
-        staging: Escape<Buffer<B>>,
+        staging: StagingBuffer<B>,
         last: ImageStateOrLayout,
         next: ImageState,
-    ) -> Result<(), OutOfMemory> {
+    ) -> Result<UploadToken, OutOfMemory> {
         use rendy_core::hal::image::{Access, Layout};
 
-        let mut family_uploads = self.family_uploads[next.queue.family.index]
-            .as_ref()
-            .unwrap()
-            .lock();
+        let (image_range, whole_level) =
+            image_upload_range(&image, &image_layers, image_offset, image_extent);
 
-        let whole_extent = if image_layers.level == 0 {
-            image.kind().extent()
-        } else {
-            image.kind().level_extent(image_layers.level)
-        };
+        if let (Some(transfer), ImageStateOrLayout::Layout(last_layout)) =
+            (self.transfer_queue(next.queue), last)
+        {
+            let last_layout = if whole_level {
+                Layout::Undefined
+            } else {
+                last_layout
+            };
 
-        let whole_level =
-            image_offset == rendy_core::hal::image::Offset::ZERO && image_extent == whole_extent;
+            let (mut transfer_uploads, mut family_uploads) =
+                self.lock_with_transfer(transfer, next.queue);
 
-        let image_range = rendy_core::hal::image::SubresourceRange {
-            aspects: image_layers.aspects,
-            levels: image_layers.level..image_layers.level + 1,
-            layers: image_layers.layers.clone(),
-        };
+            let families =
+                Some(QueueFamilyId::from(transfer.family)..QueueFamilyId::from(next.queue.family));
 
-        let (last_stage, mut last_access, last_layout) = match last.into() {
-            ImageStateOrLayout::State(last) => {
-                if last.queue != next.queue {
-                    unimplemented!("Can't sync resources across queues");
-                }
-                (
-                    last.stage,
-                    last.access,
-                    if whole_level {
-                        Layout::Undefined
-                    } else {
-                        last.layout
-                    },
-                )
-            }
-            ImageStateOrLayout::Layout(last_layout) => (
-                rendy_core::hal::pso::PipelineStage::TOP_OF_PIPE,
-                Access::empty(),
-                if whole_level {
-                    Layout::Undefined
-                } else {
-                    last_layout
-                },
-            ),
-        };
+            let transfer_upload = transfer_uploads.next_upload(device, transfer.index)?;
+            transfer_upload.release_to(device, next.queue)?;
+            let mut encoder = transfer_upload.command_buffer.encoder();
+            encoder.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                Some(Barrier::Image {
+                    states: (Access::empty(), last_layout)
+                        ..(Access::TRANSFER_WRITE, Layout::TransferDstOptimal),
+                    target: image.raw(),
+                    families: None,
+                    range: image_range.clone(),
+                }),
+            );
+            encoder.copy_buffer_to_image(
+                staging.buffer.raw(),
+                image.raw(),
+                Layout::TransferDstOptimal,
+                Some(rendy_core::hal::command::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: data_width,
+                    buffer_height: data_height,
+                    image_layers,
+                    image_offset,
+                    image_extent,
+                }),
+            );
+            encoder.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+                Dependencies::empty(),
+                Some(Barrier::Image {
+                    states: (Access::TRANSFER_WRITE, Layout::TransferDstOptimal)
+                        ..(Access::empty(), next.layout),
+                    target: image.raw(),
+                    families: families.clone(),
+                    range: image_range.clone(),
+                }),
+            );
+            transfer_upload.staging_buffers.push(staging);
 
-        let target_layout = match (last_layout, next.layout) {
-            (Layout::TransferDstOptimal, _) => Layout::TransferDstOptimal,
-            (_, Layout::General) => Layout::General,
-            (Layout::General, _) => Layout::General,
-            _ => Layout::TransferDstOptimal,
-        };
+            let next_upload = family_uploads.next_upload(device, next.queue.index)?;
+            next_upload.barrier_buffer.encoder().pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..next.stage,
+                Dependencies::empty(),
+                Some(Barrier::Image {
+                    states: (Access::empty(), Layout::TransferDstOptimal)
+                        ..(next.access, next.layout),
+                    target: image.raw(),
+                    families,
+                    range: image_range,
+                }),
+            );
 
-        if last_layout == Layout::Undefined || last_layout == target_layout {
-            last_access = Access::empty();
+            return Ok(next_upload.token());
         }
 
-        family_uploads.barriers.add_image(
+        let mut family_uploads = self.family_uploads[next.queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        let target_layout = add_image_upload_barriers(
+            &mut family_uploads.barriers,
             image.clone(),
             image_range,
-            last_stage,
-            last_access,
-            last_layout,
-            target_layout,
-            next.stage,
-            next.access,
-            next.layout,
+            whole_level,
+            last,
+            next,
         );
 
         let next_upload = family_uploads.next_upload(device, next.queue.index)?;
         let mut encoder = next_upload.command_buffer.encoder();
         encoder.copy_buffer_to_image(
-            staging.raw(),
+            staging.buffer.raw(),
             image.raw(),
             target_layout,
             Some(rendy_core::hal::command::BufferImageCopy {
@@ -365,21 +759,180 @@ where
         );
 
         next_upload.staging_buffers.push(staging);
-        Ok(())
+        Ok(next_upload.token())
+    }
+
+    /// Record updates of the batch into single command buffer and submit it.
+    /// `staging` must have content of the batch and is `None` only for empty batch.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Uploader`.
+    /// `family` must be the family of the batch queue.
+    /// Resources of the batch and `staging` must belong to the `device`.
+    ///
+    pub(crate) unsafe fn submit_batch(
+        &self,
+        device: &Device<B>,
+        family: &mut Family<B>,
+        batch: UploadBatch<'_, B>,
+        staging: Option<StagingBuffer<B>>,
+    ) -> Result<UploadToken, OutOfMemory> {
+        let queue = batch.queue();
+        assert_eq!(family.id(), queue.family);
+
+        let mut family_uploads = self.family_uploads[queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        let mut barriers = Barriers::new(
+            PipelineStage::TRANSFER,
+            rendy_core::hal::buffer::Access::TRANSFER_WRITE,
+            rendy_core::hal::image::Access::TRANSFER_WRITE,
+        );
+
+        for upload in &batch.buffers {
+            add_buffer_upload_barriers(&mut barriers, upload.last, upload.next);
+        }
+
+        let target_layouts = batch
+            .images
+            .iter()
+            .map(|upload| {
+                let (image_range, whole_level) = image_upload_range(
+                    &upload.image,
+                    &upload.image_layers,
+                    upload.image_offset,
+                    upload.image_extent,
+                );
+                add_image_upload_barriers(
+                    &mut barriers,
+                    upload.image.clone(),
+                    image_range,
+                    whole_level,
+                    upload.last,
+                    upload.next,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut fence = family_uploads.fence(device)?;
+        let mut command_buffer = family_uploads.command_buffer().begin(OneShot, ());
+        let mut encoder = command_buffer.encoder();
+
+        barriers.encode_before(&mut encoder);
+        if let Some(staging) = &staging {
+            for upload in &batch.buffers {
+                encoder.copy_buffer(
+                    staging.buffer.raw(),
+                    upload.buffer.raw(),
+                    Some(rendy_core::hal::command::BufferCopy {
+                        src: upload.staging_offset,
+                        dst: upload.offset,
+                        size: upload.content.len() as u64,
+                    }),
+                );
+            }
+            for (upload, &target_layout) in batch.images.iter().zip(&target_layouts) {
+                encoder.copy_buffer_to_image(
+                    staging.buffer.raw(),
+                    upload.image.raw(),
+                    target_layout,
+                    Some(rendy_core::hal::command::BufferImageCopy {
+                        buffer_offset: upload.staging_offset,
+                        buffer_width: upload.data_width,
+                        buffer_height: upload.data_height,
+                        image_layers: upload.image_layers.clone(),
+                        image_offset: upload.image_offset,
+                        image_extent: upload.image_extent,
+                    }),
+                );
+            }
+        }
+        barriers.encode_after(&mut encoder);
+
+        let (submit, command_buffer) = command_buffer.finish().submit_once();
+        family.queue_mut(queue.index).submit(
+            Some(Submission::new().submits(once(submit))),
+            Some(&mut fence),
+        );
+
+        let batch = Arc::new(BatchState::new(queue));
+        batch.epoch.store(fence.epoch().epoch, Ordering::Release);
+
+        family_uploads.pending.push_back(PendingUploads {
+            barrier_buffer: None,
+            command_buffer,
+            staging_buffers: staging.into_iter().collect(),
+            semaphores: Vec::new(),
+            fence,
+            batch: batch.clone(),
+        });
+
+        Ok(UploadToken { batch })
+    }
+
+    /// Wait for the upload to complete.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Uploader`.
+    ///
+    pub(crate) unsafe fn wait(
+        &self,
+        device: &Device<B>,
+        token: &UploadToken,
+        timeout_ns: u64,
+    ) -> Result<Option<FenceEpoch>, OomOrDeviceLost> {
+        if token.is_complete() {
+            return Ok(token.epoch());
+        }
+
+        let family_uploads = self.family_uploads[token.batch.queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+
+        let pending = family_uploads
+            .pending
+            .iter()
+            .find(|pending| Arc::ptr_eq(&pending.batch, &token.batch));
+
+        if let Some(pending) = pending {
+            if device.wait_for_fence(pending.fence.raw(), timeout_ns)? {
+                pending.batch.complete.store(true, Ordering::Release);
+            }
+        }
+
+        if token.is_complete() {
+            Ok(token.epoch())
+        } else {
+            Ok(None)
+        }
     }
 
     /// Cleanup pending updates.
+    /// Calls `complete` with epochs of signaled fences.
     ///
     /// # Safety
     ///
     /// `device` must be the same that was used to create this `Uploader`.
     ///
-    pub(crate) unsafe fn cleanup(&mut self, device: &Device<B>) {
+    pub(crate) unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        mut complete: impl FnMut(FenceEpoch),
+    ) {
+        let staging_pool = self.staging_pool.get_mut();
         for uploader in self.family_uploads.iter_mut() {
             if let Some(uploader) = uploader {
-                uploader.get_mut().cleanup(device);
+                uploader
+                    .get_mut()
+                    .cleanup(device, &mut complete, staging_pool);
             }
         }
+        staging_pool.trim();
     }
 
     /// Flush new updates.
@@ -389,6 +942,27 @@ where
     /// `families` must be the same that was used to create this `Uploader`.
     ///
     pub(crate) unsafe fn flush(&mut self, families: &mut Families<B>) {
+        // Ownership releases must be submitted before acquires that wait for them.
+        for family in families.as_slice_mut() {
+            let releases = self.family_uploads[family.id().index]
+                .as_mut()
+                .expect("Uploader must be initialized for all families")
+                .get_mut()
+                .flush_releases(family);
+
+            for (queue, semaphore) in releases {
+                self.family_uploads[queue.family.index]
+                    .as_mut()
+                    .expect("Uploader must be initialized for all families")
+                    .get_mut()
+                    .next[queue.index]
+                    .as_mut()
+                    .expect("Ownership acquire must be recorded")
+                    .waits
+                    .push(semaphore);
+            }
+        }
+
         for family in families.as_slice_mut() {
             let uploader = self.family_uploads[family.id().index]
                 .as_mut()
@@ -406,26 +980,181 @@ where
         self.family_uploads.drain(..).for_each(|fu| {
             fu.map(|fu| fu.into_inner().dispose(device));
         });
+
+        // Free staging buffers must be released before resources are disposed.
+        self.staging_pool.get_mut().classes.clear();
+    }
+}
+
+/// Add barriers for buffer upload recorded between them.
+fn add_buffer_upload_barriers<B>(
+    barriers: &mut Barriers<B>,
+    last: Option<BufferState>,
+    next: BufferState,
+) where
+    B: rendy_core::hal::Backend,
+{
+    if let Some(last) = last {
+        if last.queue != next.queue {
+            unimplemented!("Can't sync resources across queues");
+        }
+    }
+
+    barriers.add_buffer(
+        last.map_or(rendy_core::hal::pso::PipelineStage::empty(), |l| l.stage),
+        rendy_core::hal::buffer::Access::empty(),
+        next.stage,
+        next.access,
+    );
+}
+
+/// Check that image can be updated with content of the size.
+pub(crate) fn check_image_upload<B>(
+    image: &Image<B>,
+    image_layers: &rendy_core::hal::image::SubresourceLayers,
+    image_extent: rendy_core::hal::image::Extent,
+    content_size: u64,
+) where
+    B: rendy_core::hal::Backend,
+{
+    assert!(image
+        .info()
+        .usage
+        .contains(rendy_core::hal::image::Usage::TRANSFER_DST));
+    assert_eq!(image.format().surface_desc().aspects, image_layers.aspects);
+    assert!(image_layers.layers.start <= image_layers.layers.end);
+    assert!(image_layers.layers.end <= image.kind().num_layers());
+    assert!(image_layers.level < image.info().levels);
+
+    let format_desc = image.format().surface_desc();
+    let texels_count = (image_extent.width / format_desc.dim.0 as u32) as u64
+        * (image_extent.height / format_desc.dim.1 as u32) as u64
+        * image_extent.depth as u64
+        * (image_layers.layers.end - image_layers.layers.start) as u64;
+    let total_bytes = (format_desc.bits as u64 / 8) * texels_count;
+    assert_eq!(
+        total_bytes, content_size,
+        "Size of must match size of the image region"
+    );
+}
+
+/// Get subresource range of image upload.
+/// Also checks if upload covers whole mip level.
+fn image_upload_range<B>(
+    image: &Image<B>,
+    image_layers: &rendy_core::hal::image::SubresourceLayers,
+    image_offset: rendy_core::hal::image::Offset,
+    image_extent: rendy_core::hal::image::Extent,
+) -> (rendy_core::hal::image::SubresourceRange, bool)
+where
+    B: rendy_core::hal::Backend,
+{
+    let whole_extent = if image_layers.level == 0 {
+        image.kind().extent()
+    } else {
+        image.kind().level_extent(image_layers.level)
+    };
+
+    let whole_level =
+        image_offset == rendy_core::hal::image::Offset::ZERO && image_extent == whole_extent;
+
+    let image_range = rendy_core::hal::image::SubresourceRange {
+        aspects: image_layers.aspects,
+        levels: image_layers.level..image_layers.level + 1,
+        layers: image_layers.layers.clone(),
+    };
+
+    (image_range, whole_level)
+}
+
+/// Add barriers for image upload recorded between them.
+/// Returns layout image must be in for the copy.
+fn add_image_upload_barriers<B>(
+    barriers: &mut Barriers<B>,
+    image: Handle<Image<B>>,
+    image_range: rendy_core::hal::image::SubresourceRange,
+    whole_level: bool,
+    last: ImageStateOrLayout,
+    next: ImageState,
+) -> rendy_core::hal::image::Layout
+where
+    B: rendy_core::hal::Backend,
+{
+    use rendy_core::hal::image::{Access, Layout};
+
+    let (last_stage, mut last_access, last_layout) = match last {
+        ImageStateOrLayout::State(last) => {
+            if last.queue != next.queue {
+                unimplemented!("Can't sync resources across queues");
+            }
+            (
+                last.stage,
+                last.access,
+                if whole_level {
+                    Layout::Undefined
+                } else {
+                    last.layout
+                },
+            )
+        }
+        ImageStateOrLayout::Layout(last_layout) => (
+            rendy_core::hal::pso::PipelineStage::TOP_OF_PIPE,
+            Access::empty(),
+            if whole_level {
+                Layout::Undefined
+            } else {
+                last_layout
+            },
+        ),
+    };
+
+    let target_layout = match (last_layout, next.layout) {
+        (Layout::TransferDstOptimal, _) => Layout::TransferDstOptimal,
+        (_, Layout::General) => Layout::General,
+        (Layout::General, _) => Layout::General,
+        _ => Layout::TransferDstOptimal,
+    };
+
+    if last_layout == Layout::Undefined || last_layout == target_layout {
+        last_access = Access::empty();
     }
+
+    barriers.add_image(
+        image,
+        image_range,
+        last_stage,
+        last_access,
+        last_layout,
+        target_layout,
+        next.stage,
+        next.access,
+        next.layout,
+    );
+
+    target_layout
 }
 
 #[derive(Debug)]
 pub(crate) struct FamilyUploads<B: rendy_core::hal::Backend> {
     pool: CommandPool<B, Transfer, IndividualReset>,
-    command_buffers:
-        Vec<[CommandBuffer<B, Transfer, InitialState, PrimaryLevel, IndividualReset>; 2]>,
+    command_buffers: Vec<CommandBuffer<B, Transfer, InitialState, PrimaryLevel, IndividualReset>>,
     next: Vec<Option<NextUploads<B>>>,
     pending: VecDeque<PendingUploads<B>>,
-    fences: Vec<B::Fence>,
+    fences: Vec<Fence<B>>,
     barriers: Barriers<B>,
 }
 
 #[derive(Debug)]
 pub(crate) struct PendingUploads<B: rendy_core::hal::Backend> {
-    barrier_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
+    /// Buffer with barriers recorded before uploads.
+    /// Batches record barriers into the command buffer.
+    barrier_buffer:
+        Option<CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>>,
     command_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
-    staging_buffers: Vec<Escape<Buffer<B>>>,
-    fence: B::Fence,
+    staging_buffers: Vec<StagingBuffer<B>>,
+    semaphores: Vec<B::Semaphore>,
+    fence: Fence<B>,
+    batch: Arc<BatchState>,
 }
 
 #[derive(Debug)]
@@ -434,8 +1163,34 @@ struct NextUploads<B: rendy_core::hal::Backend> {
         CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
     command_buffer:
         CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
-    staging_buffers: Vec<Escape<Buffer<B>>>,
-    fence: B::Fence,
+    staging_buffers: Vec<StagingBuffer<B>>,
+    fence: Fence<B>,
+    batch: Arc<BatchState>,
+
+    /// Semaphores signaled by ownership releases to this queue.
+    waits: Vec<B::Semaphore>,
+
+    /// Semaphores to signal for queues that acquire ownership released by this queue.
+    signals: Vec<(QueueId, B::Semaphore)>,
+}
+
+impl<B> NextUploads<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    fn token(&self) -> UploadToken {
+        UploadToken {
+            batch: self.batch.clone(),
+        }
+    }
+
+    /// Make sure semaphore is signaled for `queue` that acquires released ownership.
+    unsafe fn release_to(&mut self, device: &Device<B>, queue: QueueId) -> Result<(), OutOfMemory> {
+        if self.signals.iter().all(|&(signal, _)| signal != queue) {
+            self.signals.push((queue, device.create_semaphore()?));
+        }
+        Ok(())
+    }
 }
 
 impl<B> FamilyUploads<B>
@@ -443,33 +1198,77 @@ where
     B: rendy_core::hal::Backend,
 {
     unsafe fn flush(&mut self, family: &mut Family<B>) {
-        for (queue, mut next) in self
-            .next
-            .drain(..)
-            .enumerate()
-            .filter_map(|(i, x)| x.map(|x| (i, x)))
-        {
-            let mut barriers_encoder = next.barrier_buffer.encoder();
-            let mut encoder = next.command_buffer.encoder();
+        for queue in 0..self.next.len() {
+            if let Some(next) = self.next[queue].take() {
+                let releases = self.submit(family, queue, next);
+                assert!(
+                    releases.is_empty(),
+                    "Ownership releases must be flushed first"
+                );
+            }
+        }
+    }
 
-            self.barriers.encode_before(&mut barriers_encoder);
-            self.barriers.encode_after(&mut encoder);
+    /// Flush uploads of queues that release ownership to other families.
+    /// Returns semaphores to wait on acquiring queues.
+    unsafe fn flush_releases(&mut self, family: &mut Family<B>) -> Vec<(QueueId, B::Semaphore)> {
+        let mut releases = Vec::new();
+        for queue in 0..self.next.len() {
+            let has_releases = self.next[queue]
+                .as_ref()
+                .map_or(false, |next| !next.signals.is_empty());
 
-            let (barriers_submit, barrier_buffer) = next.barrier_buffer.finish().submit_once();
-            let (submit, command_buffer) = next.command_buffer.finish().submit_once();
+            if has_releases {
+                let next = self.next[queue].take().unwrap();
+                releases.extend(self.submit(family, queue, next));
+            }
+        }
+        releases
+    }
 
-            family.queue_mut(queue).submit_raw_fence(
-                Some(Submission::new().submits(once(barriers_submit).chain(once(submit)))),
-                Some(&next.fence),
-            );
+    unsafe fn submit(
+        &mut self,
+        family: &mut Family<B>,
+        queue: usize,
+        mut next: NextUploads<B>,
+    ) -> Vec<(QueueId, B::Semaphore)> {
+        let mut barriers_encoder = next.barrier_buffer.encoder();
+        let mut encoder = next.command_buffer.encoder();
 
-            self.pending.push_back(PendingUploads {
-                barrier_buffer,
-                command_buffer,
-                staging_buffers: next.staging_buffers,
-                fence: next.fence,
-            });
-        }
+        self.barriers.encode_before(&mut barriers_encoder);
+        self.barriers.encode_after(&mut encoder);
+
+        let (barriers_submit, barrier_buffer) = next.barrier_buffer.finish().submit_once();
+        let (submit, command_buffer) = next.command_buffer.finish().submit_once();
+
+        family.queue_mut(queue).submit(
+            Some(
+                Submission::new()
+                    .wait(
+                        next.waits
+                            .iter()
+                            .map(|semaphore| (semaphore, PipelineStage::TOP_OF_PIPE)),
+                    )
+                    .submits(once(barriers_submit).chain(once(submit)))
+                    .signal(next.signals.iter().map(|(_, semaphore)| semaphore)),
+            ),
+            Some(&mut next.fence),
+        );
+
+        next.batch
+            .epoch
+            .store(next.fence.epoch().epoch, Ordering::Release);
+
+        self.pending.push_back(PendingUploads {
+            barrier_buffer: Some(barrier_buffer),
+            command_buffer,
+            staging_buffers: next.staging_buffers,
+            semaphores: next.waits,
+            fence: next.fence,
+            batch: next.batch,
+        });
+
+        next.signals
     }
 
     unsafe fn next_upload(
@@ -481,29 +1280,41 @@ where
             self.next.push(None);
         }
 
+        if self.next[queue].is_none() {
+            let fence = self.fence(device)?;
+            let buf_a = self.command_buffer();
+            let buf_b = self.command_buffer();
+            let family = self.pool.family_id();
+            self.next[queue] = Some(NextUploads {
+                barrier_buffer: buf_a.begin(OneShot, ()),
+                command_buffer: buf_b.begin(OneShot, ()),
+                staging_buffers: Vec::new(),
+                fence,
+                batch: Arc::new(BatchState::new(QueueId {
+                    family,
+                    index: queue,
+                })),
+                waits: Vec::new(),
+                signals: Vec::new(),
+            });
+        }
+
+        Ok(self.next[queue].as_mut().unwrap())
+    }
+
+    fn command_buffer(
+        &mut self,
+    ) -> CommandBuffer<B, Transfer, InitialState, PrimaryLevel, IndividualReset> {
         let pool = &mut self.pool;
+        self.command_buffers
+            .pop()
+            .unwrap_or_else(|| pool.allocate_buffers(1).remove(0))
+    }
 
-        match &mut self.next[queue] {
-            Some(next) => Ok(next),
-            slot @ None => {
-                let [buf_a, buf_b] = self.command_buffers.pop().unwrap_or_else(|| {
-                    let mut bufs = pool.allocate_buffers(2);
-                    [bufs.remove(1), bufs.remove(0)]
-                });
-                let fence = self
-                    .fences
-                    .pop()
-                    .map_or_else(|| device.create_fence(false), Ok)?;
-                *slot = Some(NextUploads {
-                    barrier_buffer: buf_a.begin(OneShot, ()),
-                    command_buffer: buf_b.begin(OneShot, ()),
-                    staging_buffers: Vec::new(),
-                    fence,
-                });
-
-                Ok(slot.as_mut().unwrap())
-            }
-        }
+    unsafe fn fence(&mut self, device: &Device<B>) -> Result<Fence<B>, OutOfMemory> {
+        self.fences
+            .pop()
+            .map_or_else(|| Fence::new(device, false), Ok)
     }
 
     /// Cleanup pending updates.
@@ -512,25 +1323,43 @@ where
     ///
     /// `device` must be the same that was used with other methods of this instance.
     ///
-    unsafe fn cleanup(&mut self, device: &Device<B>) {
-        while let Some(pending) = self.pending.pop_front() {
-            match device.get_fence_status(&pending.fence) {
-                Ok(false) => {
+    unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        complete: &mut impl FnMut(FenceEpoch),
+        staging_pool: &mut StagingPool<B>,
+    ) {
+        while let Some(mut pending) = self.pending.pop_front() {
+            match pending.fence.check_signaled(device) {
+                Ok(None) => {
                     self.pending.push_front(pending);
                     return;
                 }
                 Err(rendy_core::hal::device::DeviceLost) => {
                     panic!("Device lost error is not handled yet");
                 }
-                Ok(true) => {
-                    device
-                        .reset_fence(&pending.fence)
+                Ok(Some(epoch)) => {
+                    pending.batch.complete.store(true, Ordering::Release);
+                    complete(epoch);
+                    pending
+                        .fence
+                        .reset(device)
                         .expect("Can always reset signalled fence");
                     self.fences.push(pending.fence);
-                    self.command_buffers.push([
-                        pending.command_buffer.mark_complete().reset(),
-                        pending.barrier_buffer.mark_complete().reset(),
-                    ]);
+                    for semaphore in pending.semaphores {
+                        device.destroy_semaphore(semaphore);
+                    }
+                    for staging in pending.staging_buffers {
+                        if staging.pooled {
+                            staging_pool.recycle(staging.buffer);
+                        }
+                    }
+                    self.command_buffers
+                        .push(pending.command_buffer.mark_complete().reset());
+                    if let Some(barrier_buffer) = pending.barrier_buffer {
+                        self.command_buffers
+                            .push(barrier_buffer.mark_complete().reset());
+                    }
                 }
             }
         }
@@ -542,26 +1371,80 @@ where
     ///
     unsafe fn dispose(mut self, device: &Device<B>) {
         let pool = &mut self.pool;
-        self.pending.drain(..).for_each(|pending| {
-            device.destroy_fence(pending.fence);
+        self.pending.drain(..).for_each(|mut pending| {
+            pending.fence.mark_signaled();
+            device.destroy_fence(pending.fence.into_inner());
+            for semaphore in pending.semaphores {
+                device.destroy_semaphore(semaphore);
+            }
             pool.free_buffers(Some(pending.command_buffer.mark_complete()));
-            pool.free_buffers(Some(pending.barrier_buffer.mark_complete()));
+            pool.free_buffers(
+                pending
+                    .barrier_buffer
+                    .map(|barrier_buffer| barrier_buffer.mark_complete()),
+            );
         });
 
         self.fences
             .drain(..)
-            .for_each(|fence| device.destroy_fence(fence));
-        pool.free_buffers(
-            self.command_buffers
-                .drain(..)
-                .flat_map(|[a, b]| once(a).chain(once(b))),
-        );
+            .for_each(|fence| device.destroy_fence(fence.into_inner()));
+        pool.free_buffers(self.command_buffers.drain(..));
 
         pool.free_buffers(self.next.drain(..).filter_map(|n| n).flat_map(|next| {
-            device.destroy_fence(next.fence);
+            device.destroy_fence(next.fence.into_inner());
+            for semaphore in next
+                .waits
+                .into_iter()
+                .chain(next.signals.into_iter().map(|(_, semaphore)| semaphore))
+            {
+                device.destroy_semaphore(semaphore);
+            }
             once(next.command_buffer).chain(once(next.barrier_buffer))
         }));
         drop(pool);
         self.pool.dispose(device);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_staging_class() {
+        let config = StagingPoolConfig {
+            min_size: 1024,
+            max_size: 16 * 1024,
+            max_free: 64 * 1024,
+        };
+
+        assert_eq!(config.class(0), Some(0));
+        assert_eq!(config.class(1), Some(0));
+        assert_eq!(config.class(1024), Some(0));
+        assert_eq!(config.class(1025), Some(1));
+        assert_eq!(config.class(4096), Some(2));
+        assert_eq!(config.class(4097), Some(3));
+        assert_eq!(config.class(16 * 1024), Some(4));
+        assert_eq!(config.class(16 * 1024 + 1), None);
+
+        for class in 0..5 {
+            let size = config.class_size(class);
+            assert_eq!(config.class(size), Some(class));
+            assert_eq!(config.class(size - size / 4), Some(class));
+        }
+    }
+
+    #[test]
+    fn test_staging_trim() {
+        // Free buffers above recent demand are dropped.
+        assert_eq!(free_to_keep(4, 1, 3, 1024, u64::max_value()), 2);
+        assert_eq!(free_to_keep(4, 3, 3, 1024, u64::max_value()), 0);
+        assert_eq!(free_to_keep(4, 5, 3, 1024, u64::max_value()), 0);
+        assert_eq!(free_to_keep(1, 0, 3, 1024, u64::max_value()), 1);
+
+        // Free buffers must fit into what is left of the budget.
+        assert_eq!(free_to_keep(4, 0, 4, 1024, 2048), 2);
+        assert_eq!(free_to_keep(4, 0, 4, 1024, 2047), 1);
+        assert_eq!(free_to_keep(4, 0, 4, 1024, 0), 0);
+    }
+}
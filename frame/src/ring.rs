@@ -0,0 +1,65 @@
+//! Ring of memory for data used by a single frame.
+
+use crate::{
+    factory::Factory,
+    frame::{CompleteFrame, Frames},
+    memory::{HeapsError, MemoryUsage, RingAllocator, RingBlock, RingConfig},
+};
+
+/// Ring of host visible memory for transient per-frame data, like uniforms.
+///
+/// Blocks are allocated for the next frame of `Frames`
+/// and reclaimed all at once after that frame is complete,
+/// so there is no need to keep separate buffers for each frame in flight.
+/// Memory of the ring is accounted by the `Factory` memory heaps.
+#[derive(Debug)]
+pub struct FrameRing<B: rendy_core::hal::Backend> {
+    allocator: RingAllocator<B>,
+}
+
+impl<B> FrameRing<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Create new ring in memory suitable for `usage`.
+    pub fn new(
+        factory: &Factory<B>,
+        usage: impl MemoryUsage,
+        config: RingConfig,
+    ) -> Result<Self, HeapsError> {
+        Ok(FrameRing {
+            allocator: factory.create_ring_allocator(usage, config)?,
+        })
+    }
+
+    /// Allocate block for the next frame of `frames`.
+    /// Block must not be used after that frame is complete.
+    ///
+    /// Blocks of frames already complete in `frames` timeline are reclaimed first.
+    pub fn allocate(
+        &mut self,
+        factory: &Factory<B>,
+        frames: &Frames<B>,
+        size: u64,
+        align: u64,
+    ) -> Result<RingBlock<B>, HeapsError> {
+        self.cleanup(factory, frames);
+        factory.allocate_from_ring(&mut self.allocator, frames.next().index(), size, align)
+    }
+
+    /// Reclaim blocks of all frames up to and including complete one.
+    pub fn complete(&mut self, factory: &Factory<B>, complete: &CompleteFrame) {
+        factory.complete_ring(&mut self.allocator, complete.index() + 1);
+    }
+
+    /// Reclaim blocks of all frames that are complete in `frames` timeline.
+    pub fn cleanup(&mut self, factory: &Factory<B>, frames: &Frames<B>) {
+        factory.complete_ring(&mut self.allocator, frames.complete_upper_bound());
+    }
+
+    /// Dispose of the ring.
+    /// All frames blocks were allocated for must be complete.
+    pub fn dispose(self, factory: &Factory<B>) {
+        factory.dispose_ring_allocator(self.allocator);
+    }
+}
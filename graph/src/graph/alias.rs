@@ -0,0 +1,430 @@
+//! Placement of transient resources into shared memory blocks.
+//!
+//! Resources whose lifetimes within a frame never overlap can occupy the same memory.
+//! Lifetime of the resource spans from the first to the last submission that uses it,
+//! in the order submissions are submitted.
+//! Only resources used on a single queue are aliased, so that submission order
+//! is also execution order and pipeline barriers are enough to separate occupants.
+
+use {
+    crate::chain,
+    rendy_core::hal::{memory::Requirements, pso::PipelineStage},
+};
+
+/// Lifetime of the resource within the frame.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Lifetime {
+    queue: chain::QueueId,
+    start: usize,
+    end: usize,
+    first: chain::SubmissionId,
+    last: chain::SubmissionId,
+}
+
+impl Lifetime {
+    /// Find lifetime of the resource from its chain.
+    /// Returns `None` if resource is used on more than one queue.
+    pub(super) fn of<R, S>(chain: &chain::Chain<R>, schedule: &chain::Schedule<S>) -> Option<Self>
+    where
+        R: chain::Resource,
+    {
+        let mut lifetime: Option<Lifetime> = None;
+
+        for link in chain.links() {
+            for (qid, queue) in link.queues() {
+                let first = chain::SubmissionId::new(qid, queue.first);
+                let last = chain::SubmissionId::new(qid, queue.last);
+                let start = schedule[first].submit_order();
+                let end = schedule[last].submit_order();
+
+                match &mut lifetime {
+                    None => {
+                        lifetime = Some(Lifetime {
+                            queue: qid,
+                            start,
+                            end,
+                            first,
+                            last,
+                        })
+                    }
+                    Some(lifetime) => {
+                        if lifetime.queue != qid {
+                            return None;
+                        }
+                        if start < lifetime.start {
+                            lifetime.start = start;
+                            lifetime.first = first;
+                        }
+                        if end > lifetime.end {
+                            lifetime.end = end;
+                            lifetime.last = last;
+                        }
+                    }
+                }
+            }
+        }
+
+        lifetime
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// Resource that can be placed into shared memory.
+#[derive(Debug)]
+pub(super) struct Candidate {
+    /// Index of the resource.
+    pub(super) index: usize,
+
+    /// Lifetime of the resource.
+    pub(super) lifetime: Lifetime,
+
+    /// Memory requirements of the resource.
+    pub(super) requirements: Requirements,
+}
+
+/// Memory block shared by resources with non-overlapping lifetimes.
+#[derive(Debug)]
+pub(super) struct Slot {
+    /// Indices of candidates sorted by lifetime start.
+    pub(super) members: Vec<usize>,
+
+    /// Size of the memory block.
+    pub(super) size: u64,
+
+    /// Alignment of the memory block.
+    pub(super) align: u64,
+
+    /// Memory types suitable for all members.
+    pub(super) mask: u32,
+
+    queue: chain::QueueId,
+}
+
+/// Distribute candidates among slots.
+/// Biggest resources are placed first so that smaller ones fill the gaps in their lifetimes.
+pub(super) fn pack(candidates: &[Candidate]) -> Vec<Slot> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(candidates[index].requirements.size));
+
+    let mut slots: Vec<Slot> = Vec::new();
+
+    for index in order {
+        let candidate = &candidates[index];
+        let mask = candidate.requirements.type_mask as u32;
+
+        let fit = slots.iter_mut().find(|slot| {
+            slot.queue == candidate.lifetime.queue
+                && slot.mask & mask != 0
+                && slot
+                    .members
+                    .iter()
+                    .all(|&member| !candidates[member].lifetime.overlaps(&candidate.lifetime))
+        });
+
+        match fit {
+            Some(slot) => {
+                slot.members.push(index);
+                slot.size = slot.size.max(candidate.requirements.size);
+                slot.align = slot.align.max(candidate.requirements.alignment);
+                slot.mask &= mask;
+            }
+            None => slots.push(Slot {
+                members: vec![index],
+                size: candidate.requirements.size,
+                align: candidate.requirements.alignment,
+                mask,
+                queue: candidate.lifetime.queue,
+            }),
+        }
+    }
+
+    for slot in &mut slots {
+        slot.members
+            .sort_by_key(|&member| candidates[member].lifetime.start);
+    }
+
+    log::debug!(
+        "{} transient resources packed into {} memory blocks",
+        candidates.len(),
+        slots.len()
+    );
+
+    slots
+}
+
+/// Barrier that separates resource from the previous occupant of the shared memory.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Alias<A> {
+    /// Submission that uses the resource first.
+    /// Aliasing barrier must be inserted there.
+    pub(super) first: chain::SubmissionId,
+
+    /// Submission that uses the resource last.
+    /// Resource content is discarded afterwards.
+    pub(super) last: chain::SubmissionId,
+
+    /// Access performed on the memory by previous occupant.
+    pub(super) access: A,
+
+    /// Stages at which previous occupant accessed the memory.
+    pub(super) stages: PipelineStage,
+}
+
+/// Find aliasing barriers for members of the slot.
+/// Previous occupant of the first member is the last member from the previous frame.
+/// Slots with single member require no aliasing barriers.
+pub(super) fn aliases<R>(
+    slot: &Slot,
+    candidates: &[Candidate],
+    last_state: impl Fn(usize) -> (R::Access, PipelineStage),
+) -> Vec<(usize, Alias<R::Access>)>
+where
+    R: chain::Resource,
+{
+    if slot.members.len() < 2 {
+        return Vec::new();
+    }
+
+    slot.members
+        .iter()
+        .enumerate()
+        .map(|(i, &member)| {
+            let prev = slot.members[(i + slot.members.len() - 1) % slot.members.len()];
+            let (access, stages) = last_state(candidates[prev].index);
+            let lifetime = &candidates[member].lifetime;
+            (
+                candidates[member].index,
+                Alias {
+                    first: lifetime.first,
+                    last: lifetime.last,
+                    access,
+                    stages,
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        rendy_core::hal::{buffer, queue::QueueFamilyId},
+    };
+
+    fn queue(family: usize, index: usize) -> chain::QueueId {
+        chain::QueueId::new(QueueFamilyId(family), index)
+    }
+
+    /// Schedule with a submission on each queue, submitted in the order listed.
+    fn schedule(queues: &[chain::QueueId]) -> (chain::Schedule<()>, Vec<chain::SubmissionId>) {
+        let mut schedule = chain::Schedule::new();
+        let sids = queues
+            .iter()
+            .enumerate()
+            .map(|(order, &qid)| {
+                schedule
+                    .ensure_queue(qid)
+                    .add_submission(order, 0, order, ())
+            })
+            .collect();
+        schedule.build_order();
+        (schedule, sids)
+    }
+
+    /// Buffer chain with a link for each group of submissions.
+    fn chain(links: &[&[chain::SubmissionId]]) -> chain::Chain<chain::Buffer> {
+        let node = |sid| chain::LinkNode {
+            sid,
+            state: chain::State {
+                access: buffer::Access::SHADER_WRITE,
+                layout: (),
+                stages: PipelineStage::COMPUTE_SHADER,
+                usage: buffer::Usage::STORAGE,
+            },
+        };
+
+        let mut chain = chain::Chain::new();
+        for sids in links {
+            let mut link = chain::Link::new(node(sids[0]));
+            for &sid in &sids[1..] {
+                link.add_node(node(sid));
+            }
+            chain.add_link(link);
+        }
+        chain
+    }
+
+    fn candidate(index: usize, queue: chain::QueueId, start: usize, end: usize) -> Candidate {
+        Candidate {
+            index,
+            lifetime: Lifetime {
+                queue,
+                start,
+                end,
+                first: chain::SubmissionId::new(queue, start),
+                last: chain::SubmissionId::new(queue, end),
+            },
+            requirements: Requirements {
+                size: 256,
+                alignment: 16,
+                type_mask: 0b11,
+            },
+        }
+    }
+
+    #[test]
+    fn test_lifetime_spans_links() {
+        let q = queue(0, 0);
+        let (schedule, sids) = schedule(&[q, q, q, q]);
+        let lifetime = Lifetime::of(&chain(&[&[sids[1]], &[sids[2]]]), &schedule).unwrap();
+        assert_eq!((lifetime.start, lifetime.end), (1, 2));
+        assert_eq!((lifetime.first, lifetime.last), (sids[1], sids[2]));
+        assert_eq!(lifetime.queue, q);
+    }
+
+    #[test]
+    fn test_lifetime_follows_submit_order() {
+        // Submissions of other queue are interleaved.
+        let (q0, q1) = (queue(0, 0), queue(1, 0));
+        let (schedule, sids) = schedule(&[q0, q1, q0, q1, q0]);
+        let lifetime = Lifetime::of(&chain(&[&[sids[0], sids[2], sids[4]]]), &schedule).unwrap();
+        assert_eq!((lifetime.start, lifetime.end), (0, 4));
+        assert_eq!((lifetime.first, lifetime.last), (sids[0], sids[4]));
+    }
+
+    #[test]
+    fn test_lifetime_multiple_queues() {
+        let (q0, q1, q2) = (queue(0, 0), queue(0, 1), queue(1, 0));
+        let (schedule, sids) = schedule(&[q0, q1, q2]);
+        // Queues of the same family.
+        assert!(Lifetime::of(&chain(&[&[sids[0], sids[1]]]), &schedule).is_none());
+        // Queues of different families.
+        assert!(Lifetime::of(&chain(&[&[sids[0]], &[sids[2]]]), &schedule).is_none());
+    }
+
+    #[test]
+    fn test_lifetime_overlaps() {
+        let q = queue(0, 0);
+        let a = candidate(0, q, 0, 2).lifetime;
+        assert!(a.overlaps(&candidate(1, q, 2, 3).lifetime));
+        assert!(a.overlaps(&candidate(1, q, 1, 1).lifetime));
+        assert!(candidate(1, q, 1, 5).lifetime.overlaps(&a));
+        assert!(!a.overlaps(&candidate(1, q, 3, 4).lifetime));
+        assert!(!candidate(1, q, 3, 4).lifetime.overlaps(&a));
+    }
+
+    #[test]
+    fn test_pack_disjoint() {
+        let q = queue(0, 0);
+        let mut candidates = vec![candidate(0, q, 3, 4), candidate(1, q, 0, 1)];
+        candidates[1].requirements.size = 512;
+        candidates[0].requirements.alignment = 64;
+
+        let slots = pack(&candidates);
+        assert_eq!(slots.len(), 1);
+        // Members are sorted by lifetime start.
+        assert_eq!(slots[0].members, vec![1, 0]);
+        assert_eq!(slots[0].size, 512);
+        assert_eq!(slots[0].align, 64);
+    }
+
+    #[test]
+    fn test_pack_overlapping() {
+        let q = queue(0, 0);
+        let candidates = vec![candidate(0, q, 0, 2), candidate(1, q, 2, 4)];
+        assert_eq!(pack(&candidates).len(), 2);
+    }
+
+    #[test]
+    fn test_pack_fills_gaps() {
+        let q = queue(0, 0);
+        let mut candidates = vec![
+            candidate(0, q, 0, 1),
+            candidate(1, q, 2, 3),
+            candidate(2, q, 0, 3),
+        ];
+        candidates[2].requirements.size = 1024;
+
+        // Biggest one takes a slot alone, smaller ones share another.
+        let slots = pack(&candidates);
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].members, vec![2]);
+        assert_eq!(slots[1].members, vec![0, 1]);
+        assert_eq!(slots[1].size, 256);
+    }
+
+    #[test]
+    fn test_pack_restrictions() {
+        let (q0, q1) = (queue(0, 0), queue(0, 1));
+
+        // Resources used on different queues are never aliased.
+        let candidates = vec![candidate(0, q0, 0, 1), candidate(1, q1, 2, 3)];
+        assert_eq!(pack(&candidates).len(), 2);
+
+        // Memory types must intersect.
+        let mut candidates = vec![candidate(0, q0, 0, 1), candidate(1, q0, 2, 3)];
+        candidates[0].requirements.type_mask = 0b01;
+        candidates[1].requirements.type_mask = 0b10;
+        assert_eq!(pack(&candidates).len(), 2);
+
+        candidates[1].requirements.type_mask = 0b11;
+        let slots = pack(&candidates);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].mask, 0b01);
+    }
+
+    #[test]
+    fn test_aliases_wrap_around() {
+        let q = queue(0, 0);
+        let candidates = vec![
+            candidate(10, q, 0, 1),
+            candidate(11, q, 2, 3),
+            candidate(12, q, 4, 5),
+        ];
+        let slots = pack(&candidates);
+        assert_eq!(slots.len(), 1);
+
+        let last_state = |index: usize| match index {
+            10 => (buffer::Access::SHADER_WRITE, PipelineStage::COMPUTE_SHADER),
+            11 => (buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER),
+            12 => (buffer::Access::SHADER_READ, PipelineStage::FRAGMENT_SHADER),
+            _ => unreachable!(),
+        };
+        let aliases = aliases::<chain::Buffer>(&slots[0], &candidates, last_state);
+        assert_eq!(aliases.len(), 3);
+
+        // First member is preceded by the last one from the previous frame.
+        let (index, alias) = &aliases[0];
+        assert_eq!(*index, 10);
+        assert_eq!(alias.access, buffer::Access::SHADER_READ);
+        assert_eq!(alias.stages, PipelineStage::FRAGMENT_SHADER);
+        assert_eq!(
+            (alias.first, alias.last),
+            (candidates[0].lifetime.first, candidates[0].lifetime.last)
+        );
+
+        let (index, alias) = &aliases[1];
+        assert_eq!(*index, 11);
+        assert_eq!(alias.access, buffer::Access::SHADER_WRITE);
+        assert_eq!(alias.stages, PipelineStage::COMPUTE_SHADER);
+
+        let (index, alias) = &aliases[2];
+        assert_eq!(*index, 12);
+        assert_eq!(alias.access, buffer::Access::TRANSFER_WRITE);
+        assert_eq!(alias.stages, PipelineStage::TRANSFER);
+    }
+
+    #[test]
+    fn test_aliases_single_member() {
+        let candidates = vec![candidate(0, queue(0, 0), 0, 1)];
+        let slots = pack(&candidates);
+        let aliases = aliases::<chain::Buffer>(&slots[0], &candidates, |_| {
+            (buffer::Access::empty(), PipelineStage::empty())
+        });
+        assert!(aliases.is_empty());
+    }
+}
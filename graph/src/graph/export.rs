@@ -0,0 +1,482 @@
+//! Export of the built graph schedule for inspection.
+//!
+//! Both formats describe the same data:
+//! submissions in submit order with semaphore waits and signals and pipeline barriers,
+//! and links of every resource chain with access, layout and submissions using them.
+
+use {
+    super::{alias::Alias, Graph},
+    crate::{chain, memory},
+    rendy_core::hal::Backend,
+    std::io::{Result, Write},
+};
+
+impl<B, T> Graph<B, T>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    /// Write schedule of the graph in Graphviz DOT format.
+    ///
+    /// Submissions are grouped into clusters by queue.
+    /// Solid edges connect consecutive links of resource chains,
+    /// dashed edges connect semaphore signals to waits.
+    pub fn export_dot(&self, out: &mut impl Write) -> Result<()> {
+        write_dot(out, &self.schedule, &self.chains)
+    }
+
+    /// Write schedule of the graph as JSON document.
+    pub fn export_json(&self, out: &mut impl Write) -> Result<()> {
+        write_json(
+            out,
+            &self.schedule,
+            &self.chains,
+            &self.ctx.buffer_aliases,
+            &self.ctx.image_aliases,
+        )
+    }
+}
+
+type Schedule = chain::Schedule<chain::SyncData<usize, usize>>;
+
+fn write_dot(out: &mut impl Write, schedule: &Schedule, chains: &chain::Chains) -> Result<()> {
+    writeln!(out, "digraph schedule {{")?;
+    writeln!(out, "    node [shape=box];")?;
+
+    let mut queues: Vec<chain::QueueId> = schedule
+        .ordered()
+        .map(|submission| submission.id().queue())
+        .collect();
+    queues.sort_by_key(|qid| (qid.family().0, qid.index()));
+    queues.dedup();
+
+    for qid in queues {
+        writeln!(
+            out,
+            "    subgraph cluster_{}_{} {{",
+            qid.family().0,
+            qid.index()
+        )?;
+        writeln!(
+            out,
+            "        label=\"family {} queue {}\";",
+            qid.family().0,
+            qid.index()
+        )?;
+        for submission in schedule
+            .ordered()
+            .filter(|submission| submission.id().queue() == qid)
+        {
+            writeln!(
+                out,
+                "        {} [label=\"node {}\\norder {}\"];",
+                dot_id(submission.id()),
+                submission.node(),
+                submission.submit_order(),
+            )?;
+        }
+        writeln!(out, "    }}")?;
+    }
+
+    for (id, chain) in sorted(&chains.buffers) {
+        for (prev, next) in chain.links().iter().zip(&chain.links()[1..]) {
+            let label = format!("buffer {}\\n{:?}", id.0, next.access());
+            dot_link_edges(out, prev, next, &label)?;
+        }
+    }
+
+    for (id, chain) in sorted(&chains.images) {
+        for (prev, next) in chain.links().iter().zip(&chain.links()[1..]) {
+            let label = format!("image {}\\n{:?}\\n{:?}", id.0, next.access(), next.layout());
+            dot_link_edges(out, prev, next, &label)?;
+        }
+    }
+
+    for signal in schedule.ordered() {
+        for semaphore in signal.sync().signal.iter().map(|s| *s.semaphore()) {
+            for wait in schedule.ordered() {
+                for w in wait
+                    .sync()
+                    .wait
+                    .iter()
+                    .filter(|w| *w.semaphore() == semaphore)
+                {
+                    writeln!(
+                        out,
+                        "    {} -> {} [style=dashed, label=\"semaphore {}\\n{:?}\"];",
+                        dot_id(signal.id()),
+                        dot_id(wait.id()),
+                        semaphore,
+                        w.stage(),
+                    )?;
+                }
+            }
+        }
+    }
+
+    writeln!(out, "}}")
+}
+
+fn write_json(
+    out: &mut impl Write,
+    schedule: &Schedule,
+    chains: &chain::Chains,
+    buffer_aliases: &[Option<Alias<rendy_core::hal::buffer::Access>>],
+    image_aliases: &[Option<Alias<rendy_core::hal::image::Access>>],
+) -> Result<()> {
+    writeln!(out, "{{")?;
+
+    writeln!(out, "  \"submissions\": [")?;
+    for (i, submission) in schedule.ordered().enumerate() {
+        if i > 0 {
+            writeln!(out, ",")?;
+        }
+        let sync = submission.sync();
+        write!(
+            out,
+            "    {{\"node\": {}, \"queue\": {}, \"index\": {}, \"order\": {}, ",
+            submission.node(),
+            json_queue(submission.id().queue()),
+            submission.id().index(),
+            submission.submit_order(),
+        )?;
+        write!(out, "\"waits\": [")?;
+        for (j, wait) in sync.wait.iter().enumerate() {
+            if j > 0 {
+                write!(out, ", ")?;
+            }
+            write!(
+                out,
+                "{{\"semaphore\": {}, \"stages\": {}, \"previous_frame\": {}}}",
+                wait.semaphore(),
+                json_debug(wait.stage()),
+                wait.previous_frame(),
+            )?;
+        }
+        write!(out, "], \"signals\": [")?;
+        for (j, signal) in sync.signal.iter().enumerate() {
+            if j > 0 {
+                write!(out, ", ")?;
+            }
+            write!(out, "{}", signal.semaphore())?;
+        }
+        write!(out, "], \"acquire\": ")?;
+        json_guard(out, &sync.acquire)?;
+        write!(out, ", \"release\": ")?;
+        json_guard(out, &sync.release)?;
+        write!(out, "}}")?;
+    }
+    writeln!(out, "\n  ],")?;
+
+    writeln!(out, "  \"buffers\": [")?;
+    for (i, (id, chain)) in sorted(&chains.buffers).into_iter().enumerate() {
+        if i > 0 {
+            writeln!(out, ",")?;
+        }
+        write!(out, "    {{\"id\": {}, ", id.0)?;
+        if let Some(alias) = buffer_aliases.get(id.0).and_then(Option::as_ref) {
+            write!(
+                out,
+                "\"alias\": {{\"first\": {}, \"last\": {}}}, ",
+                json_submission(alias.first),
+                json_submission(alias.last)
+            )?;
+        }
+        write!(out, "\"links\": [")?;
+        for (j, link) in chain.links().iter().enumerate() {
+            if j > 0 {
+                write!(out, ", ")?;
+            }
+            write!(
+                out,
+                "{{\"family\": {}, \"access\": {}, \"usage\": {}, \"stages\": {}, \"submissions\": ",
+                link.family().0,
+                json_debug(link.access()),
+                json_debug(link.usage()),
+                json_debug(link.state().stages),
+            )?;
+            json_link_submissions(out, link)?;
+            write!(out, "}}")?;
+        }
+        write!(out, "]}}")?;
+    }
+    writeln!(out, "\n  ],")?;
+
+    writeln!(out, "  \"images\": [")?;
+    for (i, (id, chain)) in sorted(&chains.images).into_iter().enumerate() {
+        if i > 0 {
+            writeln!(out, ",")?;
+        }
+        write!(out, "    {{\"id\": {}, ", id.0)?;
+        if let Some(alias) = image_aliases.get(id.0).and_then(Option::as_ref) {
+            write!(
+                out,
+                "\"alias\": {{\"first\": {}, \"last\": {}}}, ",
+                json_submission(alias.first),
+                json_submission(alias.last)
+            )?;
+        }
+        write!(out, "\"links\": [")?;
+        for (j, link) in chain.links().iter().enumerate() {
+            if j > 0 {
+                write!(out, ", ")?;
+            }
+            write!(
+                out,
+                "{{\"family\": {}, \"access\": {}, \"layout\": {}, \"usage\": {}, \"stages\": {}, \"submissions\": ",
+                link.family().0,
+                json_debug(link.access()),
+                json_debug(link.layout()),
+                json_debug(link.usage()),
+                json_debug(link.state().stages),
+            )?;
+            json_link_submissions(out, link)?;
+            write!(out, "}}")?;
+        }
+        write!(out, "]}}")?;
+    }
+    writeln!(out, "\n  ]")?;
+
+    writeln!(out, "}}")
+}
+
+/// Chains sorted by resource id, so that output is stable.
+fn sorted<R: chain::Resource>(
+    chains: &std::collections::HashMap<chain::Id, chain::Chain<R>>,
+) -> Vec<(chain::Id, &chain::Chain<R>)> {
+    let mut chains: Vec<_> = chains.iter().map(|(&id, chain)| (id, chain)).collect();
+    chains.sort_by_key(|&(id, _)| id.0);
+    chains
+}
+
+fn dot_id(sid: chain::SubmissionId) -> String {
+    format!(
+        "s_{}_{}_{}",
+        sid.family().0,
+        sid.queue().index(),
+        sid.index()
+    )
+}
+
+/// Connect every submission of the link with every submission of the next one.
+fn dot_link_edges<R: chain::Resource>(
+    out: &mut impl Write,
+    prev: &chain::Link<R>,
+    next: &chain::Link<R>,
+    label: &str,
+) -> Result<()> {
+    for (pqid, pqueue) in prev.queues() {
+        for (nqid, nqueue) in next.queues() {
+            writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                dot_id(chain::SubmissionId::new(pqid, pqueue.last)),
+                dot_id(chain::SubmissionId::new(nqid, nqueue.first)),
+                label
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Format value with `Debug` as JSON string.
+fn json_debug(value: impl std::fmt::Debug) -> String {
+    memory::json_string(&format!("{:?}", value))
+}
+
+fn json_queue(qid: chain::QueueId) -> String {
+    format!(
+        "{{\"family\": {}, \"index\": {}}}",
+        qid.family().0,
+        qid.index()
+    )
+}
+
+fn json_submission(sid: chain::SubmissionId) -> String {
+    format!(
+        "{{\"queue\": {}, \"index\": {}}}",
+        json_queue(sid.queue()),
+        sid.index()
+    )
+}
+
+fn json_link_submissions<R: chain::Resource>(
+    out: &mut impl Write,
+    link: &chain::Link<R>,
+) -> Result<()> {
+    write!(out, "[")?;
+    for (i, (qid, queue)) in link.queues().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write!(
+            out,
+            "{{\"queue\": {}, \"first\": {}, \"last\": {}}}",
+            json_queue(qid),
+            queue.first,
+            queue.last
+        )?;
+    }
+    write!(out, "]")
+}
+
+fn json_guard(out: &mut impl Write, guard: &chain::Guard) -> Result<()> {
+    write!(out, "{{\"buffers\": [")?;
+    let mut buffers: Vec<_> = guard.buffers.iter().collect();
+    buffers.sort_by_key(|&(id, _)| id.0);
+    for (i, (id, barrier)) in buffers.into_iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write!(
+            out,
+            "{{\"id\": {}, \"access\": [{}, {}], \"stages\": [{}, {}], \"families\": {}}}",
+            id.0,
+            json_debug(barrier.states.start.0),
+            json_debug(barrier.states.end.0),
+            json_debug(barrier.states.start.2),
+            json_debug(barrier.states.end.2),
+            json_families(&barrier.families),
+        )?;
+    }
+    write!(out, "], \"images\": [")?;
+    let mut images: Vec<_> = guard.images.iter().collect();
+    images.sort_by_key(|&(id, _)| id.0);
+    for (i, (id, barrier)) in images.into_iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write!(
+            out,
+            "{{\"id\": {}, \"access\": [{}, {}], \"layout\": [{}, {}], \"stages\": [{}, {}], \"families\": {}}}",
+            id.0,
+            json_debug(barrier.states.start.0),
+            json_debug(barrier.states.end.0),
+            json_debug(barrier.states.start.1),
+            json_debug(barrier.states.end.1),
+            json_debug(barrier.states.start.2),
+            json_debug(barrier.states.end.2),
+            json_families(&barrier.families),
+        )?;
+    }
+    write!(out, "]}}")
+}
+
+fn json_families(
+    families: &Option<std::ops::Range<rendy_core::hal::queue::QueueFamilyId>>,
+) -> String {
+    match families {
+        Some(families) => format!("[{}, {}]", families.start.0, families.end.0),
+        None => "null".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        rendy_core::hal::{buffer, image, pso::PipelineStage, queue::QueueFamilyId},
+    };
+
+    /// Node 0 reads buffer written by node 1 in the previous frame and draws to an image.
+    /// Node 1 runs on another queue.
+    fn chains() -> (Schedule, chain::Chains) {
+        let buffer = |access| {
+            let mut buffers = std::collections::HashMap::new();
+            buffers.insert(
+                chain::Id(0),
+                chain::State {
+                    access,
+                    layout: (),
+                    stages: PipelineStage::COMPUTE_SHADER,
+                    usage: buffer::Usage::STORAGE,
+                },
+            );
+            buffers
+        };
+        let mut images = std::collections::HashMap::new();
+        images.insert(
+            chain::Id(0),
+            chain::State {
+                access: image::Access::COLOR_ATTACHMENT_WRITE,
+                layout: image::Layout::ColorAttachmentOptimal,
+                stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+                usage: image::Usage::COLOR_ATTACHMENT,
+            },
+        );
+
+        let nodes = vec![
+            chain::Node {
+                id: 0,
+                family: QueueFamilyId(0),
+                async_family: None,
+                dependencies: vec![],
+                buffers: buffer(buffer::Access::SHADER_READ),
+                images,
+            },
+            chain::Node {
+                id: 1,
+                family: QueueFamilyId(0),
+                async_family: None,
+                dependencies: vec![0],
+                buffers: buffer(buffer::Access::SHADER_WRITE),
+                images: std::collections::HashMap::new(),
+            },
+        ];
+
+        let chains = chain::collect(nodes, |_| 2);
+        let mut next = 0;
+        let mut schedule = chain::sync(&chains, || {
+            next += 1;
+            (next, next)
+        });
+        schedule.build_order();
+        (schedule, chains)
+    }
+
+    #[test]
+    fn test_export_dot() {
+        let (schedule, chains) = chains();
+        let mut out = Vec::new();
+        write_dot(&mut out, &schedule, &chains).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+
+        assert!(dot.starts_with("digraph schedule {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("subgraph cluster_0_0 {"));
+        assert!(dot.contains("subgraph cluster_0_1 {"));
+        assert!(dot.contains("s_0_0_0 [label=\"node 0\\norder 0\"];"));
+        assert!(dot.contains("s_0_1_0 [label=\"node 1\\norder 1\"];"));
+        assert!(dot.contains("s_0_0_0 -> s_0_1_0 [label=\"buffer 0\\nSHADER_WRITE\"];"));
+        assert!(dot.contains("s_0_0_0 -> s_0_1_0 [style=dashed, label=\"semaphore 1\\n"));
+        assert!(dot.contains("s_0_1_0 -> s_0_0_0 [style=dashed, label=\"semaphore 2\\n"));
+    }
+
+    #[test]
+    fn test_export_json() {
+        let (schedule, chains) = chains();
+        let mut out = Vec::new();
+        write_json(&mut out, &schedule, &chains, &[], &[]).unwrap();
+        let json = String::from_utf8(out).unwrap();
+
+        assert!(json.contains(
+            "{\"node\": 0, \"queue\": {\"family\": 0, \"index\": 0}, \"index\": 0, \"order\": 0, \
+             \"waits\": [{\"semaphore\": 2, \"stages\": \"COMPUTE_SHADER\", \"previous_frame\": true}], \
+             \"signals\": [1]"
+        ));
+        assert!(json.contains(
+            "{\"node\": 1, \"queue\": {\"family\": 0, \"index\": 1}, \"index\": 0, \"order\": 1, \
+             \"waits\": [{\"semaphore\": 1, \"stages\": \"COMPUTE_SHADER\", \"previous_frame\": false}], \
+             \"signals\": [2]"
+        ));
+        assert!(json.contains(
+            "{\"id\": 0, \"links\": [{\"family\": 0, \"access\": \"SHADER_READ\", \
+             \"usage\": \"STORAGE\", \"stages\": \"COMPUTE_SHADER\", \
+             \"submissions\": [{\"queue\": {\"family\": 0, \"index\": 0}, \"first\": 0, \"last\": 0}]}, "
+        ));
+        assert!(json.contains(
+            "{\"id\": 0, \"links\": [{\"family\": 0, \"access\": \"COLOR_ATTACHMENT_WRITE\", \
+             \"layout\": \"ColorAttachmentOptimal\""
+        ));
+    }
+}
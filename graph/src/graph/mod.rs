@@ -1,21 +1,35 @@
+mod alias;
+mod export;
+
 use {
     crate::{
-        chain,
-        command::{Families, FamilyId, QueueId},
+        chain::{self, AccessFlags as _},
+        command::{
+            CommandPool, Families, Family, FamilyId, IndividualReset, PrimaryLevel, QueueId,
+            QueueType, Submission,
+        },
         core::{device_owned, DeviceId},
-        factory::Factory,
+        factory::{Factory, ImageState, ImageStateOrLayout},
         frame::{Fences, Frame, Frames},
-        memory::Data,
+        memory::{Data, MemoryBlock},
         node::{
-            BufferBarrier, DynNode, ImageBarrier, NodeBuffer, NodeBuildError, NodeBuilder,
-            NodeImage,
+            free_barrier_commands, history::HistoryNodeBuilder, record_node_barriers,
+            BarrierCommands, BufferBarrier, DynNode, ImageBarrier, NodeBuffer, NodeBuildError,
+            NodeBuilder, NodeImage,
         },
         resource::{
-            Buffer, BufferCreationError, BufferInfo, Handle, Image, ImageCreationError, ImageInfo,
+            Buffer, BufferCreationError, BufferInfo, CreationError, Handle, Image,
+            ImageCreationError, ImageInfo,
         },
         BufferId, ImageId, NodeId,
     },
-    rendy_core::hal::{queue::QueueFamilyId, Backend},
+    rendy_core::hal::{
+        device::{Device as _, OutOfMemory},
+        pso::PipelineStage,
+        queue::QueueFamilyId,
+        Backend,
+    },
+    std::{collections::HashMap, ops::Range},
     thread_profiler::profile_scope,
 };
 
@@ -23,6 +37,66 @@ use {
 struct GraphNode<B: Backend, T: ?Sized> {
     node: Box<dyn DynNode<B, T>>,
     queue: (usize, usize),
+    buffers: Vec<BufferId>,
+    images: Vec<ImageId>,
+    enabled: bool,
+    bypass: Option<Bypass<B>>,
+}
+
+/// Commands submitted in place of disabled node.
+/// Perform node's acquire and release barriers so that
+/// resources end up in the state following nodes expect.
+#[derive(Debug)]
+struct Bypass<B: Backend> {
+    pool: CommandPool<B, QueueType, IndividualReset>,
+    /// Barriers for each phase of history images.
+    barriers: Vec<Option<BarrierCommands<B, QueueType, PrimaryLevel>>>,
+}
+
+impl<B> Bypass<B>
+where
+    B: Backend,
+{
+    fn record(
+        factory: &Factory<B>,
+        family: &Family<B>,
+        ctx: &GraphContext<B>,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+    ) -> Result<Self, OutOfMemory> {
+        let mut pool = factory.create_command_pool(family)?;
+        let barriers = record_node_barriers(ctx, factory, &mut pool, buffers, images);
+        Ok(Bypass { pool, barriers })
+    }
+
+    /// Record barriers anew after resources were reallocated.
+    ///
+    /// # Safety
+    ///
+    /// Commands must not be pending.
+    unsafe fn rerecord(
+        &mut self,
+        factory: &Factory<B>,
+        ctx: &GraphContext<B>,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+    ) {
+        let barriers = record_node_barriers(ctx, factory, &mut self.pool, buffers, images);
+        free_barrier_commands(
+            &mut self.pool,
+            std::mem::replace(&mut self.barriers, barriers),
+        );
+    }
+
+    /// Free commands and destroy pool.
+    ///
+    /// # Safety
+    ///
+    /// Commands must not be pending.
+    unsafe fn dispose(mut self, factory: &Factory<B>) {
+        free_barrier_commands(&mut self.pool, self.barriers);
+        factory.destroy_command_pool(self.pool);
+    }
 }
 
 /// Graph that renders whole frame.
@@ -36,6 +110,11 @@ pub struct Graph<B: Backend, T: ?Sized> {
     fences: Vec<Fences<B>>,
     inflight: u32,
     ctx: GraphContext<B>,
+    chains: chain::Chains,
+    image_descs: Vec<ImageDesc>,
+    images: Vec<(ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+    surface_extent: Option<rendy_core::hal::window::Extent2D>,
+    aliasing: bool,
 }
 
 device_owned!(Graph<B, T: ?Sized>);
@@ -101,78 +180,478 @@ pub struct GraphContext<B: Backend> {
             Option<rendy_core::hal::command::ClearValue>,
         )>,
     >,
+    buffer_aliases: Vec<Option<alias::Alias<rendy_core::hal::buffer::Access>>>,
+    image_aliases: Vec<Option<alias::Alias<rendy_core::hal::image::Access>>>,
+    buffer_blocks: Vec<MemoryBlock<B>>,
+    image_blocks: Vec<MemoryBlock<B>>,
+    imports: Imports<B>,
+    histories: Histories,
     /// Number of potential frames in flight
     pub frames_in_flight: u32,
 }
 
+/// Images that exchange their ids every frame.
+#[derive(Debug)]
+struct Histories {
+    /// Ids of each history, current one first.
+    ids: Vec<Vec<ImageId>>,
+    /// History and position in it of each history image.
+    slots: HashMap<usize, (usize, usize)>,
+    /// Number of frames after which all histories return to their initial binding.
+    period: usize,
+    /// Index of the frame being recorded.
+    frame: u64,
+}
+
+impl Histories {
+    fn new<'a>(histories: impl IntoIterator<Item = &'a HistoryImage>) -> Self {
+        let ids: Vec<Vec<_>> = histories
+            .into_iter()
+            .map(|history| {
+                std::iter::once(history.current)
+                    .chain(history.previous.iter().cloned())
+                    .collect()
+            })
+            .collect();
+
+        let slots = ids
+            .iter()
+            .enumerate()
+            .flat_map(|(history, ids)| {
+                ids.iter()
+                    .enumerate()
+                    .map(move |(slot, id)| (id.0, (history, slot)))
+            })
+            .collect();
+
+        let period = ids.iter().fold(1, |period, ids| lcm(period, ids.len()));
+
+        Histories {
+            ids,
+            slots,
+            period,
+            frame: 0,
+        }
+    }
+
+    fn phase(&self) -> usize {
+        (self.frame % self.period as u64) as usize
+    }
+
+    /// Index of the image bound to the id in given phase.
+    /// Image that was bound to slot `k` is bound to slot `k + 1` in the next phase.
+    fn bound(&self, index: usize, phase: usize) -> usize {
+        match self.slots.get(&index) {
+            Some(&(history, slot)) => {
+                let ids = &self.ids[history];
+                let len = ids.len();
+                ids[(slot + len - phase % len) % len].0
+            }
+            None => index,
+        }
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    a / gcd(a, b) * b
+}
+
+/// Resource created outside of the graph
+/// with states it is in before and must be left in after each frame.
+#[derive(Debug)]
+struct Import<R, S> {
+    resource: Handle<R>,
+    states: Range<S>,
+    stages: Range<PipelineStage>,
+}
+
+#[derive(Debug)]
+struct Imports<B: Backend> {
+    buffers: HashMap<usize, Import<Buffer<B>, rendy_core::hal::buffer::State>>,
+    images: HashMap<usize, Import<Image<B>, rendy_core::hal::image::State>>,
+}
+
 impl<B: Backend> GraphContext<B> {
     fn alloc<'a>(
         factory: &Factory<B>,
         chains: &chain::Chains,
         buffers: impl IntoIterator<Item = &'a BufferInfo>,
         images: impl IntoIterator<Item = &'a (ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+        imports: Imports<B>,
+        histories: Histories,
         frames_in_flight: u32,
+        aliasing: bool,
     ) -> Result<Self, GraphBuildError> {
         profile_scope!("alloc");
 
+        let mut ctx = GraphContext {
+            buffers: Vec::new(),
+            images: Vec::new(),
+            buffer_aliases: Vec::new(),
+            image_aliases: Vec::new(),
+            buffer_blocks: Vec::new(),
+            image_blocks: Vec::new(),
+            imports,
+            histories,
+            frames_in_flight,
+        };
+
         log::trace!("Allocate buffers");
-        let buffers: Vec<Option<Handle<Buffer<B>>>> = buffers
-            .into_iter()
-            .enumerate()
-            .map(|(index, info)| {
-                chains
-                    .buffers
-                    .get(&chain::Id(index))
-                    .map(|buffer| {
-                        factory
-                            .create_buffer(
-                                BufferInfo {
-                                    usage: buffer.usage(),
-                                    ..info.clone()
-                                },
-                                Data,
-                            )
-                            .map(|buffer| Some(buffer.into()))
-                    })
-                    .unwrap_or(Ok(None))
-            })
-            .collect::<Result<_, _>>()
-            .map_err(GraphBuildError::Buffer)?;
+        let result = ctx
+            .alloc_buffers(factory, chains, buffers, aliasing)
+            .map_err(GraphBuildError::Buffer)
+            .and_then(|()| {
+                log::trace!("Allocate images");
+                ctx.alloc_images(factory, chains, images, aliasing, |_| None)
+                    .map_err(GraphBuildError::Image)
+            });
 
-        log::trace!("Allocate images");
-        let images: Vec<Option<(Handle<Image<B>>, _)>> = images
-            .into_iter()
+        match result {
+            Ok(()) => Ok(ctx),
+            Err(err) => {
+                unsafe {
+                    // Nothing was submitted yet.
+                    ctx.dispose(factory);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn alloc_buffers<'a>(
+        &mut self,
+        factory: &Factory<B>,
+        chains: &chain::Chains,
+        infos: impl IntoIterator<Item = &'a BufferInfo>,
+        aliasing: bool,
+    ) -> Result<(), BufferCreationError> {
+        let mut candidates = Vec::new();
+        let mut unbound = Vec::new();
+
+        for (index, info) in infos.into_iter().enumerate() {
+            self.buffers.push(None);
+            self.buffer_aliases.push(None);
+
+            let chain = match chains.buffers.get(&chain::Id(index)) {
+                Some(chain) => chain,
+                None => continue,
+            };
+
+            if let Some(import) = self.imports.buffers.get(&index) {
+                assert!(
+                    import.resource.info().usage.contains(chain.usage()),
+                    "Imported buffer {} lacks usage {:?} required by nodes",
+                    index,
+                    chain.usage()
+                );
+                self.buffers[index] = Some(import.resource.clone());
+                continue;
+            }
+
+            let info = BufferInfo {
+                usage: chain.usage(),
+                ..info.clone()
+            };
+
+            // Buffer can be aliased only if it is overwritten before being read each frame.
+            let lifetime = if aliasing && overwrites_buffer(chain) {
+                alias::Lifetime::of(chain, &chains.schedule)
+            } else {
+                None
+            };
+
+            let created = match lifetime {
+                Some(lifetime) => unsafe { factory.create_unbound_buffer(info.clone()) }.map(
+                    |(raw, requirements)| {
+                        candidates.push(alias::Candidate {
+                            index,
+                            lifetime,
+                            requirements,
+                        });
+                        unbound.push(Some((raw, info)));
+                    },
+                ),
+                None => factory.create_buffer(info, Data).map(|buffer| {
+                    self.buffers[index] = Some(buffer.into());
+                }),
+            };
+
+            if let Err(err) = created {
+                unbound
+                    .into_iter()
+                    .flatten()
+                    .for_each(|(raw, _)| unsafe { factory.device().destroy_buffer(raw) });
+                return Err(err);
+            }
+        }
+
+        let slots = alias::pack(&candidates);
+        for (i, slot) in slots.iter().enumerate() {
+            let block = match factory.allocate_block(slot.mask, Data, slot.size, slot.align) {
+                Ok(block) => block,
+                Err(err) => {
+                    unbound
+                        .into_iter()
+                        .flatten()
+                        .for_each(|(raw, _)| unsafe { factory.device().destroy_buffer(raw) });
+                    return Err(CreationError::Allocate(err));
+                }
+            };
+
+            for &member in &slot.members {
+                let (raw, info) = unbound[member].take().unwrap();
+                match unsafe { factory.bind_aliased_buffer(info, raw, &block, 0) } {
+                    Ok(buffer) => self.buffers[candidates[member].index] = Some(buffer.into()),
+                    Err(err) => {
+                        self.buffer_blocks.push(block);
+                        unbound
+                            .into_iter()
+                            .flatten()
+                            .for_each(|(raw, _)| unsafe { factory.device().destroy_buffer(raw) });
+                        return Err(err);
+                    }
+                }
+            }
+
+            self.buffer_blocks.push(block);
+            log::trace!("Buffer memory block {} shared by {:?}", i, slot.members);
+
+            for (index, alias) in alias::aliases::<chain::Buffer>(slot, &candidates, |index| {
+                let state = chains.buffers[&chain::Id(index)]
+                    .links()
+                    .last()
+                    .unwrap()
+                    .state();
+                (state.access, state.stages)
+            }) {
+                self.buffer_aliases[index] = Some(alias);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn alloc_images<'a>(
+        &mut self,
+        factory: &Factory<B>,
+        chains: &chain::Chains,
+        infos: impl IntoIterator<Item = &'a (ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+        aliasing: bool,
+        mut keep: impl FnMut(
+            usize,
+        ) -> Option<(
+            Handle<Image<B>>,
+            Option<rendy_core::hal::command::ClearValue>,
+        )>,
+    ) -> Result<(), ImageCreationError> {
+        let mut candidates = Vec::new();
+        let mut unbound = Vec::new();
+
+        for (index, (info, clear)) in infos.into_iter().enumerate() {
+            self.images.push(None);
+            self.image_aliases.push(None);
+
+            let chain = match chains.images.get(&chain::Id(index)) {
+                Some(chain) => chain,
+                None => continue,
+            };
+
+            if let Some(import) = self.imports.images.get(&index) {
+                assert!(
+                    import.resource.info().usage.contains(chain.usage()),
+                    "Imported image {} lacks usage {:?} required by nodes",
+                    index,
+                    chain.usage()
+                );
+                self.images[index] = Some((import.resource.clone(), None));
+                continue;
+            }
+
+            if let Some(image) = keep(index) {
+                self.images[index] = Some(image);
+                continue;
+            }
+
+            let history = self
+                .histories
+                .slots
+                .get(&index)
+                .map(|&(history, _)| history);
+
+            // Images of one history take turns in each of its ids.
+            let usage = match history {
+                Some(history) => self.histories.ids[history]
+                    .iter()
+                    .filter_map(|id| chains.images.get(&chain::Id(id.0)))
+                    .fold(rendy_core::hal::image::Usage::empty(), |usage, chain| {
+                        usage | chain.usage()
+                    }),
+                None => chain.usage(),
+            };
+
+            let info = ImageInfo {
+                usage,
+                ..info.clone()
+            };
+
+            // Image content is discarded at the beginning of each frame
+            // unless the first link only reads it.
+            // History images keep content for following frames.
+            let lifetime = if aliasing && history.is_none() && chain.links()[0].access().exclusive()
+            {
+                alias::Lifetime::of(chain, &chains.schedule)
+            } else {
+                None
+            };
+
+            let created = match lifetime {
+                Some(lifetime) => unsafe { factory.create_unbound_image(info.clone()) }.map(
+                    |(raw, requirements)| {
+                        candidates.push(alias::Candidate {
+                            index,
+                            lifetime,
+                            requirements,
+                        });
+                        unbound.push(Some((raw, info, *clear)));
+                    },
+                ),
+                None => factory.create_image(info, Data).map(|image| {
+                    self.images[index] = Some((image.into(), *clear));
+                }),
+            };
+
+            if let Err(err) = created {
+                unbound
+                    .into_iter()
+                    .flatten()
+                    .for_each(|(raw, _, _)| unsafe { factory.device().destroy_image(raw) });
+                return Err(err);
+            }
+        }
+
+        let slots = alias::pack(&candidates);
+        for (i, slot) in slots.iter().enumerate() {
+            let block = match factory.allocate_block(slot.mask, Data, slot.size, slot.align) {
+                Ok(block) => block,
+                Err(err) => {
+                    unbound
+                        .into_iter()
+                        .flatten()
+                        .for_each(|(raw, _, _)| unsafe { factory.device().destroy_image(raw) });
+                    return Err(CreationError::Allocate(err));
+                }
+            };
+
+            for &member in &slot.members {
+                let (raw, info, clear) = unbound[member].take().unwrap();
+                match unsafe { factory.bind_aliased_image(info, raw, &block, 0) } {
+                    Ok(image) => {
+                        self.images[candidates[member].index] = Some((image.into(), clear))
+                    }
+                    Err(err) => {
+                        self.image_blocks.push(block);
+                        unbound
+                            .into_iter()
+                            .flatten()
+                            .for_each(|(raw, _, _)| unsafe { factory.device().destroy_image(raw) });
+                        return Err(err);
+                    }
+                }
+            }
+
+            self.image_blocks.push(block);
+            log::trace!("Image memory block {} shared by {:?}", i, slot.members);
+
+            for (index, alias) in alias::aliases::<chain::Image>(slot, &candidates, |index| {
+                let state = chains.images[&chain::Id(index)]
+                    .links()
+                    .last()
+                    .unwrap()
+                    .state();
+                (state.access, state.stages)
+            }) {
+                self.image_aliases[index] = Some(alias);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Allocate images again after their kinds were changed.
+    /// Images with own memory and unchanged kind are kept.
+    /// Images that share memory are placed anew.
+    /// Returns flags of reallocated images.
+    ///
+    /// # Safety
+    ///
+    /// Images must not be used by pending commands.
+    unsafe fn realloc_images<'a>(
+        &mut self,
+        factory: &Factory<B>,
+        chains: &chain::Chains,
+        infos: impl IntoIterator<Item = &'a (ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+        aliasing: bool,
+        changed: &[bool],
+    ) -> Result<Vec<bool>, ImageCreationError> {
+        profile_scope!("realloc_images");
+
+        let mut old = std::mem::replace(&mut self.images, Vec::new());
+        let old_blocks = std::mem::replace(&mut self.image_blocks, Vec::new());
+        self.image_aliases.clear();
+
+        let mut kept = vec![false; old.len()];
+        let result = self.alloc_images(factory, chains, infos, aliasing, |index| {
+            match old[index].take() {
+                Some((image, clear)) if !changed[index] && image.block().is_some() => {
+                    kept[index] = true;
+                    Some((image, clear))
+                }
+                _ => None,
+            }
+        });
+
+        drop(old);
+        for block in old_blocks {
+            factory.free_block(block);
+        }
+        result?;
+
+        let imports = &self.imports.images;
+        Ok(self
+            .images
+            .iter()
+            .zip(kept)
             .enumerate()
-            .map(|(index, (info, clear))| {
-                chains
-                    .images
-                    .get(&chain::Id(index))
-                    .map(|image| {
-                        factory
-                            .create_image(
-                                ImageInfo {
-                                    usage: image.usage(),
-                                    ..info.clone()
-                                },
-                                Data,
-                            )
-                            .map(|image| Some((image.into(), *clear)))
-                    })
-                    .unwrap_or(Ok(None))
-            })
-            .collect::<Result<_, _>>()
-            .map_err(GraphBuildError::Image)?;
+            .map(|(index, (image, kept))| image.is_some() && !kept && !imports.contains_key(&index))
+            .collect())
+    }
 
-        Ok(Self {
-            buffers,
-            images,
-            frames_in_flight,
-        })
+    /// Destroy resources and free memory shared by transient resources.
+    ///
+    /// # Safety
+    ///
+    /// Resources must not be used by pending commands.
+    unsafe fn dispose(self, factory: &Factory<B>) {
+        drop(self.buffers);
+        drop(self.images);
+        for block in self.buffer_blocks.into_iter().chain(self.image_blocks) {
+            factory.free_block(block);
+        }
     }
 
     /// Get reference to transient image by id.
+    ///
+    /// History images are bound to their ids anew each frame,
+    /// see `history_phase`.
     pub fn get_image(&self, id: ImageId) -> Option<&Handle<Image<B>>> {
-        self.get_image_with_clear(id).map(|(i, _)| i)
+        self.get_image_in_phase(id, self.history_phase())
     }
 
     /// Get reference to transient image and clear value by id.
@@ -183,10 +662,36 @@ impl<B: Backend> GraphContext<B> {
         &Handle<Image<B>>,
         Option<rendy_core::hal::command::ClearValue>,
     )> {
+        let clear = self.images.get(id.0).and_then(|x| x.as_ref())?.1;
+        self.get_image(id).map(|image| (image, clear))
+    }
+
+    /// Get reference to transient image bound to the id in given phase.
+    /// Nodes use this to create objects for every phase up front.
+    pub fn get_image_in_phase(&self, id: ImageId, phase: usize) -> Option<&Handle<Image<B>>> {
         self.images
-            .get(id.0)
+            .get(self.histories.bound(id.0, phase))
             .and_then(|x| x.as_ref())
-            .map(|&(ref x, ref y)| (&*x, *y))
+            .map(|(x, _)| &*x)
+    }
+
+    /// Number of distinct bindings of history images to their ids.
+    /// It is `1` when there are no history images.
+    pub fn history_period(&self) -> usize {
+        self.histories.period
+    }
+
+    /// Binding of history images to their ids in the frame being recorded,
+    /// in range `0..history_period()`.
+    ///
+    /// Each frame images of every history shift one step back:
+    /// image bound to `HistoryImage::current` becomes bound to `HistoryImage::previous(1)` and so on,
+    /// while the oldest one becomes bound to `current`.
+    /// Nodes that keep views, framebuffers, descriptors or recorded commands
+    /// referencing history images must keep them for every phase
+    /// and pick ones for the current phase when recording.
+    pub fn history_phase(&self) -> usize {
+        self.histories.phase()
     }
 
     /// Get reference to transient buffer by id.
@@ -219,13 +724,21 @@ where
         let mut fences = self.fences.pop().unwrap_or_else(Fences::<B>::default);
         let mut fences_used = 0;
         let ref semaphores = self.semaphores;
+        let first_frame = self.frames.next().index() == 0;
+        self.ctx.histories.frame = self.frames.next().index();
 
         for submission in self.schedule.ordered() {
             log::trace!("Run node {}", submission.node());
             let sid = submission.id();
             let qid = sid.queue();
 
-            let GraphNode { node, queue } = self
+            let GraphNode {
+                node,
+                queue,
+                enabled,
+                bypass,
+                ..
+            } = self
                 .nodes
                 .get_mut(submission.node())
                 .expect("Submission references node with out of bound index");
@@ -246,41 +759,64 @@ where
                 None
             };
 
-            unsafe {
-                node.run(
-                    &self.ctx,
-                    factory,
-                    families.family_by_index_mut(queue.0).queue_mut(queue.1),
-                    aux,
-                    &self.frames,
-                    &submission
-                        .sync()
-                        .wait
-                        .iter()
-                        .map(|wait| {
-                            log::trace!(
-                                "Node {} waits for {}",
-                                submission.node(),
-                                *wait.semaphore()
-                            );
-                            (&semaphores[*wait.semaphore()], wait.stage())
-                        })
-                        .collect::<smallvec::SmallVec<[_; 16]>>(),
-                    &submission
-                        .sync()
-                        .signal
-                        .iter()
-                        .map(|signal| {
-                            log::trace!(
-                                "Node {} signals {}",
-                                submission.node(),
-                                *signal.semaphore()
-                            );
-                            &semaphores[*signal.semaphore()]
-                        })
-                        .collect::<smallvec::SmallVec<[_; 16]>>(),
-                    fence,
-                )
+            let waits = submission
+                .sync()
+                .wait
+                .iter()
+                .filter(|wait| !(first_frame && wait.previous_frame()))
+                .map(|wait| {
+                    log::trace!("Node {} waits for {}", submission.node(), *wait.semaphore());
+                    (&semaphores[*wait.semaphore()], wait.stage())
+                })
+                .collect::<smallvec::SmallVec<[_; 16]>>();
+
+            let signals = submission
+                .sync()
+                .signal
+                .iter()
+                .map(|signal| {
+                    log::trace!("Node {} signals {}", submission.node(), *signal.semaphore());
+                    &semaphores[*signal.semaphore()]
+                })
+                .collect::<smallvec::SmallVec<[_; 16]>>();
+
+            if *enabled {
+                unsafe {
+                    node.run(
+                        &self.ctx,
+                        factory,
+                        families.family_by_index_mut(queue.0).queue_mut(queue.1),
+                        aux,
+                        &self.frames,
+                        &waits,
+                        &signals,
+                        fence,
+                    )
+                }
+            } else {
+                log::trace!("Bypass disabled node {}", submission.node());
+                let bypass = bypass
+                    .as_ref()
+                    .expect("Commands are recorded when node is disabled");
+
+                unsafe {
+                    families
+                        .family_by_index_mut(queue.0)
+                        .queue_mut(queue.1)
+                        .submit(
+                            Some(
+                                Submission::new()
+                                    .submits(
+                                        bypass.barriers[self.ctx.history_phase()]
+                                            .as_ref()
+                                            .map(|(submit, _)| submit),
+                                    )
+                                    .wait(waits.iter().cloned())
+                                    .signal(signals.iter().cloned()),
+                            ),
+                            fence,
+                        );
+                }
             }
         }
 
@@ -288,6 +824,148 @@ where
         self.frames.advance(fences);
     }
 
+    /// Resize images that are relative to the surface extent
+    /// and images relative to them.
+    ///
+    /// Affected images are reallocated
+    /// and nodes that use them are notified with `DynNode::resize`.
+    /// Nodes that aren't affected are kept intact.
+    pub fn resize(
+        &mut self,
+        factory: &mut Factory<B>,
+        extent: rendy_core::hal::window::Extent2D,
+        aux: &T,
+    ) -> Result<(), GraphBuildError> {
+        profile_scope!("resize");
+
+        self.assert_device_owner(factory.device());
+
+        self.surface_extent = Some(extent);
+        let kinds = resolve_image_kinds(&self.image_descs, self.surface_extent);
+        let changed: Vec<bool> = self
+            .images
+            .iter()
+            .zip(&kinds)
+            .map(|((info, _), kind)| info.kind != *kind)
+            .collect();
+
+        if !changed.contains(&true) {
+            return Ok(());
+        }
+
+        log::debug!("Resize graph images to fit surface {:?}", extent);
+        for ((info, _), kind) in self.images.iter_mut().zip(kinds) {
+            info.kind = kind;
+        }
+
+        assert!(factory.wait_idle().is_ok());
+
+        let reallocated = unsafe {
+            // Device is idle.
+            self.ctx
+                .realloc_images(factory, &self.chains, &self.images, self.aliasing, &changed)
+        }
+        .map_err(GraphBuildError::Image)?;
+
+        unsafe {
+            // Device is idle.
+            init_read_first_images(
+                factory,
+                &self.ctx,
+                &self.chains,
+                (0..reallocated.len()).filter(|&index| reallocated[index]),
+            );
+        }
+
+        for submission in self.schedule.ordered() {
+            let GraphNode {
+                node,
+                buffers,
+                images,
+                bypass,
+                ..
+            } = &mut self.nodes[submission.node()];
+
+            if !images.iter().any(|id| reallocated[id.0]) {
+                continue;
+            }
+
+            log::trace!("Resize node {}", submission.node());
+            let buffers =
+                node_buffers(&self.ctx, &self.chains, submission, buffers.iter().cloned());
+            let images = node_images(&self.ctx, &self.chains, submission, images.iter().cloned());
+
+            if let Some(bypass) = bypass {
+                unsafe {
+                    // Device is idle.
+                    bypass.rerecord(factory, &self.ctx, &buffers, &images);
+                }
+            }
+
+            unsafe {
+                // Device is idle.
+                node.resize(&self.ctx, factory, aux, buffers, images)
+            }
+            .map_err(GraphBuildError::Node)?;
+        }
+
+        Ok(())
+    }
+
+    /// Enable or disable node.
+    ///
+    /// Disabled node is not run. Instead graph submits
+    /// node's barriers along with the same semaphores and fence,
+    /// so that following nodes stay synchronized and find resources in expected layouts.
+    /// Content of resources written by disabled node is undefined,
+    /// and node that presents to a surface doesn't present while disabled.
+    /// Barriers are recorded when node is disabled for the first time.
+    ///
+    /// All nodes are enabled after graph is built.
+    pub fn set_node_enabled(
+        &mut self,
+        factory: &Factory<B>,
+        families: &Families<B>,
+        node: NodeId,
+        enabled: bool,
+    ) -> Result<(), OutOfMemory> {
+        self.assert_device_owner(factory.device());
+
+        let GraphNode {
+            queue,
+            buffers,
+            images,
+            bypass,
+            ..
+        } = &mut self.nodes[node.0];
+
+        if !enabled && bypass.is_none() {
+            let submission = self
+                .schedule
+                .ordered()
+                .find(|submission| submission.node() == node.0)
+                .expect("Every node is scheduled");
+            let buffers =
+                node_buffers(&self.ctx, &self.chains, submission, buffers.iter().cloned());
+            let images = node_images(&self.ctx, &self.chains, submission, images.iter().cloned());
+            *bypass = Some(Bypass::record(
+                factory,
+                families.family_by_index(queue.0),
+                &self.ctx,
+                &buffers,
+                &images,
+            )?);
+        }
+
+        self.nodes[node.0].enabled = enabled;
+        Ok(())
+    }
+
+    /// Check if node is enabled.
+    pub fn node_enabled(&self, node: NodeId) -> bool {
+        self.nodes[node.0].enabled
+    }
+
     /// Get queue that will exeute given node.
     pub fn node_queue(&self, node: NodeId) -> QueueId {
         let (f, i) = self.nodes[node.0].queue;
@@ -312,6 +990,9 @@ where
         unsafe {
             // Device is idle.
             for node in self.nodes {
+                if let Some(bypass) = node.bypass {
+                    bypass.dispose(factory);
+                }
                 node.node.dispose(factory, data);
             }
 
@@ -323,16 +1004,127 @@ where
         drop(self.schedule);
         drop(self.fences);
         drop(self.inflight);
-        drop(self.ctx);
+        drop(self.chains);
+
+        unsafe {
+            // Device is idle.
+            self.ctx.dispose(factory);
+        }
+    }
+}
+
+/// Size of the image owned by graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageSize {
+    /// Image of fixed kind.
+    Fixed(rendy_core::hal::image::Kind),
+
+    /// Two-dimensional image with extent proportional to the surface extent.
+    /// Surface extent is set with `GraphBuilder::with_surface_extent`
+    /// and changed with `Graph::resize`.
+    Surface {
+        /// Factor by which surface width and height are multiplied.
+        scale: f32,
+
+        /// Number of array layers.
+        layers: rendy_core::hal::image::Layer,
+
+        /// Number of samples per texel.
+        samples: rendy_core::hal::image::NumSamples,
+    },
+
+    /// Two-dimensional image with extent proportional to another image owned by graph.
+    /// That image must be created before this one.
+    Image {
+        /// Image extent of which is scaled.
+        image: ImageId,
+
+        /// Factor by which image width and height are multiplied.
+        scale: f32,
+
+        /// Number of array layers.
+        layers: rendy_core::hal::image::Layer,
+
+        /// Number of samples per texel.
+        samples: rendy_core::hal::image::NumSamples,
+    },
+}
+
+impl ImageSize {
+    /// Single-sampled image of the surface size.
+    pub fn full_surface() -> Self {
+        ImageSize::Surface {
+            scale: 1.0,
+            layers: 1,
+            samples: 1,
+        }
+    }
+
+    /// Single-sampled image of the scaled surface size.
+    pub fn scaled_surface(scale: f32) -> Self {
+        ImageSize::Surface {
+            scale,
+            layers: 1,
+            samples: 1,
+        }
+    }
+
+    /// Single-sampled image of the scaled size of another image.
+    pub fn scaled_image(image: ImageId, scale: f32) -> Self {
+        ImageSize::Image {
+            image,
+            scale,
+            layers: 1,
+            samples: 1,
+        }
     }
 }
 
+/// Image that keeps its content from previous frames.
+/// Created with `GraphBuilder::create_history_image`.
+#[derive(Clone, Debug)]
+pub struct HistoryImage {
+    current: ImageId,
+    previous: Vec<ImageId>,
+}
+
+impl HistoryImage {
+    /// Image written in the current frame.
+    pub fn current(&self) -> ImageId {
+        self.current
+    }
+
+    /// Image with content `current` had `frames` frames ago.
+    /// `previous(1)` is the last frame.
+    pub fn previous(&self, frames: usize) -> ImageId {
+        assert!(frames > 0 && frames <= self.previous.len());
+        self.previous[frames - 1]
+    }
+
+    /// Number of previous frames kept.
+    pub fn depth(&self) -> usize {
+        self.previous.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ImageDesc {
+    size: ImageSize,
+    levels: rendy_core::hal::image::Level,
+    format: rendy_core::hal::format::Format,
+    clear: Option<rendy_core::hal::command::ClearValue>,
+}
+
 /// Build graph from nodes and resource.
 pub struct GraphBuilder<B: Backend, T: ?Sized> {
     nodes: Vec<Box<dyn NodeBuilder<B, T>>>,
     buffers: Vec<BufferInfo>,
-    images: Vec<(ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+    images: Vec<ImageDesc>,
+    imports: Imports<B>,
+    histories: Vec<HistoryImage>,
     frames_in_flight: u32,
+    aliasing: bool,
+    surface_extent: Option<rendy_core::hal::window::Extent2D>,
 }
 
 impl<B, T> Default for GraphBuilder<B, T>
@@ -345,7 +1137,14 @@ where
             nodes: Vec::default(),
             buffers: Vec::default(),
             images: Vec::default(),
+            imports: Imports {
+                buffers: HashMap::default(),
+                images: HashMap::default(),
+            },
+            histories: Vec::default(),
             frames_in_flight: u32::default(),
+            aliasing: true,
+            surface_extent: None,
         }
     }
 }
@@ -360,7 +1159,11 @@ where
             .field("nodes", &self.nodes)
             .field("buffers", &self.buffers)
             .field("images", &self.images)
+            .field("imports", &self.imports)
+            .field("histories", &self.histories)
             .field("frames_in_flight", &self.frames_in_flight)
+            .field("aliasing", &self.aliasing)
+            .field("surface_extent", &self.surface_extent)
             .finish()
     }
 }
@@ -376,7 +1179,14 @@ where
             nodes: Vec::new(),
             buffers: Vec::new(),
             images: Vec::new(),
+            imports: Imports {
+                buffers: HashMap::new(),
+                images: HashMap::new(),
+            },
+            histories: Vec::new(),
             frames_in_flight: 3,
+            aliasing: true,
+            surface_extent: None,
         }
     }
 
@@ -398,20 +1208,129 @@ where
         levels: rendy_core::hal::image::Level,
         format: rendy_core::hal::format::Format,
         clear: Option<rendy_core::hal::command::ClearValue>,
+    ) -> ImageId {
+        self.create_sized_image(ImageSize::Fixed(kind), levels, format, clear)
+    }
+
+    /// Create new image owned by graph
+    /// with size that can be relative to the surface or another image.
+    pub fn create_sized_image(
+        &mut self,
+        size: ImageSize,
+        levels: rendy_core::hal::image::Level,
+        format: rendy_core::hal::format::Format,
+        clear: Option<rendy_core::hal::command::ClearValue>,
     ) -> ImageId {
         profile_scope!("create_image");
 
-        self.images.push((
-            ImageInfo {
-                kind,
-                levels,
-                format,
-                tiling: rendy_core::hal::image::Tiling::Optimal,
-                view_caps: rendy_core::hal::image::ViewCapabilities::empty(),
-                usage: rendy_core::hal::image::Usage::empty(),
-            },
+        if let ImageSize::Image { image, .. } = size {
+            assert!(
+                image.0 < self.images.len(),
+                "Image size can be relative only to an image created before"
+            );
+        }
+
+        self.images.push(ImageDesc {
+            size,
+            levels,
+            format,
             clear,
-        ));
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Create new image owned by graph
+    /// along with `depth` images that keep its content from previous frames.
+    ///
+    /// Nodes write `HistoryImage::current` and read `HistoryImage::previous`.
+    /// Instead of copying content, graph binds images to the ids anew each frame,
+    /// so that image written as `current` is read as `previous(1)` in the next frame.
+    /// Nodes that use history images must look them up for every frame,
+    /// or keep objects referencing them for each `GraphContext::history_phase`.
+    /// Content of previous images is undefined until enough frames are rendered
+    /// and after the images are reallocated by `Graph::resize`.
+    pub fn create_history_image(
+        &mut self,
+        size: ImageSize,
+        levels: rendy_core::hal::image::Level,
+        format: rendy_core::hal::format::Format,
+        clear: Option<rendy_core::hal::command::ClearValue>,
+        depth: usize,
+    ) -> HistoryImage {
+        profile_scope!("create_history_image");
+
+        assert!(
+            depth > 0,
+            "History image must keep at least one previous frame"
+        );
+
+        let history = HistoryImage {
+            current: self.create_sized_image(size, levels, format, clear),
+            previous: (0..depth)
+                .map(|_| self.create_sized_image(size, levels, format, None))
+                .collect(),
+        };
+        self.histories.push(history.clone());
+        history
+    }
+
+    /// Import buffer created outside of the graph.
+    ///
+    /// Graph transitions the buffer from the first of `states` before first node that uses it
+    /// and into the second one after last node that uses it.
+    /// Buffer must be in the first state at `stages.start` whenever the graph runs
+    /// and must be usable by queue families of the nodes accessing it.
+    /// Buffer's usage must cover usage requested by the nodes.
+    pub fn import_buffer(
+        &mut self,
+        buffer: Handle<Buffer<B>>,
+        states: Range<rendy_core::hal::buffer::State>,
+        stages: Range<PipelineStage>,
+    ) -> BufferId {
+        profile_scope!("import_buffer");
+
+        self.buffers.push(buffer.info().clone());
+        self.imports.buffers.insert(
+            self.buffers.len() - 1,
+            Import {
+                resource: buffer,
+                states,
+                stages,
+            },
+        );
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Import image created outside of the graph.
+    ///
+    /// Graph transitions the image from the first of `states` before first node that uses it
+    /// and into the second one after last node that uses it.
+    /// Content of the image is preserved.
+    /// Image must be in the first state at `stages.start` whenever the graph runs
+    /// and must be usable by queue families of the nodes accessing it.
+    /// Image's usage must cover usage requested by the nodes.
+    pub fn import_image(
+        &mut self,
+        image: Handle<Image<B>>,
+        states: Range<rendy_core::hal::image::State>,
+        stages: Range<PipelineStage>,
+    ) -> ImageId {
+        profile_scope!("import_image");
+
+        self.images.push(ImageDesc {
+            size: ImageSize::Fixed(image.kind()),
+            levels: image.levels(),
+            format: image.format(),
+            clear: None,
+        });
+        self.imports.images.insert(
+            self.images.len() - 1,
+            Import {
+                resource: image,
+                states,
+                stages,
+            },
+        );
         ImageId(self.images.len() - 1)
     }
 
@@ -432,6 +1351,21 @@ where
         self
     }
 
+    /// Set extent of the surface
+    /// for images with size relative to it.
+    pub fn with_surface_extent(mut self, extent: rendy_core::hal::window::Extent2D) -> Self {
+        self.surface_extent = Some(extent);
+        self
+    }
+
+    /// Choose whether transient resources with non-overlapping lifetimes
+    /// may share memory.
+    /// Enabled by default.
+    pub fn with_transient_aliasing(mut self, aliasing: bool) -> Self {
+        self.aliasing = aliasing;
+        self
+    }
+
     /// Build `Graph`.
     ///
     /// # Parameters
@@ -451,10 +1385,15 @@ where
     ) -> Result<Graph<B, T>, GraphBuildError> {
         profile_scope!("build");
 
+        let mut nodes = self.nodes;
+        for history in &self.histories {
+            add_history_node(&mut nodes, history);
+        }
+
         log::trace!("Schedule nodes execution");
         let chain_nodes: Vec<chain::Node> = {
             profile_scope!("schedule_nodes");
-            self.nodes
+            nodes
                 .iter()
                 .enumerate()
                 .map(|(i, b)| make_chain_node(&**b, i, factory, families))
@@ -466,14 +1405,41 @@ where
         });
         log::trace!("Scheduled nodes execution {:#?}", chains);
 
-        let mut ctx = GraphContext::alloc(
+        let images: Vec<_> = self
+            .images
+            .iter()
+            .zip(resolve_image_kinds(&self.images, self.surface_extent))
+            .map(|(desc, kind)| {
+                (
+                    ImageInfo {
+                        kind,
+                        levels: desc.levels,
+                        format: desc.format,
+                        tiling: rendy_core::hal::image::Tiling::Optimal,
+                        view_caps: rendy_core::hal::image::ViewCapabilities::empty(),
+                        usage: rendy_core::hal::image::Usage::empty(),
+                    },
+                    desc.clear,
+                )
+            })
+            .collect();
+
+        let ctx = GraphContext::alloc(
             factory,
             &chains,
             &self.buffers,
-            &self.images,
+            &images,
+            self.imports,
+            Histories::new(&self.histories),
             self.frames_in_flight,
+            self.aliasing,
         )?;
 
+        unsafe {
+            // Graph was never run.
+            init_read_first_images(factory, &ctx, &chains, 0..images.len());
+        }
+
         log::trace!("Synchronize");
 
         let mut semaphores = 0..;
@@ -483,10 +1449,11 @@ where
         });
         schedule.build_order();
         log::trace!("Schedule: {:#?}", schedule);
+        log_overlap(&schedule);
 
         log::trace!("Build nodes");
-        let mut built_nodes: Vec<_> = (0..self.nodes.len()).map(|_| None).collect();
-        let mut node_descs: Vec<_> = self.nodes.into_iter().map(Some).collect();
+        let mut built_nodes: Vec<_> = (0..nodes.len()).map(|_| None).collect();
+        let mut node_descs: Vec<_> = nodes.into_iter().map(Some).collect();
 
         {
             profile_scope!("build_nodes");
@@ -499,99 +1466,287 @@ where
                         log::trace!("For submission {:#?}", submission.id());
                         let builder = node_descs[submission.node()].take().unwrap();
                         log::trace!("Build node {:#?}", builder);
-                        let node = build_node(
-                            &mut ctx,
-                            builder,
+                        let buffers = resource_ids(builder.buffers());
+                        let images = resource_ids(builder.images());
+                        let node = match builder.build(
+                            &ctx,
                             factory,
                             families.family_by_index_mut(family.id().0),
                             queue.id().index(),
                             aux,
-                            &chains,
-                            &submission,
-                        )
-                        .map_err(GraphBuildError::Node)?;
+                            node_buffers(&ctx, &chains, &submission, buffers.iter().cloned()),
+                            node_images(&ctx, &chains, &submission, images.iter().cloned()),
+                        ) {
+                            Ok(node) => node,
+                            Err(err) => {
+                                unsafe {
+                                    // Graph was never run.
+                                    ctx.dispose(factory);
+                                }
+                                return Err(GraphBuildError::Node(err));
+                            }
+                        };
                         log::debug!("Node built: {:#?}", node);
-                        built_nodes[submission.node()] = Some((node, submission.id().queue()));
+                        built_nodes[submission.node()] = Some(GraphNode {
+                            node,
+                            queue: (family.id().0, queue.id().index()),
+                            buffers,
+                            images,
+                            enabled: true,
+                            bypass: None,
+                        });
                     }
                 }
             }
         }
 
         log::debug!("Create {} semaphores", semaphores.start);
-        let semaphores = (0..semaphores.start)
+        let semaphores = match (0..semaphores.start)
             .map(|_| factory.create_semaphore())
             .collect::<Result<_, _>>()
-            .map_err(GraphBuildError::Semaphore)?;
+        {
+            Ok(semaphores) => semaphores,
+            Err(err) => {
+                unsafe {
+                    // Graph was never run.
+                    ctx.dispose(factory);
+                }
+                return Err(GraphBuildError::Semaphore(err));
+            }
+        };
 
         Ok(Graph {
             device: factory.device().id(),
             ctx,
-            nodes: built_nodes
-                .into_iter()
-                .map(Option::unwrap)
-                .map(|(node, qid)| GraphNode {
-                    node,
-                    queue: (qid.family().0, qid.index()),
-                })
-                .collect(),
+            nodes: built_nodes.into_iter().map(Option::unwrap).collect(),
             schedule,
             semaphores,
             inflight: self.frames_in_flight,
             frames: Frames::new(),
             fences: Vec::new(),
+            chains,
+            image_descs: self.images,
+            images,
+            surface_extent: self.surface_extent,
+            aliasing: self.aliasing,
         })
     }
 }
 
-fn build_node<'a, B: Backend, T: ?Sized>(
+/// Add node that brings all images of the history into one state
+/// after all nodes that use them,
+/// so that each of them can be bound to any id of the history in the next frame.
+fn add_history_node<B, T>(nodes: &mut Vec<Box<dyn NodeBuilder<B, T>>>, history: &HistoryImage)
+where
+    B: Backend,
+    T: ?Sized,
+{
+    let images: Vec<ImageId> = std::iter::once(history.current)
+        .chain(history.previous.iter().cloned())
+        .collect();
+
+    let users: Vec<NodeId> = (0..nodes.len())
+        .filter(|&index| {
+            nodes[index]
+                .images()
+                .iter()
+                .any(|&(id, _)| images.contains(&id))
+        })
+        .map(NodeId)
+        .collect();
+
+    nodes.push(Box::new(HistoryNodeBuilder::new(images, users)));
+}
+
+/// Transition images that nodes read before writing each frame
+/// from undefined layout into the state the previous frame would leave them in,
+/// as their content is supposed to come from the previous frame.
+///
+/// # Safety
+///
+/// Images must not be used by pending commands.
+unsafe fn init_read_first_images<B: Backend>(
+    factory: &Factory<B>,
     ctx: &GraphContext<B>,
-    builder: Box<dyn NodeBuilder<B, T>>,
-    factory: &mut Factory<B>,
-    family: &mut rendy_command::Family<B>,
-    queue: usize,
-    aux: &T,
     chains: &chain::Chains,
-    submission: &chain::Submission<chain::SyncData<usize, usize>>,
-) -> Result<Box<dyn DynNode<B, T>>, NodeBuildError> {
-    let mut buffer_ids: Vec<_> = builder.buffers().into_iter().map(|(id, _)| id).collect();
-    buffer_ids.sort();
-    buffer_ids.dedup();
+    indices: impl IntoIterator<Item = usize>,
+) {
+    for index in indices {
+        let chain = match chains.images.get(&chain::Id(index)) {
+            Some(chain) => chain,
+            None => continue,
+        };
+        let link = &chain.links()[0];
+        // Any image of a history may be bound to an id read first.
+        let history = ctx.histories.slots.contains_key(&index);
+        if (link.access().exclusive() && !history) || ctx.imports.images.contains_key(&index) {
+            continue;
+        }
+
+        let image = ctx
+            .get_image(ImageId(index))
+            .expect("Image referenced from at least one node must be instantiated");
+        // Barrier between frames is recorded after the last link
+        // if it is exclusive, and before the first link otherwise.
+        let last = &chain.links()[chain.links().len() - 1];
+        let (sid, state) = if last.family() == link.family() && last.access().exclusive() {
+            let sid = chain_ends(chain, &chains.schedule).start;
+            (sid, link.submission_state(sid))
+        } else {
+            let (qid, queue) = last.queues().next().unwrap();
+            let sid = chain::SubmissionId::new(qid, queue.last);
+            (sid, last.submission_state(sid))
+        };
+        factory.transition_image(
+            image.clone(),
+            rendy_core::hal::image::SubresourceRange {
+                aspects: image.format().surface_desc().aspects,
+                levels: 0..image.levels(),
+                layers: 0..image.layers(),
+            },
+            ImageStateOrLayout::undefined(),
+            ImageState {
+                queue: QueueId {
+                    family: FamilyId {
+                        device: factory.device().id(),
+                        index: sid.family().0,
+                    },
+                    index: sid.queue().index(),
+                },
+                stage: state.stages,
+                access: state.access,
+                layout: state.layout,
+            },
+        );
+    }
+}
+
+/// Report how nodes are spread among queues.
+fn log_overlap(schedule: &chain::Schedule<chain::SyncData<usize, usize>>) {
+    if schedule.queue_count() < 2 {
+        return;
+    }
 
-    let buffers: Vec<_> = buffer_ids
-        .into_iter()
+    for queue in schedule.iter().flat_map(|family| family.iter()) {
+        log::info!(
+            "Queue {:?} executes nodes {:?}",
+            queue.id(),
+            queue.iter().map(|s| s.node()).collect::<Vec<_>>()
+        );
+    }
+
+    let transfers = schedule
+        .ordered()
+        .flat_map(|submission| {
+            let acquire = &submission.sync().acquire;
+            acquire
+                .buffers
+                .values()
+                .map(|barrier| barrier.families.is_some())
+                .chain(
+                    acquire
+                        .images
+                        .values()
+                        .map(|barrier| barrier.families.is_some()),
+                )
+        })
+        .filter(|&transfer| transfer)
+        .count();
+    log::info!(
+        "Nodes run on {} queues with {} ownership transfers per frame",
+        schedule.queue_count(),
+        transfers
+    );
+}
+
+/// Sorted ids of resources used by node.
+fn resource_ids<I: Ord, A>(resources: Vec<(I, A)>) -> Vec<I> {
+    let mut ids: Vec<_> = resources.into_iter().map(|(id, _)| id).collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+fn node_buffers<B: Backend>(
+    ctx: &GraphContext<B>,
+    chains: &chain::Chains,
+    submission: &chain::Submission<chain::SyncData<usize, usize>>,
+    ids: impl IntoIterator<Item = BufferId>,
+) -> Vec<NodeBuffer> {
+    ids.into_iter()
         .map(|id| {
             let chain_id = chain::Id(id.0);
             let sync = submission.sync();
             let buffer = ctx
                 .get_buffer(id)
                 .expect("Buffer referenced from at least one node must be instantiated");
+            let alias = ctx.buffer_aliases[id.0].as_ref();
+            let import = ctx.imports.buffers.get(&id.0).map(|import| {
+                let chain = &chains.buffers[&chain_id];
+                (import, chain_ends(chain, &chains.schedule))
+            });
             NodeBuffer {
                 id,
                 range: 0..buffer.size(),
-                acquire: sync.acquire.buffers.get(&chain_id).map(
-                    |chain::Barrier { states, families }| BufferBarrier {
-                        states: states.start.0..states.end.0,
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
-                    },
-                ),
-                release: sync.release.buffers.get(&chain_id).map(
-                    |chain::Barrier { states, families }| BufferBarrier {
-                        states: states.start.0..states.end.0,
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
-                    },
-                ),
+                acquire: match (alias, import) {
+                    // Buffer comes from outside of the graph.
+                    (_, Some((import, ends))) if ends.start == submission.id() => {
+                        let state = chains.buffers[&chain_id].links()[0].state();
+                        Some(BufferBarrier {
+                            states: import.states.start..state.access,
+                            stages: import.stages.start..state.stages,
+                            families: None,
+                        })
+                    }
+                    // Memory was used by another buffer since last frame.
+                    (Some(alias), _) if alias.first == submission.id() => {
+                        let state = chains.buffers[&chain_id].links()[0].state();
+                        Some(BufferBarrier {
+                            states: alias.access..state.access,
+                            stages: alias.stages..state.stages,
+                            families: None,
+                        })
+                    }
+                    _ => sync.acquire.buffers.get(&chain_id).map(
+                        |chain::Barrier { states, families }| BufferBarrier {
+                            states: states.start.0..states.end.0,
+                            stages: states.start.2..states.end.2,
+                            families: families.clone(),
+                        },
+                    ),
+                },
+                release: match (alias, import) {
+                    // Buffer is returned in the state outside users expect.
+                    (_, Some((import, ends))) if ends.end == submission.id() => {
+                        let chain = &chains.buffers[&chain_id];
+                        let state = chain.links()[chain.links().len() - 1].state();
+                        Some(BufferBarrier {
+                            states: state.access..import.states.end,
+                            stages: state.stages..import.stages.end,
+                            families: None,
+                        })
+                    }
+                    (Some(alias), _) if alias.last == submission.id() => None,
+                    _ => sync.release.buffers.get(&chain_id).map(
+                        |chain::Barrier { states, families }| BufferBarrier {
+                            states: states.start.0..states.end.0,
+                            stages: states.start.2..states.end.2,
+                            families: families.clone(),
+                        },
+                    ),
+                },
             }
         })
-        .collect();
-
-    let mut image_ids: Vec<_> = builder.images().into_iter().map(|(id, _)| id).collect();
-    image_ids.sort();
-    image_ids.dedup();
+        .collect()
+}
 
-    let images: Vec<_> = image_ids
-        .into_iter()
+fn node_images<B: Backend>(
+    ctx: &GraphContext<B>,
+    chains: &chain::Chains,
+    submission: &chain::Submission<chain::SyncData<usize, usize>>,
+    ids: impl IntoIterator<Item = ImageId>,
+) -> Vec<NodeImage> {
+    ids.into_iter()
         .map(|id| {
             let chain_id = chain::Id(id.0);
             let sync = submission.sync();
@@ -599,6 +1754,11 @@ fn build_node<'a, B: Backend, T: ?Sized>(
             let (image, clear) = ctx
                 .get_image_with_clear(id)
                 .expect("Image referenced from at least one node must be instantiated");
+            let alias = ctx.image_aliases[id.0].as_ref();
+            let import = ctx.imports.images.get(&id.0).map(|import| {
+                let chain = &chains.images[&chain_id];
+                (import, chain_ends(chain, &chains.schedule))
+            });
             NodeImage {
                 id,
                 range: rendy_core::hal::image::SubresourceRange {
@@ -610,31 +1770,207 @@ fn build_node<'a, B: Backend, T: ?Sized>(
                     .submission_state(submission.id())
                     .layout,
                 clear: if link == 0 { clear } else { None },
-                acquire: sync.acquire.images.get(&chain_id).map(
-                    |chain::Barrier { states, families }| ImageBarrier {
-                        states: (
-                            states.start.0,
-                            if link == 0 {
-                                rendy_core::hal::image::Layout::Undefined
-                            } else {
-                                states.start.1
-                            },
-                        )..(states.end.0, states.end.1),
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
-                    },
-                ),
-                release: sync.release.images.get(&chain_id).map(
-                    |chain::Barrier { states, families }| ImageBarrier {
-                        states: (states.start.0, states.start.1)..(states.end.0, states.end.1),
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
-                    },
-                ),
+                load: import.is_some() || loads_image(&chains.images[&chain_id], link),
+                store: import.is_some()
+                    || read_later(&chains.images[&chain_id], link, submission.id()),
+                acquire: match (alias, import) {
+                    // Image comes from outside of the graph.
+                    (_, Some((import, ends))) if ends.start == submission.id() => {
+                        let state = chains.images[&chain_id].links()[0].state();
+                        Some(ImageBarrier {
+                            states: import.states.start..(state.access, state.layout),
+                            stages: import.stages.start..state.stages,
+                            families: None,
+                        })
+                    }
+                    // Memory was used by another image since last frame.
+                    (Some(alias), _) if alias.first == submission.id() => {
+                        let state = chains.images[&chain_id].links()[0].state();
+                        Some(ImageBarrier {
+                            states: (alias.access, rendy_core::hal::image::Layout::Undefined)
+                                ..(state.access, state.layout),
+                            stages: alias.stages..state.stages,
+                            families: None,
+                        })
+                    }
+                    _ => sync.acquire.images.get(&chain_id).map(
+                        |chain::Barrier { states, families }| ImageBarrier {
+                            states: (
+                                states.start.0,
+                                // Content left by the previous frame is discarded
+                                // unless the first link reads it.
+                                if !loads_image(&chains.images[&chain_id], link) {
+                                    rendy_core::hal::image::Layout::Undefined
+                                } else {
+                                    states.start.1
+                                },
+                            )..(states.end.0, states.end.1),
+                            stages: states.start.2..states.end.2,
+                            families: families.clone(),
+                        },
+                    ),
+                },
+                release: match (alias, import) {
+                    // Image is returned in the state outside users expect.
+                    (_, Some((import, ends))) if ends.end == submission.id() => {
+                        let chain = &chains.images[&chain_id];
+                        let state = chain.links()[chain.links().len() - 1].state();
+                        Some(ImageBarrier {
+                            states: (state.access, state.layout)..import.states.end,
+                            stages: state.stages..import.stages.end,
+                            families: None,
+                        })
+                    }
+                    (Some(alias), _) if alias.last == submission.id() => None,
+                    _ => sync.release.images.get(&chain_id).map(
+                        |chain::Barrier { states, families }| ImageBarrier {
+                            states: (states.start.0, states.start.1)..(states.end.0, states.end.1),
+                            stages: states.start.2..states.end.2,
+                            families: families.clone(),
+                        },
+                    ),
+                },
             }
         })
-        .collect();
-    builder.build(ctx, factory, family, queue, aux, buffers, images)
+        .collect()
+}
+
+/// First and last submissions that use the resource, in submit order.
+/// Those are the submissions that get barriers between frames.
+fn chain_ends<R: chain::Resource, S>(
+    chain: &chain::Chain<R>,
+    schedule: &chain::Schedule<S>,
+) -> Range<chain::SubmissionId> {
+    let sids = || {
+        chain.links().iter().flat_map(|link| {
+            link.queues().flat_map(|(qid, queue)| {
+                vec![
+                    chain::SubmissionId::new(qid, queue.first),
+                    chain::SubmissionId::new(qid, queue.last),
+                ]
+            })
+        })
+    };
+    let order = |sid: &chain::SubmissionId| (schedule[*sid].submit_order(), sid.queue().index());
+    sids().min_by_key(order).unwrap()..sids().max_by_key(order).unwrap()
+}
+
+/// Check if image content is loaded by the link.
+/// Content left by the previous frame is discarded
+/// unless the first link only reads it.
+fn loads_image(chain: &chain::Chain<chain::Image>, link: usize) -> bool {
+    link != 0 || !chain.links()[0].access().exclusive()
+}
+
+/// Check if image content is read after the submission.
+/// Other submissions sharing the link are only reading the image,
+/// as writes are exclusive.
+/// Following links are checked until one overwrites the content,
+/// then the first link of the next frame if it loads the content.
+fn read_later(chain: &chain::Chain<chain::Image>, link: usize, sid: chain::SubmissionId) -> bool {
+    use rendy_core::hal::image::Access;
+
+    let links = chain.links();
+    let shared = links[link]
+        .queues()
+        .any(|(qid, queue)| qid != sid.queue() || queue.first != queue.last);
+    if shared {
+        return true;
+    }
+
+    for later in &links[link + 1..] {
+        let access = later.access();
+        if overwrites_image(access) {
+            return false;
+        }
+        if access.intersects(
+            Access::INPUT_ATTACHMENT_READ
+                | Access::SHADER_READ
+                | Access::COLOR_ATTACHMENT_READ
+                | Access::DEPTH_STENCIL_ATTACHMENT_READ
+                | Access::TRANSFER_READ
+                | Access::HOST_READ
+                | Access::MEMORY_READ,
+        ) {
+            return true;
+        }
+    }
+
+    loads_image(chain, 0)
+}
+
+/// Check if image is written by the link without reading previous content.
+fn overwrites_image(access: rendy_core::hal::image::Access) -> bool {
+    use rendy_core::hal::image::Access;
+
+    access.exclusive()
+        && (access
+            & !(Access::COLOR_ATTACHMENT_WRITE
+                | Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | Access::SHADER_WRITE
+                | Access::TRANSFER_WRITE
+                | Access::HOST_WRITE
+                | Access::MEMORY_WRITE))
+            .is_empty()
+}
+
+/// Find kinds of graph images for given surface extent.
+fn resolve_image_kinds(
+    images: &[ImageDesc],
+    surface_extent: Option<rendy_core::hal::window::Extent2D>,
+) -> Vec<rendy_core::hal::image::Kind> {
+    fn scale(
+        extent: rendy_core::hal::image::Extent,
+        scale: f32,
+        layers: rendy_core::hal::image::Layer,
+        samples: rendy_core::hal::image::NumSamples,
+    ) -> rendy_core::hal::image::Kind {
+        rendy_core::hal::image::Kind::D2(
+            ((extent.width as f32 * scale) as u32).max(1),
+            ((extent.height as f32 * scale) as u32).max(1),
+            layers,
+            samples,
+        )
+    }
+
+    let mut kinds: Vec<rendy_core::hal::image::Kind> = Vec::with_capacity(images.len());
+    for desc in images {
+        let kind = match desc.size {
+            ImageSize::Fixed(kind) => kind,
+            ImageSize::Surface {
+                scale: factor,
+                layers,
+                samples,
+            } => {
+                let extent = surface_extent
+                    .expect("Surface extent must be set for images relative to the surface");
+                scale(extent.to_extent(), factor, layers, samples)
+            }
+            ImageSize::Image {
+                image,
+                scale: factor,
+                layers,
+                samples,
+            } => scale(kinds[image.0].extent(), factor, layers, samples),
+        };
+        kinds.push(kind);
+    }
+    kinds
+}
+
+/// Check if buffer is overwritten before it is read in each frame.
+/// Content of such buffer doesn't need to be preserved between frames.
+fn overwrites_buffer(chain: &chain::Chain<chain::Buffer>) -> bool {
+    use rendy_core::hal::buffer::Access;
+
+    let access = chain.links()[0].access();
+    access.exclusive()
+        && (access
+            & !(Access::SHADER_WRITE
+                | Access::TRANSFER_WRITE
+                | Access::HOST_WRITE
+                | Access::MEMORY_WRITE))
+            .is_empty()
 }
 
 fn make_chain_node<B, T>(
@@ -652,6 +1988,9 @@ where
     chain::Node {
         id,
         family: QueueFamilyId(builder.family(factory, families).unwrap().index),
+        async_family: builder
+            .async_family(factory, families)
+            .map(|family| QueueFamilyId(family.index)),
         dependencies: builder.dependencies().into_iter().map(|id| id.0).collect(),
         buffers: buffers
             .into_iter()
@@ -683,3 +2022,82 @@ where
             .collect(),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        rendy_core::hal::image::{Access, Layout, Usage},
+    };
+
+    fn sid(index: usize) -> chain::SubmissionId {
+        chain::SubmissionId::new(chain::QueueId::new(QueueFamilyId(0), 0), index)
+    }
+
+    /// Image chain with a link for each access, submitted in order on one queue.
+    fn chain(accesses: &[Access]) -> chain::Chain<chain::Image> {
+        let mut chain = chain::Chain::new();
+        for (index, &access) in accesses.iter().enumerate() {
+            chain.add_link(chain::Link::new(chain::LinkNode {
+                sid: sid(index),
+                state: chain::State {
+                    access,
+                    layout: Layout::General,
+                    stages: PipelineStage::ALL_COMMANDS,
+                    usage: Usage::empty(),
+                },
+            }));
+        }
+        chain
+    }
+
+    fn ops(chain: &chain::Chain<chain::Image>, link: usize) -> (bool, bool) {
+        (loads_image(chain, link), read_later(chain, link, sid(link)))
+    }
+
+    fn color() -> Access {
+        Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE
+    }
+
+    fn depth() -> Access {
+        Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+    }
+
+    #[test]
+    fn test_single_pass_depth_is_not_kept() {
+        assert_eq!(ops(&chain(&[depth()]), 0), (false, false));
+    }
+
+    #[test]
+    fn test_read_by_next_pass_is_stored() {
+        let image = chain(&[color(), Access::SHADER_READ]);
+        assert_eq!(ops(&image, 0), (false, true));
+
+        // Next frame discards content as the first link writes it.
+        assert_eq!(ops(&image, 1), (true, false));
+    }
+
+    #[test]
+    fn test_read_first_is_loaded_and_stored() {
+        // History image read before it is written again.
+        let image = chain(&[Access::SHADER_READ, color()]);
+        assert_eq!(ops(&image, 0), (true, true));
+        assert_eq!(ops(&image, 1), (true, true));
+    }
+
+    #[test]
+    fn test_overwritten_by_later_pass_is_not_stored() {
+        let image = chain(&[color(), Access::COLOR_ATTACHMENT_WRITE, Access::SHADER_READ]);
+        assert_eq!(ops(&image, 0), (false, false));
+        assert!(read_later(&image, 1, sid(1)));
+
+        let image = chain(&[depth(), Access::TRANSFER_WRITE, Access::TRANSFER_READ]);
+        assert_eq!(ops(&image, 0), (false, false));
+    }
+
+    #[test]
+    fn test_later_attachment_use_is_stored() {
+        // Later pass may blend with the content.
+        assert_eq!(ops(&chain(&[color(), color()]), 0), (false, true));
+    }
+}
@@ -0,0 +1,441 @@
+//!
+//! Compute node built from simple compute pipeline.
+//! Counterpart of `SimpleGraphicsPipeline` for nodes that only dispatch compute work.
+//!
+
+use {
+    crate::{
+        command::{
+            CommandPool, Compute, EncoderCommon, Family, IndividualReset, MultiShot,
+            NoSimultaneousUse, QueueId, Submit,
+        },
+        core::uses_pipeline_barriers,
+        factory::Factory,
+        frame::{
+            cirque::{CirqueRef, CommandCirque},
+            Frames,
+        },
+        graph::GraphContext,
+        node::{
+            gfx_acquire_barriers, gfx_release_barriers, render::PrepareResult, BufferAccess,
+            DescBuilder, ImageAccess, Node, NodeBuffer, NodeBuildError, NodeDesc, NodeImage,
+            NodeSubmittable,
+        },
+        resource::{ComputePipeline, DescriptorSetLayout, Escape, Handle, PipelineLayout},
+    },
+    rendy_core::hal::Backend,
+    rendy_shader::{ShaderSetBuilder, SpecConstantSet},
+};
+
+pub use crate::core::types::{Layout, SetLayout};
+
+/// Descriptor for simple compute pipeline implementation.
+pub trait SimpleComputePipelineDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
+    /// Simple compute pipeline implementation
+    type Pipeline: SimpleComputePipeline<B, T>;
+
+    /// Make simple compute node builder.
+    fn builder(self) -> DescBuilder<B, T, SimpleComputeNodeDesc<Self>>
+    where
+        Self: Sized + 'static,
+    {
+        SimpleComputeNodeDesc { inner: self }.builder()
+    }
+
+    /// Get set or buffer resources the node uses.
+    fn buffers(&self) -> Vec<BufferAccess> {
+        Vec::new()
+    }
+
+    /// Get set or image resources the node uses.
+    fn images(&self) -> Vec<ImageAccess> {
+        Vec::new()
+    }
+
+    /// Shaders of the pipeline.
+    /// Set must contain compute shader. Other stages are ignored.
+    fn shaders(&self) -> &ShaderSetBuilder;
+
+    /// Specialization constants for the shaders.
+    fn specialization(&self) -> SpecConstantSet {
+        SpecConstantSet::default()
+    }
+
+    /// Layout for compute pipeline.
+    ///
+    /// With `spirv-reflection` feature enabled default implementation reflects layout from `shaders`.
+    /// Otherwise it returns empty layout.
+    fn layout(&self) -> Result<Layout, rendy_core::hal::pso::CreationError> {
+        #[cfg(feature = "spirv-reflection")]
+        return self
+            .shaders()
+            .reflect()
+            .and_then(|reflection| reflection.layout())
+            .map_err(|e| {
+                log::warn!("Failed to reflect compute pipeline layout {:?}", e);
+                rendy_core::hal::pso::CreationError::Other
+            });
+
+        #[cfg(not(feature = "spirv-reflection"))]
+        return Ok(Layout {
+            sets: Vec::new(),
+            push_constants: Vec::new(),
+        });
+    }
+
+    /// Build pipeline instance.
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+        set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<Self::Pipeline, NodeBuildError>;
+}
+
+/// Simple compute pipeline.
+pub trait SimpleComputePipeline<B: Backend, T: ?Sized>:
+    std::fmt::Debug + Sized + Send + Sync + 'static
+{
+    /// This pipeline descriptor.
+    type Desc: SimpleComputePipelineDesc<B, T, Pipeline = Self>;
+
+    /// Make simple compute node builder.
+    fn builder() -> DescBuilder<B, T, SimpleComputeNodeDesc<Self::Desc>>
+    where
+        Self::Desc: Default + 'static,
+    {
+        Self::Desc::default().builder()
+    }
+
+    /// Prepare to record dispatch commands.
+    ///
+    /// Should return `PrepareResult::DrawRecord` if commands must be re-recorded.
+    /// Descriptors referencing history images must match `GraphContext::history_phase`.
+    fn prepare(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+        _index: usize,
+        _aux: &T,
+    ) -> PrepareResult {
+        PrepareResult::DrawRecord
+    }
+
+    /// Record dispatch commands to the command buffer provided.
+    /// Pipeline is already bound and barriers for graph resources are recorded by the node.
+    fn dispatch(
+        &mut self,
+        layout: &B::PipelineLayout,
+        encoder: EncoderCommon<'_, B, Compute>,
+        index: usize,
+        aux: &T,
+    );
+
+    /// Update pipeline after images it uses were reallocated by `Graph::resize`.
+    /// See [`DynNode::resize`](../trait.DynNode.html#tymethod.resize).
+    fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+        set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError>;
+
+    /// Free all resources and destroy pipeline instance.
+    fn dispose(self, factory: &mut Factory<B>, aux: &T);
+}
+
+/// Descriptor for simple compute node.
+#[derive(Debug)]
+pub struct SimpleComputeNodeDesc<P: std::fmt::Debug> {
+    inner: P,
+}
+
+/// Node that consist of simple compute pipeline.
+/// Records commands into per-frame command buffers
+/// and wraps them with barriers for graph resources.
+#[derive(Debug)]
+pub struct SimpleComputeNode<B: Backend, P> {
+    set_layouts: Vec<Handle<DescriptorSetLayout<B>>>,
+    pipeline_layout: Handle<PipelineLayout<B>>,
+    compute_pipeline: Escape<ComputePipeline<B>>,
+    pipeline: P,
+    queue: QueueId,
+    command_pool: CommandPool<B, Compute, IndividualReset>,
+    command_cirque: CommandCirque<B, Compute>,
+    barriers: bool,
+    buffers: Vec<NodeBuffer>,
+    images: Vec<NodeImage>,
+}
+
+impl<B, T, P> NodeDesc<B, T> for SimpleComputeNodeDesc<P>
+where
+    B: Backend,
+    T: ?Sized,
+    P: SimpleComputePipelineDesc<B, T> + 'static,
+{
+    type Node = SimpleComputeNode<B, P::Pipeline>;
+
+    fn buffers(&self) -> Vec<BufferAccess> {
+        self.inner.buffers()
+    }
+
+    fn images(&self) -> Vec<ImageAccess> {
+        self.inner.images()
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        queue: usize,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Self::Node, NodeBuildError> {
+        let queue = QueueId {
+            family: family.id(),
+            index: queue,
+        };
+
+        let layout = self.inner.layout().map_err(NodeBuildError::Pipeline)?;
+
+        log::trace!("Load compute shader set");
+        let mut shader_set = self
+            .inner
+            .shaders()
+            .build(factory, self.inner.specialization())
+            .map_err(rendy_core::hal::pso::CreationError::Shader)
+            .map_err(NodeBuildError::Pipeline)?;
+
+        let set_layouts = layout
+            .sets
+            .into_iter()
+            .map(|set| factory.get_descriptor_set_layout(set.bindings))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                shader_set.dispose(factory);
+                NodeBuildError::OutOfMemory(e)
+            })?;
+
+        let pipeline_layout = factory
+            .get_pipeline_layout(set_layouts.clone(), layout.push_constants)
+            .map_err(|e| {
+                shader_set.dispose(factory);
+                NodeBuildError::OutOfMemory(e)
+            })?;
+
+        let compute_pipeline = match shader_set.raw_compute() {
+            Err(e) => {
+                log::warn!("Shader error {:?}", e);
+                Err(rendy_core::hal::pso::CreationError::Other)
+            }
+            Ok(shader) => unsafe {
+                factory.create_compute_pipeline(
+                    &rendy_core::hal::pso::ComputePipelineDesc {
+                        shader,
+                        layout: pipeline_layout.raw(),
+                        flags: rendy_core::hal::pso::PipelineCreationFlags::empty(),
+                        parent: rendy_core::hal::pso::BasePipeline::None,
+                    },
+                    pipeline_layout.clone(),
+                )
+            },
+        };
+
+        shader_set.dispose(factory);
+
+        let compute_pipeline = compute_pipeline.map_err(NodeBuildError::Pipeline)?;
+
+        let pipeline = self.inner.build(
+            ctx,
+            factory,
+            queue,
+            aux,
+            buffers.clone(),
+            images.clone(),
+            &set_layouts,
+        )?;
+
+        let command_pool = match factory.create_command_pool(family) {
+            Ok(command_pool) => command_pool,
+            Err(e) => {
+                pipeline.dispose(factory, aux);
+                return Err(NodeBuildError::OutOfMemory(e));
+            }
+        };
+
+        let command_pool = command_pool
+            .with_capability()
+            .expect("Graph must specify family that supports `Compute`");
+
+        Ok(SimpleComputeNode {
+            set_layouts,
+            pipeline_layout,
+            compute_pipeline,
+            pipeline,
+            queue,
+            command_pool,
+            command_cirque: CommandCirque::new(),
+            barriers: uses_pipeline_barriers::<B>(factory.device()),
+            buffers,
+            images,
+        })
+    }
+}
+
+impl<'a, B, P> NodeSubmittable<'a, B> for SimpleComputeNode<B, P>
+where
+    B: Backend,
+{
+    type Submittable = Submit<B>;
+    type Submittables = Option<Submit<B>>;
+}
+
+impl<B, T, P> Node<B, T> for SimpleComputeNode<B, P>
+where
+    B: Backend,
+    T: ?Sized,
+    P: SimpleComputePipeline<B, T>,
+{
+    type Capability = Compute;
+
+    fn run<'a>(
+        &'a mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        aux: &T,
+        frames: &'a Frames<B>,
+    ) -> Option<Submit<B>> {
+        let SimpleComputeNode {
+            set_layouts,
+            pipeline_layout,
+            compute_pipeline,
+            pipeline,
+            queue,
+            command_pool,
+            command_cirque,
+            barriers,
+            buffers,
+            images,
+        } = self;
+
+        let submit = command_cirque.encode(frames, command_pool, |mut cbuf| {
+            let index = cbuf.index();
+
+            let force_record = pipeline
+                .prepare(ctx, factory, *queue, set_layouts, index, aux)
+                .force_record();
+
+            // Recorded barriers reference images bound in one phase.
+            if force_record || ctx.history_period() > 1 {
+                cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
+            }
+
+            cbuf.or_init(|cbuf| {
+                let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
+                let mut encoder = cbuf.encoder();
+
+                if *barriers {
+                    let (stages, barriers) = gfx_acquire_barriers(ctx, &*buffers, &*images);
+                    if !barriers.is_empty() {
+                        log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+                        unsafe {
+                            encoder.pipeline_barrier(
+                                stages,
+                                rendy_core::hal::memory::Dependencies::empty(),
+                                barriers,
+                            );
+                        }
+                    }
+                }
+
+                encoder.bind_compute_pipeline(compute_pipeline.raw());
+                pipeline.dispatch(pipeline_layout.raw(), encoder.reborrow(), index, aux);
+
+                if *barriers {
+                    let (stages, barriers) = gfx_release_barriers(ctx, &*buffers, &*images);
+                    if !barriers.is_empty() {
+                        log::trace!("Release {:?} : {:#?}", stages, barriers);
+                        unsafe {
+                            encoder.pipeline_barrier(
+                                stages,
+                                rendy_core::hal::memory::Dependencies::empty(),
+                                barriers,
+                            );
+                        }
+                    }
+                }
+
+                cbuf.finish()
+            })
+        });
+
+        Some(submit)
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        self.pipeline.resize(
+            ctx,
+            factory,
+            aux,
+            buffers.clone(),
+            images.clone(),
+            &self.set_layouts,
+        )?;
+
+        // Recorded barriers reference reallocated images.
+        let pool = &mut self.command_pool;
+        std::mem::replace(&mut self.command_cirque, CommandCirque::new()).dispose(|buffer| {
+            buffer.either_with(
+                &mut *pool,
+                |pool, executable| pool.free_buffers(Some(executable)),
+                |pool, pending| {
+                    let executable = pending.mark_complete();
+                    pool.free_buffers(Some(executable))
+                },
+            );
+        });
+
+        self.buffers = buffers;
+        self.images = images;
+        Ok(())
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, aux: &T) {
+        self.pipeline.dispose(factory, aux);
+
+        let pool = &mut self.command_pool;
+        self.command_cirque.dispose(|buffer| {
+            buffer.either_with(
+                &mut *pool,
+                |pool, executable| pool.free_buffers(Some(executable)),
+                |pool, pending| {
+                    let executable = pending.mark_complete();
+                    pool.free_buffers(Some(executable))
+                },
+            );
+        });
+        factory.destroy_command_pool(self.command_pool.with_queue_type());
+
+        drop(self.compute_pipeline);
+        drop(self.pipeline_layout);
+        drop(self.set_layouts);
+    }
+}
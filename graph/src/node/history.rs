@@ -0,0 +1,156 @@
+//!
+//! Node that prepares history images to be bound to other ids in the next frame.
+//! Added by graph for each history created with `GraphBuilder::create_history_image`.
+//!
+
+use {
+    crate::{
+        command::{
+            CommandPool, Families, Family, FamilyId, Graphics, IndividualReset, PrimaryLevel,
+            SimultaneousUse, Submit,
+        },
+        factory::Factory,
+        frame::Frames,
+        graph::GraphContext,
+        node::{
+            free_barrier_commands, record_node_barriers, BarrierCommands, BufferAccess, DynNode,
+            ImageAccess, Node, NodeBuffer, NodeBuildError, NodeBuilder, NodeImage, NodeSubmittable,
+        },
+        BufferId, ImageId, NodeId,
+    },
+    rendy_core::hal::Backend,
+};
+
+/// Builder of the node that brings all images of one history into the same state.
+#[derive(Debug)]
+pub(crate) struct HistoryNodeBuilder {
+    images: Vec<ImageId>,
+    dependencies: Vec<NodeId>,
+}
+
+impl HistoryNodeBuilder {
+    pub(crate) fn new(images: Vec<ImageId>, dependencies: Vec<NodeId>) -> Self {
+        HistoryNodeBuilder {
+            images,
+            dependencies,
+        }
+    }
+}
+
+impl<B, T> NodeBuilder<B, T> for HistoryNodeBuilder
+where
+    B: Backend,
+    T: ?Sized,
+{
+    fn family(&self, _factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId> {
+        families.with_capability::<Graphics>()
+    }
+
+    fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
+        Vec::new()
+    }
+
+    fn images(&self) -> Vec<(ImageId, ImageAccess)> {
+        // Previous images are usually sampled,
+        // so readers find them in the layout they expect.
+        self.images
+            .iter()
+            .map(|&id| {
+                (
+                    id,
+                    ImageAccess {
+                        access: rendy_core::hal::image::Access::SHADER_READ,
+                        usage: rendy_core::hal::image::Usage::SAMPLED,
+                        layout: rendy_core::hal::image::Layout::ShaderReadOnlyOptimal,
+                        stages: rendy_core::hal::pso::PipelineStage::FRAGMENT_SHADER,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn dependencies(&self) -> Vec<NodeId> {
+        self.dependencies.clone()
+    }
+
+    fn build<'a>(
+        self: Box<Self>,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn DynNode<B, T>>, NodeBuildError> {
+        assert!(buffers.is_empty());
+
+        let mut pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?
+            .with_capability()
+            .expect("Graph must specify family that supports `Graphics`");
+
+        let commands = record_node_barriers(ctx, factory, &mut pool, &[], &images);
+
+        Ok(Box::new((HistoryNode { pool, commands },)))
+    }
+}
+
+/// Node that only records barriers for images of one history.
+/// Commands are recorded once for each phase of history images.
+#[derive(Debug)]
+pub(crate) struct HistoryNode<B: Backend> {
+    pool: CommandPool<B, Graphics, IndividualReset>,
+    commands: Vec<Option<BarrierCommands<B, Graphics, PrimaryLevel>>>,
+}
+
+impl<'a, B> NodeSubmittable<'a, B> for HistoryNode<B>
+where
+    B: Backend,
+{
+    type Submittable = &'a Submit<B, SimultaneousUse>;
+    type Submittables = Option<&'a Submit<B, SimultaneousUse>>;
+}
+
+impl<B, T> Node<B, T> for HistoryNode<B>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    type Capability = Graphics;
+
+    fn run<'a>(
+        &'a mut self,
+        ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        _aux: &T,
+        _frames: &'a Frames<B>,
+    ) -> Option<&'a Submit<B, SimultaneousUse>> {
+        self.commands[ctx.history_phase()]
+            .as_ref()
+            .map(|(submit, _)| submit)
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _aux: &T,
+        _buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        // Recorded commands reference reallocated images.
+        let commands = record_node_barriers(ctx, factory, &mut self.pool, &[], &images);
+        free_barrier_commands(
+            &mut self.pool,
+            std::mem::replace(&mut self.commands, commands),
+        );
+        Ok(())
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &T) {
+        free_barrier_commands(&mut self.pool, self.commands);
+        factory.destroy_command_pool(self.pool);
+    }
+}
@@ -1,12 +1,19 @@
 //! Defines node - building block for framegraph.
 //!
 
+pub mod compute;
+mod history;
 pub mod present;
 pub mod render;
 
 use {
     crate::{
-        command::{Capability, Families, Family, FamilyId, Fence, Queue, Submission, Submittable},
+        command::{
+            Capability, CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId,
+            Fence, IndividualReset, Level, MultiShot, PendingState, PrimaryLevel, Queue,
+            SimultaneousUse, Submission, Submit, Submittable, Supports,
+        },
+        core::uses_pipeline_barriers,
         factory::{Factory, UploadError},
         frame::Frames,
         graph::GraphContext,
@@ -118,6 +125,17 @@ pub struct NodeImage {
     /// Specify that node should clear image to this value.
     pub clear: Option<rendy_core::hal::command::ClearValue>,
 
+    /// Image content written by previous nodes must be preserved.
+    /// `false` when node is the first to use the image this frame,
+    /// e.g. render pass can use `AttachmentLoadOp::DontCare`.
+    pub load: bool,
+
+    /// Image content written by this node is read by subsequent nodes
+    /// later this frame or by the first node that uses it next frame.
+    /// `false` when nothing reads the content,
+    /// e.g. render pass can use `AttachmentStoreOp::DontCare`.
+    pub store: bool,
+
     /// Acquire barrier.
     /// Node implementation must insert it before first command that uses the image.
     /// Barrier must be inserted even if this node doesn't use the image.
@@ -164,6 +182,21 @@ pub trait Node<B: Backend, T: ?Sized>:
         frames: &'a Frames<B>,
     ) -> <Self as NodeSubmittable<'a, B>>::Submittables;
 
+    /// Update the node after images it uses were reallocated by `Graph::resize`.
+    /// See [`DynNode::resize`](trait.DynNode.html#tymethod.resize).
+    ///
+    /// # Safety
+    ///
+    /// Must be called after waiting for device idle.
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError>;
+
     /// Dispose of the node.
     ///
     /// # Safety
@@ -232,6 +265,24 @@ pub trait DynNode<B: Backend, T: ?Sized>: std::fmt::Debug + Sync + Send {
         fence: Option<&mut Fence<B>>,
     );
 
+    /// Update the node after images it uses were reallocated by `Graph::resize`.
+    /// `buffers` and `images` are built the same way as for the node builder.
+    /// Old images are destroyed before this is called,
+    /// so views, descriptors and framebuffers referencing them must be recreated.
+    /// Nodes that keep nothing derived from graph images may just return `Ok(())`.
+    ///
+    /// # Safety
+    ///
+    /// Must be called after waiting for device idle.
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError>;
+
     /// Dispose of the node.
     ///
     /// # Safety
@@ -269,6 +320,17 @@ where
         )
     }
 
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        Node::resize(&mut self.0, ctx, factory, aux, buffers, images)
+    }
+
     unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         N::dispose(self.0, factory, aux);
     }
@@ -345,6 +407,14 @@ pub trait NodeBuilder<B: Backend, T: ?Sized>: std::fmt::Debug {
     /// Pick family for this node to be executed onto.
     fn family(&self, factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId>;
 
+    /// Pick family onto which node can be moved
+    /// to execute asynchronously to nodes on other families.
+    /// Graph moves the node there when that lets it start earlier.
+    /// Returns `None` by default.
+    fn async_family(&self, _factory: &mut Factory<B>, _families: &Families<B>) -> Option<FamilyId> {
+        None
+    }
+
     /// Get buffer accessed by the node.
     fn buffers(&self) -> Vec<(BufferId, BufferAccess)>;
 
@@ -373,6 +443,7 @@ pub struct DescBuilder<B: Backend, T: ?Sized, N> {
     buffers: Vec<BufferId>,
     images: Vec<ImageId>,
     dependencies: Vec<NodeId>,
+    prefer_async: bool,
     marker: std::marker::PhantomData<fn(B, &T)>,
 }
 
@@ -388,6 +459,7 @@ where
             .field("buffers", &self.buffers)
             .field("images", &self.images)
             .field("dependencies", &self.dependencies)
+            .field("prefer_async", &self.prefer_async)
             .finish()
     }
 }
@@ -404,6 +476,7 @@ where
             buffers: Vec::new(),
             images: Vec::new(),
             dependencies: Vec::new(),
+            prefer_async: false,
             marker: std::marker::PhantomData,
         }
     }
@@ -448,6 +521,20 @@ where
         self.add_dependency(dependency);
         self
     }
+
+    /// Allow node to run on a family without graphics support,
+    /// e.g. async compute, if device has one.
+    pub fn set_prefer_async(&mut self, prefer_async: bool) -> &mut Self {
+        self.prefer_async = prefer_async;
+        self
+    }
+
+    /// Allow node to run on a family without graphics support,
+    /// e.g. async compute, if device has one.
+    pub fn with_prefer_async(mut self, prefer_async: bool) -> Self {
+        self.set_prefer_async(prefer_async);
+        self
+    }
 }
 
 impl<B, T, N> NodeBuilder<B, T> for DescBuilder<B, T, N>
@@ -460,6 +547,22 @@ where
         families.with_capability::<<N::Node as Node<B, T>>::Capability>()
     }
 
+    fn async_family(&self, factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId> {
+        if !self.prefer_async {
+            return None;
+        }
+
+        let family = self.family(factory, families)?;
+        families.find(|candidate| {
+            candidate.id() != family
+                && !candidate.capability().supports_graphics()
+                && Supports::<<N::Node as Node<B, T>>::Capability>::supports(
+                    &candidate.capability(),
+                )
+                .is_some()
+        })
+    }
+
     fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
         let desc_buffers = self.desc.buffers();
         assert_eq!(self.buffers.len(), desc_buffers.len());
@@ -502,6 +605,20 @@ pub fn gfx_acquire_barriers<'a, 'b, B: Backend>(
 ) -> (
     std::ops::Range<rendy_core::hal::pso::PipelineStage>,
     Vec<rendy_core::hal::memory::Barrier<'a, B>>,
+) {
+    gfx_acquire_barriers_in_phase(ctx, ctx.history_phase(), buffers, images)
+}
+
+/// Convert graph barriers into gfx barriers
+/// for images bound in given phase of history images.
+pub fn gfx_acquire_barriers_in_phase<'a, 'b, B: Backend>(
+    ctx: &'a GraphContext<B>,
+    phase: usize,
+    buffers: impl IntoIterator<Item = &'b NodeBuffer>,
+    images: impl IntoIterator<Item = &'b NodeImage>,
+) -> (
+    std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+    Vec<rendy_core::hal::memory::Barrier<'a, B>>,
 ) {
     let mut bstart = rendy_core::hal::pso::PipelineStage::empty();
     let mut bend = rendy_core::hal::pso::PipelineStage::empty();
@@ -535,7 +652,10 @@ pub fn gfx_acquire_barriers<'a, 'b, B: Backend>(
                 rendy_core::hal::memory::Barrier::Image {
                     states: acquire.states.clone(),
                     families: acquire.families.clone(),
-                    target: ctx.get_image(image.id).expect("Image does not exist").raw(),
+                    target: ctx
+                        .get_image_in_phase(image.id, phase)
+                        .expect("Image does not exist")
+                        .raw(),
                     range: image.range.clone(),
                 }
             })
@@ -553,6 +673,20 @@ pub fn gfx_release_barriers<'a, B: Backend>(
 ) -> (
     std::ops::Range<rendy_core::hal::pso::PipelineStage>,
     Vec<rendy_core::hal::memory::Barrier<'a, B>>,
+) {
+    gfx_release_barriers_in_phase(ctx, ctx.history_phase(), buffers, images)
+}
+
+/// Convert graph barriers into gfx barriers
+/// for images bound in given phase of history images.
+pub fn gfx_release_barriers_in_phase<'a, 'b, B: Backend>(
+    ctx: &'a GraphContext<B>,
+    phase: usize,
+    buffers: impl IntoIterator<Item = &'b NodeBuffer>,
+    images: impl IntoIterator<Item = &'b NodeImage>,
+) -> (
+    std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+    Vec<rendy_core::hal::memory::Barrier<'a, B>>,
 ) {
     let mut bstart = rendy_core::hal::pso::PipelineStage::empty();
     let mut bend = rendy_core::hal::pso::PipelineStage::empty();
@@ -586,7 +720,10 @@ pub fn gfx_release_barriers<'a, B: Backend>(
                 rendy_core::hal::memory::Barrier::Image {
                     states: release.states.clone(),
                     families: release.families.clone(),
-                    target: ctx.get_image(image.id).expect("Image does not exist").raw(),
+                    target: ctx
+                        .get_image_in_phase(image.id, phase)
+                        .expect("Image does not exist")
+                        .raw(),
                     range: image.range.clone(),
                 }
             })
@@ -595,3 +732,103 @@ pub fn gfx_release_barriers<'a, B: Backend>(
 
     (bstart | istart..bend | iend, barriers)
 }
+
+/// Command buffer with recorded barriers that is submitted every frame.
+pub(crate) type BarrierCommands<B, C, L> = (
+    Submit<B, SimultaneousUse, L>,
+    CommandBuffer<
+        B,
+        C,
+        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
+        L,
+        IndividualReset,
+    >,
+);
+
+/// Record barriers of all batches into one command buffer.
+/// Returns `None` if there are no barriers.
+pub(crate) fn record_barrier_commands<'a, B, C, L>(
+    pool: &mut CommandPool<B, C, IndividualReset>,
+    batches: impl IntoIterator<
+        Item = (
+            std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+            Vec<rendy_core::hal::memory::Barrier<'a, B>>,
+        ),
+    >,
+) -> Option<BarrierCommands<B, C, L>>
+where
+    B: Backend,
+    C: Capability,
+    L: Level,
+{
+    let batches = batches
+        .into_iter()
+        .filter(|(_, barriers)| !barriers.is_empty())
+        .collect::<Vec<_>>();
+    if batches.is_empty() {
+        return None;
+    }
+
+    let initial = pool.allocate_buffers(1).pop().unwrap();
+    let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+    for (stages, barriers) in batches {
+        log::trace!("Barriers {:?} : {:#?}", stages, barriers);
+        unsafe {
+            recording.encoder().pipeline_barrier(
+                stages,
+                rendy_core::hal::memory::Dependencies::empty(),
+                barriers,
+            );
+        }
+    }
+
+    Some(recording.finish().submit())
+}
+
+/// Record acquire and release barriers of the node into one command buffer
+/// for each phase of history images.
+pub(crate) fn record_node_barriers<B, C>(
+    ctx: &GraphContext<B>,
+    factory: &Factory<B>,
+    pool: &mut CommandPool<B, C, IndividualReset>,
+    buffers: &[NodeBuffer],
+    images: &[NodeImage],
+) -> Vec<Option<BarrierCommands<B, C, PrimaryLevel>>>
+where
+    B: Backend,
+    C: Capability,
+{
+    (0..ctx.history_period())
+        .map(|phase| {
+            if !uses_pipeline_barriers::<B>(factory.device()) {
+                return None;
+            }
+
+            record_barrier_commands(
+                pool,
+                vec![
+                    gfx_acquire_barriers_in_phase(ctx, phase, buffers, images),
+                    gfx_release_barriers_in_phase(ctx, phase, buffers, images),
+                ],
+            )
+        })
+        .collect()
+}
+
+/// Free command buffers with recorded barriers.
+///
+/// # Safety
+///
+/// Commands must not be pending.
+pub(crate) unsafe fn free_barrier_commands<B, C, L>(
+    pool: &mut CommandPool<B, C, IndividualReset>,
+    commands: impl IntoIterator<Item = Option<BarrierCommands<B, C, L>>>,
+) where
+    B: Backend,
+    L: Level,
+{
+    for (submit, buffer) in commands.into_iter().flatten() {
+        drop(submit);
+        pool.free_buffers(Some(buffer.mark_complete()));
+    }
+}
@@ -2,30 +2,36 @@
 
 use crate::{
     command::{
-        CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence, MultiShot,
-        PendingState, Queue, SimultaneousUse, Submission, Submit,
+        CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence,
+        InitialState, MultiShot, PendingState, Queue, SimultaneousUse, Submission, Submit,
     },
     factory::Factory,
     frame::Frames,
     graph::GraphContext,
     node::{
-        gfx_acquire_barriers, gfx_release_barriers, BufferAccess, DynNode, ImageAccess, NodeBuffer,
-        NodeBuildError, NodeBuilder, NodeImage,
+        gfx_acquire_barriers_in_phase, gfx_release_barriers_in_phase, BufferAccess, DynNode,
+        ImageAccess, NodeBuffer, NodeBuildError, NodeBuilder, NodeImage,
     },
-    wsi::{Surface, Target},
+    resource::Image,
+    wsi::{Surface, SwapchainError, Target},
     BufferId, ImageId, NodeId,
 };
 
-#[derive(Debug)]
-struct ForImage<B: rendy_core::hal::Backend> {
-    acquire: B::Semaphore,
-    release: B::Semaphore,
-    submit: Submit<B, SimultaneousUse>,
-    buffer: CommandBuffer<
+type PresentCommands<B> = (
+    Submit<B, SimultaneousUse>,
+    CommandBuffer<
         B,
         rendy_core::hal::queue::QueueType,
         PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
     >,
+);
+
+#[derive(Debug)]
+struct ForImage<B: rendy_core::hal::Backend> {
+    acquire: B::Semaphore,
+    release: B::Semaphore,
+    /// Commands for each phase of history images.
+    commands: Vec<PresentCommands<B>>,
 }
 
 impl<B: rendy_core::hal::Backend> ForImage<B> {
@@ -34,10 +40,12 @@ impl<B: rendy_core::hal::Backend> ForImage<B> {
         factory: &Factory<B>,
         pool: &mut CommandPool<B, rendy_core::hal::queue::QueueType>,
     ) {
-        drop(self.submit);
+        for (submit, buffer) in self.commands {
+            drop(submit);
+            pool.free_buffers(Some(buffer.mark_complete()));
+        }
         factory.destroy_semaphore(self.acquire);
         factory.destroy_semaphore(self.release);
-        pool.free_buffers(Some(self.buffer.mark_complete()));
     }
 }
 
@@ -103,151 +111,26 @@ fn create_per_image_data<B: rendy_core::hal::Backend>(
     target: &Target<B>,
     blit_filter: rendy_core::hal::image::Filter,
 ) -> Vec<ForImage<B>> {
-    let input_image_res = ctx.get_image(input_image.id).expect("Image does not exist");
-
-    let target_images = target.backbuffer();
-    let buffers = pool.allocate_buffers(target_images.len());
-    target_images
+    target
+        .backbuffer()
         .iter()
-        .zip(buffers)
-        .map(|(target_image, buf_initial)| {
-            let mut buf_recording = buf_initial.begin(MultiShot(SimultaneousUse), ());
-            let mut encoder = buf_recording.encoder();
-            let (mut stages, mut barriers) =
-                gfx_acquire_barriers(ctx, None, Some(input_image));
-            stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-            stages.end |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-            barriers.push(rendy_core::hal::memory::Barrier::Image {
-                states: (
-                    rendy_core::hal::image::Access::empty(),
-                    rendy_core::hal::image::Layout::Undefined,
-                )
-                    ..(
-                        rendy_core::hal::image::Access::TRANSFER_WRITE,
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                    ),
-                families: None,
-                target: target_image.raw(),
-                range: rendy_core::hal::image::SubresourceRange {
-                    aspects: rendy_core::hal::format::Aspects::COLOR,
-                    levels: 0..1,
-                    layers: 0..1,
-                },
-            });
-            log::trace!("Acquire {:?} : {:#?}", stages, barriers);
-            unsafe {
-                encoder.pipeline_barrier(
-                    stages,
-                    rendy_core::hal::memory::Dependencies::empty(),
-                    barriers,
-                );
-            }
-
-            let extents_differ = target_image.kind().extent() != input_image_res.kind().extent();
-            let formats_differ = target_image.format() != input_image_res.format();
-
-            if extents_differ || formats_differ
-            {
-                if formats_differ {
-                    log::debug!("Present node is blitting because target format {:?} doesnt match image format {:?}", target_image.format(), input_image_res.format());
-                }
-                if extents_differ {
-                    log::debug!("Present node is blitting because target extent {:?} doesnt match image extent {:?}", target_image.kind().extent(), input_image_res.kind().extent());
-                }
-                unsafe {
-                    encoder.blit_image(
-                        input_image_res.raw(),
-                        input_image.layout,
-                        target_image.raw(),
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
+        .map(|target_image| {
+            let commands = (0..ctx.history_period())
+                .zip(pool.allocate_buffers(ctx.history_period()))
+                .map(|(phase, buf_initial)| {
+                    record_commands(
+                        ctx,
+                        phase,
+                        input_image,
+                        buf_initial,
+                        target_image,
                         blit_filter,
-                        Some(rendy_core::hal::command::ImageBlit {
-                            src_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: input_image.range.aspects,
-                                level: 0,
-                                layers: input_image.range.layers.start..input_image.range.layers.start + 1,
-                            },
-                            src_bounds: rendy_core::hal::image::Offset::ZERO
-                                .into_bounds(&input_image_res.kind().extent()),
-                            dst_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: rendy_core::hal::format::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            dst_bounds: rendy_core::hal::image::Offset::ZERO
-                                .into_bounds(&target_image.kind().extent()),
-                        }),
-                    );
-                }
-            } else {
-                log::debug!("Present node is copying");
-                unsafe {
-                    encoder.copy_image(
-                        input_image_res.raw(),
-                        input_image.layout,
-                        target_image.raw(),
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                        Some(rendy_core::hal::command::ImageCopy {
-                            src_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: input_image.range.aspects,
-                                level: 0,
-                                layers: input_image.range.layers.start..input_image.range.layers.start + 1,
-                            },
-                            src_offset: rendy_core::hal::image::Offset::ZERO,
-                            dst_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: rendy_core::hal::format::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            dst_offset: rendy_core::hal::image::Offset::ZERO,
-                            extent: rendy_core::hal::image::Extent {
-                                width: target_image.kind().extent().width,
-                                height: target_image.kind().extent().height,
-                                depth: 1,
-                            },
-                        }),
-                    );
-                }
-            }
-
-            {
-                let (mut stages, mut barriers) =
-                    gfx_release_barriers(ctx, None, Some(input_image));
-                stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-                stages.end |= rendy_core::hal::pso::PipelineStage::BOTTOM_OF_PIPE;
-                barriers.push(rendy_core::hal::memory::Barrier::Image {
-                    states: (
-                        rendy_core::hal::image::Access::TRANSFER_WRITE,
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
                     )
-                        ..(
-                            rendy_core::hal::image::Access::empty(),
-                            rendy_core::hal::image::Layout::Present,
-                        ),
-                    families: None,
-                    target: target_image.raw(),
-                    range: rendy_core::hal::image::SubresourceRange {
-                        aspects: rendy_core::hal::format::Aspects::COLOR,
-                        levels: 0..1,
-                        layers: 0..1,
-                    },
-                });
-
-                log::trace!("Release {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    encoder.pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-            }
-
-            let (submit, buffer) = buf_recording.finish().submit();
+                })
+                .collect();
 
             ForImage {
-                submit,
-                buffer,
+                commands,
                 acquire: factory.create_semaphore().unwrap(),
                 release: factory.create_semaphore().unwrap(),
             }
@@ -255,6 +138,154 @@ fn create_per_image_data<B: rendy_core::hal::Backend>(
         .collect()
 }
 
+/// Record commands that transfer input image bound in given phase of history images
+/// to the swapchain image.
+fn record_commands<B: rendy_core::hal::Backend>(
+    ctx: &GraphContext<B>,
+    phase: usize,
+    input_image: &NodeImage,
+    buf_initial: CommandBuffer<B, rendy_core::hal::queue::QueueType, InitialState>,
+    target_image: &Image<B>,
+    blit_filter: rendy_core::hal::image::Filter,
+) -> PresentCommands<B> {
+    let input_image_res = ctx
+        .get_image_in_phase(input_image.id, phase)
+        .expect("Image does not exist");
+
+    let mut buf_recording = buf_initial.begin(MultiShot(SimultaneousUse), ());
+    let mut encoder = buf_recording.encoder();
+    let (mut stages, mut barriers) =
+        gfx_acquire_barriers_in_phase(ctx, phase, None, Some(input_image));
+    stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+    stages.end |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+    barriers.push(rendy_core::hal::memory::Barrier::Image {
+        states: (
+            rendy_core::hal::image::Access::empty(),
+            rendy_core::hal::image::Layout::Undefined,
+        )
+            ..(
+                rendy_core::hal::image::Access::TRANSFER_WRITE,
+                rendy_core::hal::image::Layout::TransferDstOptimal,
+            ),
+        families: None,
+        target: target_image.raw(),
+        range: rendy_core::hal::image::SubresourceRange {
+            aspects: rendy_core::hal::format::Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        },
+    });
+    log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+    unsafe {
+        encoder.pipeline_barrier(
+            stages,
+            rendy_core::hal::memory::Dependencies::empty(),
+            barriers,
+        );
+    }
+
+    let extents_differ = target_image.kind().extent() != input_image_res.kind().extent();
+    let formats_differ = target_image.format() != input_image_res.format();
+
+    if extents_differ || formats_differ {
+        if formats_differ {
+            log::debug!("Present node is blitting because target format {:?} doesnt match image format {:?}", target_image.format(), input_image_res.format());
+        }
+        if extents_differ {
+            log::debug!("Present node is blitting because target extent {:?} doesnt match image extent {:?}", target_image.kind().extent(), input_image_res.kind().extent());
+        }
+        unsafe {
+            encoder.blit_image(
+                input_image_res.raw(),
+                input_image.layout,
+                target_image.raw(),
+                rendy_core::hal::image::Layout::TransferDstOptimal,
+                blit_filter,
+                Some(rendy_core::hal::command::ImageBlit {
+                    src_subresource: rendy_core::hal::image::SubresourceLayers {
+                        aspects: input_image.range.aspects,
+                        level: 0,
+                        layers: input_image.range.layers.start..input_image.range.layers.start + 1,
+                    },
+                    src_bounds: rendy_core::hal::image::Offset::ZERO
+                        .into_bounds(&input_image_res.kind().extent()),
+                    dst_subresource: rendy_core::hal::image::SubresourceLayers {
+                        aspects: rendy_core::hal::format::Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    dst_bounds: rendy_core::hal::image::Offset::ZERO
+                        .into_bounds(&target_image.kind().extent()),
+                }),
+            );
+        }
+    } else {
+        log::debug!("Present node is copying");
+        unsafe {
+            encoder.copy_image(
+                input_image_res.raw(),
+                input_image.layout,
+                target_image.raw(),
+                rendy_core::hal::image::Layout::TransferDstOptimal,
+                Some(rendy_core::hal::command::ImageCopy {
+                    src_subresource: rendy_core::hal::image::SubresourceLayers {
+                        aspects: input_image.range.aspects,
+                        level: 0,
+                        layers: input_image.range.layers.start..input_image.range.layers.start + 1,
+                    },
+                    src_offset: rendy_core::hal::image::Offset::ZERO,
+                    dst_subresource: rendy_core::hal::image::SubresourceLayers {
+                        aspects: rendy_core::hal::format::Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    dst_offset: rendy_core::hal::image::Offset::ZERO,
+                    extent: rendy_core::hal::image::Extent {
+                        width: target_image.kind().extent().width,
+                        height: target_image.kind().extent().height,
+                        depth: 1,
+                    },
+                }),
+            );
+        }
+    }
+
+    {
+        let (mut stages, mut barriers) =
+            gfx_release_barriers_in_phase(ctx, phase, None, Some(input_image));
+        stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+        stages.end |= rendy_core::hal::pso::PipelineStage::BOTTOM_OF_PIPE;
+        barriers.push(rendy_core::hal::memory::Barrier::Image {
+            states: (
+                rendy_core::hal::image::Access::TRANSFER_WRITE,
+                rendy_core::hal::image::Layout::TransferDstOptimal,
+            )
+                ..(
+                    rendy_core::hal::image::Access::empty(),
+                    rendy_core::hal::image::Layout::Present,
+                ),
+            families: None,
+            target: target_image.raw(),
+            range: rendy_core::hal::image::SubresourceRange {
+                aspects: rendy_core::hal::format::Aspects::COLOR,
+                levels: 0..1,
+                layers: 0..1,
+            },
+        });
+
+        log::trace!("Release {:?} : {:#?}", stages, barriers);
+        unsafe {
+            encoder.pipeline_barrier(
+                stages,
+                rendy_core::hal::memory::Dependencies::empty(),
+                barriers,
+            );
+        }
+    }
+
+    buf_recording.finish().submit()
+}
+
 /// Presentation node description.
 #[derive(Debug)]
 pub struct PresentBuilder<B: rendy_core::hal::Backend> {
@@ -454,6 +485,48 @@ where
     }
 }
 
+impl<B> PresentNode<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Recreate swapchain with extent of the input image
+    /// and record blit commands again.
+    ///
+    /// # Safety
+    ///
+    /// Swapchain and command buffers must not be in use.
+    unsafe fn recreate(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+    ) -> Result<(), SwapchainError> {
+        let extent = ctx
+            .get_image(self.input_image.id)
+            .expect("Context must contain node's image")
+            .kind()
+            .extent()
+            .into();
+
+        self.target
+            .recreate(factory.physical(), factory.device(), extent)?;
+
+        for data in self.per_image.drain(..) {
+            data.dispose(factory, &mut self.pool);
+        }
+
+        self.per_image = create_per_image_data(
+            ctx,
+            &self.input_image,
+            &mut self.pool,
+            factory,
+            &self.target,
+            self.blit_filter,
+        );
+
+        Ok(())
+    }
+}
+
 impl<B, T> DynNode<B, T> for PresentNode<B>
 where
     B: rendy_core::hal::Backend,
@@ -480,7 +553,7 @@ where
                     queue.submit(
                         Some(
                             Submission::new()
-                                .submits(Some(&for_image.submit))
+                                .submits(Some(&for_image.commands[ctx.history_phase()].0))
                                 .wait(waits.iter().cloned().chain(Some((
                                     &for_image.acquire,
                                     rendy_core::hal::pso::PipelineStage::TRANSFER,
@@ -516,30 +589,25 @@ where
             // TODO: use retired swapchains once available in hal and remove that wait
             factory.wait_idle().unwrap();
 
-            let extent = ctx
-                .get_image(self.input_image.id)
-                .expect("Context must contain node's image")
-                .kind()
-                .extent()
-                .into();
-
-            self.target
-                .recreate(factory.physical(), factory.device(), extent)
+            self.recreate(ctx, factory)
                 .expect("Failed recreating swapchain");
+        }
+    }
 
-            for data in self.per_image.drain(..) {
-                data.dispose(factory, &mut self.pool);
-            }
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        assert_eq!(buffers.len(), 0);
+        assert_eq!(images.len(), 1);
 
-            self.per_image = create_per_image_data(
-                ctx,
-                &self.input_image,
-                &mut self.pool,
-                factory,
-                &self.target,
-                self.blit_filter,
-            );
-        }
+        self.input_image = images.into_iter().next().unwrap();
+        self.recreate(ctx, factory)
+            .map_err(NodeBuildError::Swapchain)
     }
 
     unsafe fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &T) {
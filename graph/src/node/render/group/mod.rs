@@ -9,7 +9,7 @@ use {
         graph::GraphContext,
         node::{
             render::{pass::SubpassBuilder, PrepareResult},
-            BufferAccess, DescBuilder, ImageAccess, NodeBuffer, NodeImage,
+            BufferAccess, DescBuilder, ImageAccess, NodeBuffer, NodeBuildError, NodeImage,
         },
         BufferId, ImageId, NodeId,
     },
@@ -70,8 +70,10 @@ pub trait RenderGroupDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
 /// One or more graphics pipelines to be called in subpass.
 pub trait RenderGroup<B: Backend, T: ?Sized>: std::fmt::Debug + Send + Sync {
     /// Prepare resources and data for rendering.
+    /// Descriptors referencing history images must match `GraphContext::history_phase`.
     fn prepare(
         &mut self,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: QueueId,
         index: usize,
@@ -88,6 +90,20 @@ pub trait RenderGroup<B: Backend, T: ?Sized>: std::fmt::Debug + Send + Sync {
         aux: &T,
     );
 
+    /// Update group after images were reallocated by `Graph::resize`.
+    /// Called with new framebuffer extent and resources used by the group.
+    /// See [`DynNode::resize`](../trait.DynNode.html#tymethod.resize).
+    fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError>;
+
     /// Free all resources and destroy group instance.
     fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T);
 }
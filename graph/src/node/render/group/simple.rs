@@ -5,11 +5,12 @@ use {
         factory::Factory,
         graph::GraphContext,
         node::{
-            render::PrepareResult, BufferAccess, DescBuilder, ImageAccess, NodeBuffer, NodeImage,
+            render::PrepareResult, BufferAccess, DescBuilder, ImageAccess, NodeBuffer,
+            NodeBuildError, NodeImage,
         },
-        resource::{DescriptorSetLayout, Handle},
+        resource::{DescriptorSetLayout, Escape, GraphicsPipeline, Handle, PipelineLayout},
     },
-    rendy_core::hal::{device::Device as _, Backend},
+    rendy_core::hal::Backend,
 };
 
 pub use crate::core::types::{Layout, SetLayout};
@@ -38,6 +39,9 @@ pub struct Pipeline {
 
     /// Primitive to use in the input assembler.
     pub input_assembler_desc: rendy_core::hal::pso::InputAssemblerDesc,
+
+    /// Multisampling for pipeline.
+    pub multisampling: Option<rendy_core::hal::pso::Multisampling>,
 }
 
 /// Descriptor for simple graphics pipeline implementation.
@@ -117,6 +121,12 @@ pub trait SimpleGraphicsPipelineDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
         }
     }
 
+    /// Multisampling desc.
+    /// Must match sample count of the subpass attachments. No multisampling by default.
+    fn multisampling(&self) -> Option<rendy_core::hal::pso::Multisampling> {
+        None
+    }
+
     /// Graphics pipelines
     fn pipeline(&self) -> Pipeline {
         Pipeline {
@@ -128,6 +138,7 @@ pub trait SimpleGraphicsPipelineDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
                 .unwrap_or(rendy_core::hal::pso::DepthStencilDesc::default()),
             rasterizer: self.rasterizer(),
             input_assembler_desc: self.input_assembler(),
+            multisampling: self.multisampling(),
         }
     }
 
@@ -173,8 +184,10 @@ pub trait SimpleGraphicsPipeline<B: Backend, T: ?Sized>:
     /// Prepare to record drawing commands.
     ///
     /// Should return true if commands must be re-recorded.
+    /// Descriptors referencing history images must match `GraphContext::history_phase`.
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         _factory: &Factory<B>,
         _queue: QueueId,
         _set_layouts: &[Handle<DescriptorSetLayout<B>>],
@@ -193,6 +206,18 @@ pub trait SimpleGraphicsPipeline<B: Backend, T: ?Sized>:
         aux: &T,
     );
 
+    /// Update pipeline after images it uses were reallocated by `Graph::resize`.
+    /// See [`DynNode::resize`](../trait.DynNode.html#tymethod.resize).
+    fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+        set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError>;
+
     /// Free all resources and destroy pipeline instance.
     fn dispose(self, factory: &mut Factory<B>, aux: &T);
 }
@@ -201,9 +226,10 @@ pub trait SimpleGraphicsPipeline<B: Backend, T: ?Sized>:
 #[derive(Debug)]
 pub struct SimpleRenderGroup<B: Backend, P> {
     set_layouts: Vec<Handle<DescriptorSetLayout<B>>>,
-    pipeline_layout: B::PipelineLayout,
-    graphics_pipeline: B::GraphicsPipeline,
+    pipeline_layout: Handle<PipelineLayout<B>>,
+    graphics_pipeline: Escape<GraphicsPipeline<B>>,
     pipeline: P,
+    rect: rendy_core::hal::pso::Rect,
 }
 
 /// Descriptor for simple render group.
@@ -256,27 +282,19 @@ where
             .layout
             .sets
             .into_iter()
-            .map(|set| {
-                factory
-                    .create_descriptor_set_layout(set.bindings)
-                    .map(Handle::from)
-            })
+            .map(|set| factory.get_descriptor_set_layout(set.bindings))
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| {
                 shader_set.dispose(factory);
                 e
             })?;
 
-        let pipeline_layout = unsafe {
-            factory.device().create_pipeline_layout(
-                set_layouts.iter().map(|l| l.raw()),
-                pipeline.layout.push_constants,
-            )
-        }
-        .map_err(|e| {
-            shader_set.dispose(factory);
-            rendy_core::hal::pso::CreationError::OutOfMemory(e)
-        })?;
+        let pipeline_layout = factory
+            .get_pipeline_layout(set_layouts.clone(), pipeline.layout.push_constants)
+            .map_err(|e| {
+                shader_set.dispose(factory);
+                rendy_core::hal::pso::CreationError::OutOfMemory(e)
+            })?;
 
         assert_eq!(pipeline.colors.len(), self.inner.colors().len());
 
@@ -304,8 +322,8 @@ where
         };
 
         let graphics_pipeline = unsafe {
-            factory.device().create_graphics_pipelines(
-                Some(rendy_core::hal::pso::GraphicsPipelineDesc {
+            factory.create_graphics_pipeline(
+                &rendy_core::hal::pso::GraphicsPipelineDesc {
                     shaders,
                     rasterizer: pipeline.rasterizer,
                     vertex_buffers,
@@ -316,25 +334,22 @@ where
                         targets: pipeline.colors.clone(),
                     },
                     depth_stencil: pipeline.depth_stencil,
-                    multisampling: None,
+                    multisampling: pipeline.multisampling,
+                    // Viewport and scissor are dynamic so that pipeline survives resizing.
                     baked_states: rendy_core::hal::pso::BakedStates {
-                        viewport: Some(rendy_core::hal::pso::Viewport {
-                            rect,
-                            depth: 0.0..1.0,
-                        }),
-                        scissor: Some(rect),
+                        viewport: None,
+                        scissor: None,
                         blend_color: None,
                         depth_bounds: None,
                     },
-                    layout: &pipeline_layout,
+                    layout: pipeline_layout.raw(),
                     subpass,
                     flags: rendy_core::hal::pso::PipelineCreationFlags::empty(),
                     parent: rendy_core::hal::pso::BasePipeline::None,
-                }),
-                None,
+                },
+                pipeline_layout.clone(),
             )
         }
-        .remove(0)
         .map_err(|e| {
             shader_set.dispose(factory);
             e
@@ -355,6 +370,7 @@ where
             pipeline_layout,
             graphics_pipeline,
             pipeline,
+            rect,
         }))
     }
 }
@@ -367,6 +383,7 @@ where
 {
     fn prepare(
         &mut self,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: QueueId,
         index: usize,
@@ -374,7 +391,7 @@ where
         aux: &T,
     ) -> PrepareResult {
         self.pipeline
-            .prepare(factory, queue, &self.set_layouts, index, aux)
+            .prepare(ctx, factory, queue, &self.set_layouts, index, aux)
     }
 
     fn draw_inline(
@@ -384,23 +401,46 @@ where
         _subpass: rendy_core::hal::pass::Subpass<'_, B>,
         aux: &T,
     ) {
-        encoder.bind_graphics_pipeline(&self.graphics_pipeline);
+        encoder.bind_graphics_pipeline(self.graphics_pipeline.raw());
+        unsafe {
+            encoder.set_viewports(
+                0,
+                &[rendy_core::hal::pso::Viewport {
+                    rect: self.rect,
+                    depth: 0.0..1.0,
+                }],
+            );
+            encoder.set_scissors(0, &[self.rect]);
+        }
+        self.pipeline
+            .draw(self.pipeline_layout.raw(), encoder, index, aux);
+    }
+
+    fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        self.rect = rendy_core::hal::pso::Rect {
+            x: 0,
+            y: 0,
+            w: framebuffer_width as i16,
+            h: framebuffer_height as i16,
+        };
         self.pipeline
-            .draw(&self.pipeline_layout, encoder, index, aux);
+            .resize(ctx, factory, aux, buffers, images, &self.set_layouts)
     }
 
     fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         self.pipeline.dispose(factory, aux);
-
-        unsafe {
-            factory
-                .device()
-                .destroy_graphics_pipeline(self.graphics_pipeline);
-            factory
-                .device()
-                .destroy_pipeline_layout(self.pipeline_layout);
-            drop(self.set_layouts);
-        }
+        drop(self.graphics_pipeline);
+        drop(self.pipeline_layout);
+        drop(self.set_layouts);
     }
 }
 
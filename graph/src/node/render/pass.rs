@@ -1,9 +1,8 @@
 use {
     crate::{
         command::{
-            CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence,
-            Graphics, IndividualReset, MultiShot, NoSimultaneousUse, PendingState, Queue, QueueId,
-            SecondaryLevel, SimultaneousUse, Submission, Submit,
+            CommandPool, Families, Family, FamilyId, Fence, Graphics, IndividualReset, MultiShot,
+            NoSimultaneousUse, Queue, QueueId, SecondaryLevel, Submission,
         },
         core::{
             hal::{device::Device as _, image::Layout, Backend},
@@ -16,9 +15,11 @@ use {
         },
         graph::GraphContext,
         node::{
-            gfx_acquire_barriers, gfx_release_barriers,
+            free_barrier_commands, gfx_acquire_barriers_in_phase, gfx_release_barriers_in_phase,
+            record_barrier_commands,
             render::group::{RenderGroup, RenderGroupBuilder},
-            BufferAccess, DynNode, ImageAccess, NodeBuffer, NodeBuildError, NodeBuilder, NodeImage,
+            BarrierCommands, BufferAccess, DynNode, ImageAccess, NodeBuffer, NodeBuildError,
+            NodeBuilder, NodeImage,
         },
         wsi::{Surface, Target},
         BufferId, ImageId, NodeId,
@@ -37,6 +38,7 @@ pub struct SubpassBuilder<B: Backend, T: ?Sized> {
     groups: Vec<Box<dyn RenderGroupBuilder<B, T>>>,
     inputs: Vec<Attachment>,
     colors: Vec<Attachment>,
+    resolves: Vec<Attachment>,
     depth_stencil: Option<Attachment>,
     dependencies: Vec<NodeId>,
 }
@@ -51,6 +53,7 @@ where
             .field("groups", &self.groups)
             .field("inputs", &self.inputs)
             .field("colors", &self.colors)
+            .field("resolves", &self.resolves)
             .field("depth_stencil", &self.depth_stencil)
             .field("dependencies", &self.dependencies)
             .finish()
@@ -67,6 +70,7 @@ where
             groups: Vec::default(),
             inputs: Vec::default(),
             colors: Vec::default(),
+            resolves: Vec::default(),
             depth_stencil: None,
             dependencies: Vec::default(),
         }
@@ -149,6 +153,36 @@ where
         self
     }
 
+    /// Add resolve attachment to the subpass.
+    /// Multisampled color attachment with the same index is resolved into it at the end of the subpass.
+    /// Either none or all color attachments of the subpass must be resolved.
+    pub fn add_resolve(&mut self, resolve: ImageId) -> &mut Self {
+        self.resolves.push(Either::Left(resolve));
+        self
+    }
+
+    /// Add resolve attachment to the subpass.
+    /// Multisampled color attachment with the same index is resolved into it at the end of the subpass.
+    /// Either none or all color attachments of the subpass must be resolved.
+    pub fn with_resolve(mut self, resolve: ImageId) -> Self {
+        self.add_resolve(resolve);
+        self
+    }
+
+    /// Add surface as resolve attachment to the subpass.
+    /// Multisampled color attachment with the same index is resolved into surface image.
+    pub fn add_resolve_surface(&mut self) -> &mut Self {
+        self.resolves.push(Either::Right(RenderPassSurface));
+        self
+    }
+
+    /// Add surface as resolve attachment to the subpass.
+    /// Multisampled color attachment with the same index is resolved into surface image.
+    pub fn with_resolve_surface(mut self) -> Self {
+        self.add_resolve_surface();
+        self
+    }
+
     /// Set depth-stencil attachment to the subpass.
     pub fn set_depth_stencil(&mut self, depth_stencil: ImageId) -> &mut Self {
         self.depth_stencil = Some(Either::Left(depth_stencil));
@@ -201,6 +235,8 @@ pub struct RenderPassNodeBuilder<B: Backend, T: ?Sized> {
         rendy_core::hal::window::Extent2D,
         Option<rendy_core::hal::command::ClearValue>,
     )>,
+    ops: HashMap<Attachment, rendy_core::hal::pass::AttachmentOps>,
+    stencil_ops: HashMap<Attachment, rendy_core::hal::pass::AttachmentOps>,
 }
 
 impl<B, T> std::fmt::Debug for RenderPassNodeBuilder<B, T>
@@ -212,6 +248,8 @@ where
         fmt.debug_struct("RenderPassNodeBuilder")
             .field("subpasses", &self.subpasses)
             .field("surface", &self.surface)
+            .field("ops", &self.ops)
+            .field("stencil_ops", &self.stencil_ops)
             .finish()
     }
 }
@@ -225,6 +263,8 @@ where
         RenderPassNodeBuilder {
             subpasses: Vec::default(),
             surface: None,
+            ops: HashMap::default(),
+            stencil_ops: HashMap::default(),
         }
     }
 }
@@ -276,6 +316,68 @@ where
         self.add_surface(surface, suggested_extent, clear);
         self
     }
+
+    /// Set load and store operations for the attachment image.
+    /// By default operations are derived from the graph:
+    /// attachment is cleared if it has clear value, loaded if previous nodes wrote it,
+    /// and stored if subsequent nodes read it.
+    pub fn add_attachment_ops(
+        &mut self,
+        image: ImageId,
+        ops: rendy_core::hal::pass::AttachmentOps,
+    ) -> &mut Self {
+        self.ops.insert(Either::Left(image), ops);
+        self
+    }
+
+    /// Set load and store operations for the attachment image.
+    /// By default operations are derived from the graph:
+    /// attachment is cleared if it has clear value, loaded if previous nodes wrote it,
+    /// and stored if subsequent nodes read it.
+    pub fn with_attachment_ops(
+        mut self,
+        image: ImageId,
+        ops: rendy_core::hal::pass::AttachmentOps,
+    ) -> Self {
+        self.add_attachment_ops(image, ops);
+        self
+    }
+
+    /// Set stencil load and store operations for the attachment image.
+    /// By default they are derived the same way as for depth.
+    pub fn add_attachment_stencil_ops(
+        &mut self,
+        image: ImageId,
+        ops: rendy_core::hal::pass::AttachmentOps,
+    ) -> &mut Self {
+        self.stencil_ops.insert(Either::Left(image), ops);
+        self
+    }
+
+    /// Set stencil load and store operations for the attachment image.
+    /// By default they are derived the same way as for depth.
+    pub fn with_attachment_stencil_ops(
+        mut self,
+        image: ImageId,
+        ops: rendy_core::hal::pass::AttachmentOps,
+    ) -> Self {
+        self.add_attachment_stencil_ops(image, ops);
+        self
+    }
+
+    /// Set load and store operations for the surface attachment.
+    /// By default surface is cleared if it has clear value and always stored for presentation.
+    pub fn add_surface_ops(&mut self, ops: rendy_core::hal::pass::AttachmentOps) -> &mut Self {
+        self.ops.insert(Either::Right(RenderPassSurface), ops);
+        self
+    }
+
+    /// Set load and store operations for the surface attachment.
+    /// By default surface is cleared if it has clear value and always stored for presentation.
+    pub fn with_surface_ops(mut self, ops: rendy_core::hal::pass::AttachmentOps) -> Self {
+        self.add_surface_ops(ops);
+        self
+    }
 }
 
 impl<B, T> NodeBuilder<B, T> for RenderPassNodeBuilder<B, T>
@@ -341,6 +443,16 @@ where
                 entry.stages |= rendy_core::hal::pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
             }
 
+            for &id in subpass.resolves.iter().filter_map(|e| e.as_ref().left()) {
+                let entry = attachments.entry(id).or_insert(ImageAccess {
+                    layout: Layout::ColorAttachmentOptimal,
+                    ..empty
+                });
+                entry.access |= rendy_core::hal::image::Access::COLOR_ATTACHMENT_WRITE;
+                entry.usage |= rendy_core::hal::image::Usage::COLOR_ATTACHMENT;
+                entry.stages |= rendy_core::hal::pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+            }
+
             if let Some(id) = subpass.depth_stencil.and_then(Either::left) {
                 let entry = attachments.entry(id).or_insert(ImageAccess {
                     layout: Layout::DepthStencilAttachmentOptimal,
@@ -421,6 +533,9 @@ where
                     .chain(subpass.colors.iter().inspect(|a| {
                         surface_color_usage = surface_color_usage || a.is_right();
                     }))
+                    .chain(subpass.resolves.iter().inspect(|a| {
+                        surface_color_usage = surface_color_usage || a.is_right();
+                    }))
                     .chain(subpass.depth_stencil.as_ref().into_iter().inspect(|a| {
                         surface_depth_usage = surface_depth_usage || a.is_right();
                     }))
@@ -446,6 +561,29 @@ where
         attachments.sort();
         attachments.dedup();
 
+        for subpass in &self.subpasses {
+            assert!(
+                subpass.resolves.is_empty() || subpass.resolves.len() == subpass.colors.len(),
+                "Either none or all color attachments of the subpass must be resolved"
+            );
+        }
+
+        // Content of attachments that are only written by resolve operations
+        // need not be loaded.
+        let subpass_builders = &self.subpasses;
+        let ops_overrides = &self.ops;
+        let stencil_ops_overrides = &self.stencil_ops;
+        let resolve_only = |attachment: Attachment| -> bool {
+            subpass_builders
+                .iter()
+                .any(|subpass| subpass.resolves.contains(&attachment))
+                && subpass_builders.iter().all(|subpass| {
+                    !subpass.inputs.contains(&attachment)
+                        && !subpass.colors.contains(&attachment)
+                        && subpass.depth_stencil != Some(attachment)
+                })
+        };
+
         let find_attachment_node_image = |id: ImageId| -> &NodeImage {
             images
                 .iter()
@@ -461,9 +599,9 @@ where
 
         log::trace!("Configure attachments");
 
-        let views: Vec<_> = attachments
+        attachments
             .iter()
-            .map(|&attachment| -> Result<Vec<_>, NodeBuildError> {
+            .map(|&attachment| -> Result<(), NodeBuildError> {
                 match attachment {
                     Either::Left(image_id) => {
                         log::debug!("Image {:?} attachment", image_id);
@@ -477,24 +615,7 @@ where
                             framebuffer_layers,
                             node_image.range.layers.end - node_image.range.layers.start,
                         );
-                        Ok(vec![unsafe {
-                            factory
-                                .device()
-                                .create_image_view(
-                                    image.raw(),
-                                    rendy_core::hal::image::ViewKind::D2,
-                                    image.format(),
-                                    rendy_core::hal::format::Swizzle::NO,
-                                    rendy_core::hal::image::SubresourceRange {
-                                        // NOTE: Framebuffer must always be created with only one mip level. If image contains multiple levels,
-                                        // only the first one is bound as an attachment.
-                                        // TODO: Allow customizing this behaviour to choose which level to bind.
-                                        levels: 0 .. 1,
-                                        ..node_image.range.clone()
-                                    }
-                                )
-                                .map_err(NodeBuildError::View)?
-                            }])
+                        Ok(())
                     },
                     Either::Right(RenderPassSurface) => {
                         log::trace!("Surface attachment");
@@ -541,29 +662,24 @@ where
                             target.backbuffer()[0].layers(),
                         );
 
-                        let views = target.backbuffer().iter().map(|image| unsafe {
-                            factory
-                                .device()
-                                .create_image_view(
-                                    image.raw(),
-                                    rendy_core::hal::image::ViewKind::D2,
-                                    image.format(),
-                                    rendy_core::hal::format::Swizzle::NO,
-                                    rendy_core::hal::image::SubresourceRange {
-                                        aspects: image.format().surface_desc().aspects,
-                                        levels: 0 .. 1,
-                                        layers: 0 .. 1,
-                                    },
-                                )
-                                .map_err(NodeBuildError::View)
-                        }).collect::<Result<Vec<_>, NodeBuildError>>()?;
-
                         node_target = Some(target);
-                        Ok(views)
+                        Ok(())
                     }
                 }
-            }).collect::<Result<Vec<_>, _>>()?
-            .into_iter().flatten().collect();
+            }).collect::<Result<Vec<_>, _>>()?;
+
+        let views = (0..ctx.history_period())
+            .map(|phase| unsafe {
+                create_views(
+                    ctx,
+                    factory,
+                    phase,
+                    &attachments,
+                    &images,
+                    node_target.as_ref(),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         log::trace!("Configure render pass instance");
 
@@ -571,13 +687,15 @@ where
             let pass_attachments: Vec<_> = attachments
                 .iter()
                 .map(|&attachment| {
-                    let (format, clear, layout, samples) = match attachment {
+                    let (format, clear, load, store, layout, samples) = match attachment {
                         Either::Left(image_id) => {
                             let node_image = find_attachment_node_image(image_id);
                             let image = ctx.get_image(image_id).expect("Image does not exist");
                             (
                                 image.format(),
                                 node_image.clear,
+                                node_image.load,
+                                node_image.store,
                                 node_image.layout,
                                 image.kind().num_samples(),
                             )
@@ -589,27 +707,60 @@ where
                                 .backbuffer()[0]
                                 .format(),
                             surface_clear,
+                            false,
+                            true,
                             rendy_core::hal::image::Layout::Present,
                             1,
                         ),
                     };
 
-                    rendy_core::hal::pass::Attachment {
-                        format: Some(format),
-                        ops: rendy_core::hal::pass::AttachmentOps {
-                            load: if clear.is_some() {
-                                rendy_core::hal::pass::AttachmentLoadOp::Clear
-                            } else {
-                                rendy_core::hal::pass::AttachmentLoadOp::Load
-                            },
-                            store: rendy_core::hal::pass::AttachmentStoreOp::Store,
+                    let derived = rendy_core::hal::pass::AttachmentOps {
+                        load: if resolve_only(attachment) {
+                            rendy_core::hal::pass::AttachmentLoadOp::DontCare
+                        } else if clear.is_some() {
+                            rendy_core::hal::pass::AttachmentLoadOp::Clear
+                        } else if load {
+                            rendy_core::hal::pass::AttachmentLoadOp::Load
+                        } else {
+                            rendy_core::hal::pass::AttachmentLoadOp::DontCare
                         },
-                        stencil_ops: rendy_core::hal::pass::AttachmentOps::DONT_CARE,
-                        layouts: if clear.is_some() {
-                            rendy_core::hal::image::Layout::Undefined..layout
+                        store: if store {
+                            rendy_core::hal::pass::AttachmentStoreOp::Store
                         } else {
-                            layout..layout
+                            rendy_core::hal::pass::AttachmentStoreOp::DontCare
                         },
+                    };
+
+                    let ops = ops_overrides.get(&attachment).cloned().unwrap_or(derived);
+                    let stencil_ops = stencil_ops_overrides
+                        .get(&attachment)
+                        .cloned()
+                        .unwrap_or_else(|| {
+                            if format
+                                .surface_desc()
+                                .aspects
+                                .contains(rendy_core::hal::format::Aspects::STENCIL)
+                            {
+                                derived
+                            } else {
+                                rendy_core::hal::pass::AttachmentOps::DONT_CARE
+                            }
+                        });
+
+                    // Previous content is undefined unless it is loaded.
+                    let layouts = if ops.load == rendy_core::hal::pass::AttachmentLoadOp::Load
+                        || stencil_ops.load == rendy_core::hal::pass::AttachmentLoadOp::Load
+                    {
+                        layout..layout
+                    } else {
+                        rendy_core::hal::image::Layout::Undefined..layout
+                    };
+
+                    rendy_core::hal::pass::Attachment {
+                        format: Some(format),
+                        ops,
+                        stencil_ops,
+                        layouts,
                         samples,
                     }
                 })
@@ -621,6 +772,7 @@ where
             struct OwningSubpassDesc {
                 inputs: Vec<(usize, Layout)>,
                 colors: Vec<(usize, Layout)>,
+                resolves: Vec<(usize, Layout)>,
                 depth_stencil: Option<(usize, Layout)>,
             }
 
@@ -662,6 +814,23 @@ where
                             )
                         })
                         .collect(),
+                    resolves: subpass
+                        .resolves
+                        .iter()
+                        .map(|&r| {
+                            (
+                                attachments.iter().position(|&a| a == r).unwrap(),
+                                match r {
+                                    Either::Left(image_id) => {
+                                        find_attachment_node_image(image_id).layout
+                                    }
+                                    Either::Right(RenderPassSurface) => {
+                                        rendy_core::hal::image::Layout::ColorAttachmentOptimal
+                                    }
+                                },
+                            )
+                        })
+                        .collect(),
                     depth_stencil: subpass.depth_stencil.map(|ds| {
                         (
                             attachments.iter().position(|&a| a == ds).unwrap(),
@@ -686,7 +855,7 @@ where
                     inputs: &subpass.inputs[..],
                     colors: &subpass.colors[..],
                     depth_stencil: subpass.depth_stencil.as_ref(),
-                    resolves: &[],
+                    resolves: &subpass.resolves[..],
                     preserves: &[],
                 })
                 .collect();
@@ -710,43 +879,40 @@ where
         };
 
         log::trace!(
-            "Create {} framebuffers",
-            views.len() - attachments.len() + 1
+            "Create {} framebuffers for each of {} phases",
+            views[0].len() - attachments.len() + 1,
+            views.len()
         );
 
-        // Swapchain image views, if any, are last ones.
-        let mut framebuffers = (attachments.len() - 1..views.len())
-            .map(|i| unsafe {
-                log::trace!(
-                    "Create framebuffer for views {}..{} and {}",
-                    0,
-                    attachments.len() - 1,
-                    i,
-                );
-                factory
-                    .device()
-                    .create_framebuffer(
-                        &render_pass,
-                        views[..attachments.len() - 1].iter().chain(Some(&views[i])),
-                        rendy_core::hal::image::Extent {
-                            width: framebuffer_width,
-                            height: framebuffer_height,
-                            depth: framebuffer_layers as u32, // This is gfx-hal BUG as this parameter actually means framebuffer layers number,
-                        },
-                    )
-                    .map_err(NodeBuildError::OutOfMemory)
+        let framebuffers = views
+            .iter()
+            .map(|views| unsafe {
+                create_framebuffers(
+                    factory,
+                    &render_pass,
+                    views,
+                    attachments.len(),
+                    framebuffer_width,
+                    framebuffer_height,
+                    framebuffer_layers,
+                )
             })
             .collect::<Result<Vec<_>, _>>()?;
 
         log::trace!("Collect clears for render pass");
 
+        // Clear values are indexed by attachment.
         let clears: Vec<_> = attachments
             .iter()
-            .filter_map(|&a| match a {
-                Either::Left(image_id) => find_attachment_node_image(image_id).clear,
-                Either::Right(RenderPassSurface) => surface_clear,
+            .map(|&a| {
+                match a {
+                    Either::Left(image_id) => find_attachment_node_image(image_id).clear,
+                    Either::Right(RenderPassSurface) => surface_clear,
+                }
+                .unwrap_or(rendy_core::hal::command::ClearValue {
+                    color: rendy_core::hal::command::ClearColor { float32: [0.0; 4] },
+                })
             })
-            .map(Into::into)
             .collect();
 
         let mut command_pool = factory
@@ -757,57 +923,8 @@ where
 
         let command_cirque = CommandCirque::new();
 
-        let acquire = if uses_pipeline_barriers::<B>(factory.device()) {
-            let (stages, barriers) = gfx_acquire_barriers(ctx, &buffers, &images);
-
-            if !barriers.is_empty() {
-                let initial = command_pool.allocate_buffers(1).pop().unwrap();
-                let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
-                log::debug!("Acquire {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    recording.encoder().pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-                let (acquire_submit, acquire_buffer) = recording.finish().submit();
-                Some(BarriersCommands {
-                    buffer: acquire_buffer,
-                    submit: acquire_submit,
-                })
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        let release = if uses_pipeline_barriers::<B>(factory.device()) {
-            let (stages, barriers) = gfx_release_barriers(ctx, &buffers, &images);
-
-            if !barriers.is_empty() {
-                let initial = command_pool.allocate_buffers(1).pop().unwrap();
-                let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
-                log::debug!("Release {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    recording.encoder().pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-                let (release_submit, release_buffer) = recording.finish().submit();
-                Some(BarriersCommands {
-                    buffer: release_buffer,
-                    submit: release_submit,
-                })
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let (acquire, release) =
+            record_barriers(ctx, factory, &mut command_pool, &buffers, &images);
 
         let subpasses = self
             .subpasses
@@ -824,49 +941,36 @@ where
                         assert_eq!(group.colors(), subpass_colors);
                         assert_eq!(group.depth(), subpass_depth);
 
-                        let buffers: Vec<_> = group
-                            .buffers()
-                            .into_iter()
-                            .map(|(id, _)| {
-                                buffers
-                                    .iter()
-                                    .find(|b| b.id == id)
-                                    .expect("Transient buffer wasn't provided")
-                                    .clone()
-                            })
-                            .collect();
-                        let images: Vec<_> = group
-                            .images()
-                            .into_iter()
-                            .map(|(id, _)| {
-                                images
-                                    .iter()
-                                    .find(|i| i.id == id)
-                                    .expect("Transient image wasn't provided")
-                                    .clone()
-                            })
-                            .collect();
-
-                        group.build(
-                            ctx,
-                            factory,
-                            QueueId {
-                                family: family.id(),
-                                index: queue,
-                            },
-                            aux,
-                            framebuffer_width,
-                            framebuffer_height,
-                            rendy_core::hal::pass::Subpass {
-                                index,
-                                main_pass: &render_pass,
-                            },
-                            buffers,
-                            images,
-                        )
+                        let buffer_ids: Vec<_> =
+                            group.buffers().into_iter().map(|(id, _)| id).collect();
+                        let image_ids: Vec<_> =
+                            group.images().into_iter().map(|(id, _)| id).collect();
+
+                        group
+                            .build(
+                                ctx,
+                                factory,
+                                QueueId {
+                                    family: family.id(),
+                                    index: queue,
+                                },
+                                aux,
+                                framebuffer_width,
+                                framebuffer_height,
+                                rendy_core::hal::pass::Subpass {
+                                    index,
+                                    main_pass: &render_pass,
+                                },
+                                group_buffers(&buffers, &buffer_ids),
+                                group_images(&images, &image_ids),
+                            )
+                            .map(|group| (group, (buffer_ids, image_ids)))
                     })
                     .collect::<Result<Vec<_>, _>>()
-                    .map(|groups| SubpassNode { groups })
+                    .map(|groups| {
+                        let (groups, resources) = groups.into_iter().unzip();
+                        SubpassNode { groups, resources }
+                    })
             })
             .collect::<Result<Vec<_>, _>>()
             .map_err(NodeBuildError::Pipeline)?;
@@ -883,6 +987,7 @@ where
                         _framebuffer_layers: framebuffer_layers,
 
                         render_pass,
+                        attachments: attachments.clone(),
                         views,
                         clears,
 
@@ -895,15 +1000,15 @@ where
                         relevant: relevant::Relevant,
                     },
 
-                    per_image: framebuffers
-                        .into_iter()
-                        .map(|fb| PerImage {
-                            framebuffer: fb,
+                    per_image: framebuffers[0]
+                        .iter()
+                        .map(|_| PerImage {
                             acquire: factory.create_semaphore().unwrap(),
                             release: factory.create_semaphore().unwrap(),
                             index: 0,
                         })
                         .collect(),
+                    framebuffers,
                     free_acquire: factory.create_semaphore().unwrap(),
                     target,
                 })
@@ -919,6 +1024,7 @@ where
                         _framebuffer_layers: framebuffer_layers,
 
                         render_pass,
+                        attachments: attachments.clone(),
                         views,
                         clears,
 
@@ -930,10 +1036,13 @@ where
 
                         relevant: relevant::Relevant,
                     },
-                    framebuffer: {
-                        assert_eq!(framebuffers.len(), 1);
-                        framebuffers.remove(0)
-                    },
+                    framebuffers: framebuffers
+                        .into_iter()
+                        .map(|mut framebuffers| {
+                            assert_eq!(framebuffers.len(), 1);
+                            framebuffers.remove(0)
+                        })
+                        .collect(),
                 })
             }
         };
@@ -946,6 +1055,9 @@ where
 struct SubpassNode<B: Backend, T: ?Sized> {
     /// RenderGroups of pipelines to exeucte withing subpass.
     groups: Vec<Box<dyn RenderGroup<B, T>>>,
+
+    /// Buffers and images used by each group.
+    resources: Vec<(Vec<BufferId>, Vec<ImageId>)>,
 }
 
 impl<B, T> std::fmt::Debug for SubpassNode<B, T>
@@ -956,32 +1068,12 @@ where
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fmt.debug_struct("SubpassNode")
             .field("groups", &self.groups)
+            .field("resources", &self.resources)
             .finish()
     }
 }
 
-struct BarriersCommands<B: Backend> {
-    submit: Submit<B, SimultaneousUse, SecondaryLevel>,
-    buffer: CommandBuffer<
-        B,
-        Graphics,
-        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
-        SecondaryLevel,
-        IndividualReset,
-    >,
-}
-
-impl<B> std::fmt::Debug for BarriersCommands<B>
-where
-    B: Backend,
-{
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fmt.debug_struct("BarriersCommands")
-            .field("submit", &self.submit)
-            .field("buffer", &self.buffer)
-            .finish()
-    }
-}
+type BarriersCommands<B> = BarrierCommands<B, Graphics, SecondaryLevel>;
 
 struct RenderPassNodeCommon<B: Backend, T: ?Sized> {
     subpasses: Vec<SubpassNode<B, T>>,
@@ -991,14 +1083,17 @@ struct RenderPassNodeCommon<B: Backend, T: ?Sized> {
     _framebuffer_layers: u16,
 
     render_pass: B::RenderPass,
-    views: Vec<B::ImageView>,
+    attachments: Vec<Attachment>,
+    /// Views for each phase of history images.
+    views: Vec<Vec<B::ImageView>>,
     clears: Vec<rendy_core::hal::command::ClearValue>,
 
     command_pool: CommandPool<B, Graphics, IndividualReset>,
     command_cirque: CommandCirque<B, Graphics>,
 
-    acquire: Option<BarriersCommands<B>>,
-    release: Option<BarriersCommands<B>>,
+    /// Barriers for each phase of history images.
+    acquire: Vec<Option<BarriersCommands<B>>>,
+    release: Vec<Option<BarriersCommands<B>>>,
 
     relevant: relevant::Relevant,
 }
@@ -1015,6 +1110,7 @@ where
             .field("framebuffer_height", &self.framebuffer_height)
             .field("_framebuffer_layers", &self._framebuffer_layers)
             .field("render_pass", &self.render_pass)
+            .field("attachments", &self.attachments)
             .field("views", &self.views)
             .field("clears", &self.clears)
             .field("command_pool", &self.command_pool)
@@ -1049,28 +1145,130 @@ where
                 },
             );
         });
-        if let Some(BarriersCommands { submit, buffer }) = self.acquire.take() {
-            drop(submit);
-            let executable = buffer.mark_complete();
-            pool.free_buffers(Some(executable));
-        }
-        if let Some(BarriersCommands { submit, buffer }) = self.release.take() {
-            drop(submit);
-            let executable = buffer.mark_complete();
-            pool.free_buffers(Some(executable));
-        }
+        free_barrier_commands(pool, self.acquire.drain(..));
+        free_barrier_commands(pool, self.release.drain(..));
         factory.destroy_command_pool(self.command_pool.with_queue_type());
 
-        for view in self.views {
+        for view in self.views.into_iter().flatten() {
             factory.device().destroy_image_view(view);
         }
         factory.device().destroy_render_pass(self.render_pass);
     }
 }
 
+impl<B, T> RenderPassNodeCommon<B, T>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    /// Recreate views of the attachments and barriers for reallocated images
+    /// and let render groups adapt to new framebuffer extent.
+    /// Returns framebuffers for new views for each phase of history images.
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        target: Option<&Target<B>>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Vec<Vec<B::Framebuffer>>, NodeBuildError> {
+        // Recorded commands reference old framebuffers.
+        let pool = &mut self.command_pool;
+        std::mem::replace(&mut self.command_cirque, CommandCirque::new()).dispose(|buffer| {
+            buffer.either_with(
+                &mut *pool,
+                |pool, executable| pool.free_buffers(Some(executable)),
+                |pool, pending| {
+                    let executable = pending.mark_complete();
+                    pool.free_buffers(Some(executable))
+                },
+            );
+        });
+
+        free_barrier_commands(pool, self.acquire.drain(..));
+        free_barrier_commands(pool, self.release.drain(..));
+        let (acquire, release) = record_barriers(ctx, factory, pool, &buffers, &images);
+        self.acquire = acquire;
+        self.release = release;
+
+        for view in self.views.drain(..).flatten() {
+            factory.device().destroy_image_view(view);
+        }
+
+        let mut framebuffer_width = u32::max_value();
+        let mut framebuffer_height = u32::max_value();
+        let mut framebuffer_layers = u16::max_value();
+
+        for &attachment in &self.attachments {
+            match attachment {
+                Either::Left(image_id) => {
+                    let node_image = images
+                        .iter()
+                        .find(|a| a.id == image_id)
+                        .expect("Attachment image wasn't provided");
+                    let image = ctx.get_image(image_id).expect("Image does not exist");
+                    let extent = image.kind().extent();
+                    framebuffer_width = min(framebuffer_width, extent.width);
+                    framebuffer_height = min(framebuffer_height, extent.height);
+                    framebuffer_layers = min(
+                        framebuffer_layers,
+                        node_image.range.layers.end - node_image.range.layers.start,
+                    );
+                }
+                Either::Right(RenderPassSurface) => {
+                    let target = target.expect("Render pass with surface attachment has target");
+                    framebuffer_width = min(framebuffer_width, target.extent().width);
+                    framebuffer_height = min(framebuffer_height, target.extent().height);
+                    framebuffer_layers = min(framebuffer_layers, target.backbuffer()[0].layers());
+                }
+            }
+        }
+
+        for phase in 0..ctx.history_period() {
+            let views = create_views(ctx, factory, phase, &self.attachments, &images, target)?;
+            self.views.push(views);
+        }
+
+        self.framebuffer_width = framebuffer_width;
+        self.framebuffer_height = framebuffer_height;
+        self._framebuffer_layers = framebuffer_layers;
+
+        for subpass in &mut self.subpasses {
+            for (group, (buffer_ids, image_ids)) in
+                subpass.groups.iter_mut().zip(&subpass.resources)
+            {
+                group.resize(
+                    ctx,
+                    factory,
+                    aux,
+                    framebuffer_width,
+                    framebuffer_height,
+                    group_buffers(&buffers, buffer_ids),
+                    group_images(&images, image_ids),
+                )?;
+            }
+        }
+
+        self.views
+            .iter()
+            .map(|views| {
+                create_framebuffers(
+                    factory,
+                    &self.render_pass,
+                    views,
+                    self.attachments.len(),
+                    framebuffer_width,
+                    framebuffer_height,
+                    framebuffer_layers,
+                )
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 struct PerImage<B: Backend> {
-    framebuffer: B::Framebuffer,
     acquire: B::Semaphore,
     release: B::Semaphore,
     index: usize,
@@ -1079,6 +1277,8 @@ struct PerImage<B: Backend> {
 struct RenderPassNodeWithSurface<B: Backend, T: ?Sized> {
     common: RenderPassNodeCommon<B, T>,
     per_image: Vec<PerImage<B>>,
+    /// Framebuffer for each swapchain image in each phase of history images.
+    framebuffers: Vec<Vec<B::Framebuffer>>,
     free_acquire: B::Semaphore,
     target: Target<B>,
 }
@@ -1092,6 +1292,7 @@ where
         fmt.debug_struct("RenderPassNodeWithSurface")
             .field("common", &self.common)
             .field("per_image", &self.per_image)
+            .field("framebuffers", &self.framebuffers)
             .field("free_acquire", &self.free_acquire)
             .field("target", &self.target)
             .finish()
@@ -1105,7 +1306,7 @@ where
 {
     unsafe fn run<'a>(
         &mut self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: &mut Queue<B>,
         aux: &T,
@@ -1135,8 +1336,11 @@ where
             target,
             free_acquire,
             per_image,
+            framebuffers,
         } = self;
 
+        let phase = ctx.history_phase();
+
         let next = match target.next_image(&free_acquire) {
             Ok(next) => {
                 log::trace!("Presentable image acquired: {:#?}", next);
@@ -1164,6 +1368,7 @@ where
                             .fold(force_record, |force_record, group| {
                                 group
                                     .prepare(
+                                        ctx,
                                         factory,
                                         queue.id(),
                                         index,
@@ -1179,7 +1384,8 @@ where
                     },
                 );
 
-                if force_record || for_image.index != index {
+                // Recorded commands reference images bound in one phase.
+                if force_record || for_image.index != index || ctx.history_period() > 1 {
                     for_image.index = index;
                     cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
                 }
@@ -1189,12 +1395,12 @@ where
                 let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
                 let mut encoder = cbuf.encoder();
 
-                if let Some(barriers) = &acquire {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
+                if let Some(barriers) = &acquire[phase] {
+                    encoder.execute_commands(std::iter::once(&barriers.0));
                 }
 
                 if let Some(next) = &next {
-                    let ref mut for_image = per_image[next[0] as usize];
+                    let framebuffer = &framebuffers[phase][next[0] as usize];
 
                     let area = rendy_core::hal::pso::Rect {
                         x: 0,
@@ -1203,12 +1409,8 @@ where
                         h: *framebuffer_height as _,
                     };
 
-                    let mut pass_encoder = encoder.begin_render_pass_inline(
-                        &render_pass,
-                        &for_image.framebuffer,
-                        area,
-                        &clears,
-                    );
+                    let mut pass_encoder =
+                        encoder.begin_render_pass_inline(&render_pass, framebuffer, area, &clears);
 
                     subpasses
                         .iter_mut()
@@ -1230,8 +1432,8 @@ where
                     drop(pass_encoder);
                 }
 
-                if let Some(barriers) = &release {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
+                if let Some(barriers) = &release[phase] {
+                    encoder.execute_commands(std::iter::once(&barriers.0));
                 }
                 cbuf.finish()
             })
@@ -1268,12 +1470,59 @@ where
         }
     }
 
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        let extent = self
+            .target
+            .surface()
+            .extent(factory.physical())
+            .unwrap_or_else(|| self.target.extent());
+
+        self.target
+            .recreate(factory.physical(), factory.device(), extent)
+            .map_err(NodeBuildError::Swapchain)?;
+
+        for per_image in self.per_image.drain(..) {
+            factory.destroy_semaphore(per_image.acquire);
+            factory.destroy_semaphore(per_image.release);
+        }
+        for framebuffer in self.framebuffers.drain(..).flatten() {
+            factory.device().destroy_framebuffer(framebuffer);
+        }
+
+        self.framebuffers =
+            self.common
+                .resize(ctx, factory, aux, Some(&self.target), buffers, images)?;
+
+        for _ in &self.framebuffers[0] {
+            self.per_image.push(PerImage {
+                acquire: factory
+                    .create_semaphore()
+                    .map_err(NodeBuildError::OutOfMemory)?,
+                release: factory
+                    .create_semaphore()
+                    .map_err(NodeBuildError::OutOfMemory)?,
+                index: 0,
+            });
+        }
+
+        Ok(())
+    }
+
     unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         for per_image in self.per_image {
-            factory.device().destroy_framebuffer(per_image.framebuffer);
             factory.destroy_semaphore(per_image.acquire);
             factory.destroy_semaphore(per_image.release);
         }
+        for framebuffer in self.framebuffers.into_iter().flatten() {
+            factory.device().destroy_framebuffer(framebuffer);
+        }
         self.common.dispose(factory, aux);
         factory.destroy_surface(factory.destroy_target(self.target));
     }
@@ -1281,7 +1530,8 @@ where
 
 struct RenderPassNodeWithoutSurface<B: Backend, T: ?Sized> {
     common: RenderPassNodeCommon<B, T>,
-    framebuffer: B::Framebuffer,
+    /// Framebuffer for each phase of history images.
+    framebuffers: Vec<B::Framebuffer>,
 }
 
 impl<B, T> std::fmt::Debug for RenderPassNodeWithoutSurface<B, T>
@@ -1292,7 +1542,7 @@ where
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fmt.debug_struct("RenderPassNodeWithoutSurface")
             .field("common", &self.common)
-            .field("framebuffer", &self.framebuffer)
+            .field("framebuffers", &self.framebuffers)
             .finish()
     }
 }
@@ -1304,7 +1554,7 @@ where
 {
     unsafe fn run<'a>(
         &mut self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: &mut Queue<B>,
         aux: &T,
@@ -1331,9 +1581,11 @@ where
                     release,
                     ..
                 },
-            framebuffer,
+            framebuffers,
         } = self;
 
+        let phase = ctx.history_phase();
+
         let submit = command_cirque.encode(frames, command_pool, |mut cbuf| {
             let index = cbuf.index();
 
@@ -1346,6 +1598,7 @@ where
                         .fold(force_record, |force_record, group| {
                             group
                                 .prepare(
+                                    ctx,
                                     factory,
                                     queue.id(),
                                     index,
@@ -1361,7 +1614,8 @@ where
                 },
             );
 
-            if force_record {
+            // Recorded commands reference images bound in one phase.
+            if force_record || ctx.history_period() > 1 {
                 cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
             }
 
@@ -1369,8 +1623,8 @@ where
                 let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
                 let mut encoder = cbuf.encoder();
 
-                if let Some(barriers) = &acquire {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
+                if let Some(barriers) = &acquire[phase] {
+                    encoder.execute_commands(std::iter::once(&barriers.0));
                 }
 
                 let area = rendy_core::hal::pso::Rect {
@@ -1380,8 +1634,12 @@ where
                     h: *framebuffer_height as _,
                 };
 
-                let mut pass_encoder =
-                    encoder.begin_render_pass_inline(&render_pass, framebuffer, area, &clears);
+                let mut pass_encoder = encoder.begin_render_pass_inline(
+                    &render_pass,
+                    &framebuffers[phase],
+                    area,
+                    &clears,
+                );
 
                 subpasses
                     .iter_mut()
@@ -1402,8 +1660,8 @@ where
 
                 drop(pass_encoder);
 
-                if let Some(barriers) = &release {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
+                if let Some(barriers) = &release[phase] {
+                    encoder.execute_commands(std::iter::once(&barriers.0));
                 }
                 cbuf.finish()
             })
@@ -1420,12 +1678,213 @@ where
         );
     }
 
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<(), NodeBuildError> {
+        for framebuffer in self.framebuffers.drain(..) {
+            factory.device().destroy_framebuffer(framebuffer);
+        }
+
+        let framebuffers = self
+            .common
+            .resize(ctx, factory, aux, None, buffers, images)?;
+        for mut framebuffers in framebuffers {
+            assert_eq!(framebuffers.len(), 1);
+            self.framebuffers.push(framebuffers.remove(0));
+        }
+        Ok(())
+    }
+
     unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         self.common.dispose(factory, aux);
-        factory.device().destroy_framebuffer(self.framebuffer);
+        for framebuffer in self.framebuffers {
+            factory.device().destroy_framebuffer(framebuffer);
+        }
     }
 }
 
+/// Create view of the graph image to be used as framebuffer attachment.
+unsafe fn create_attachment_view<B: Backend>(
+    factory: &Factory<B>,
+    image: &crate::resource::Image<B>,
+    node_image: &NodeImage,
+) -> Result<B::ImageView, NodeBuildError> {
+    factory
+        .device()
+        .create_image_view(
+            image.raw(),
+            rendy_core::hal::image::ViewKind::D2,
+            image.format(),
+            rendy_core::hal::format::Swizzle::NO,
+            rendy_core::hal::image::SubresourceRange {
+                // NOTE: Framebuffer must always be created with only one mip level. If image contains multiple levels,
+                // only the first one is bound as an attachment.
+                // TODO: Allow customizing this behaviour to choose which level to bind.
+                levels: 0..1,
+                ..node_image.range.clone()
+            },
+        )
+        .map_err(NodeBuildError::View)
+}
+
+/// Create views of the attachments in given phase of history images.
+/// Surface attachment is replaced by views of all swapchain images.
+unsafe fn create_views<B: Backend>(
+    ctx: &GraphContext<B>,
+    factory: &Factory<B>,
+    phase: usize,
+    attachments: &[Attachment],
+    images: &[NodeImage],
+    target: Option<&Target<B>>,
+) -> Result<Vec<B::ImageView>, NodeBuildError> {
+    let mut views = Vec::new();
+    for &attachment in attachments {
+        match attachment {
+            Either::Left(image_id) => {
+                let node_image = images
+                    .iter()
+                    .find(|a| a.id == image_id)
+                    .expect("Attachment image wasn't provided");
+                let image = ctx
+                    .get_image_in_phase(image_id, phase)
+                    .expect("Image does not exist");
+                views.push(create_attachment_view(factory, image, node_image)?);
+            }
+            Either::Right(RenderPassSurface) => {
+                let target = target.expect("Render pass with surface attachment has target");
+                views.extend(create_target_views(factory, target)?);
+            }
+        }
+    }
+    Ok(views)
+}
+
+/// Create views of all swapchain images.
+unsafe fn create_target_views<B: Backend>(
+    factory: &Factory<B>,
+    target: &Target<B>,
+) -> Result<Vec<B::ImageView>, NodeBuildError> {
+    target
+        .backbuffer()
+        .iter()
+        .map(|image| {
+            factory
+                .device()
+                .create_image_view(
+                    image.raw(),
+                    rendy_core::hal::image::ViewKind::D2,
+                    image.format(),
+                    rendy_core::hal::format::Swizzle::NO,
+                    rendy_core::hal::image::SubresourceRange {
+                        aspects: image.format().surface_desc().aspects,
+                        levels: 0..1,
+                        layers: 0..1,
+                    },
+                )
+                .map_err(NodeBuildError::View)
+        })
+        .collect()
+}
+
+/// Create framebuffer for each swapchain image view,
+/// or single framebuffer if there is no surface attachment.
+unsafe fn create_framebuffers<B: Backend>(
+    factory: &Factory<B>,
+    render_pass: &B::RenderPass,
+    views: &[B::ImageView],
+    attachments: usize,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    framebuffer_layers: u16,
+) -> Result<Vec<B::Framebuffer>, NodeBuildError> {
+    // Swapchain image views, if any, are last ones.
+    (attachments - 1..views.len())
+        .map(|i| {
+            log::trace!(
+                "Create framebuffer for views {}..{} and {}",
+                0,
+                attachments - 1,
+                i,
+            );
+            factory
+                .device()
+                .create_framebuffer(
+                    render_pass,
+                    views[..attachments - 1].iter().chain(Some(&views[i])),
+                    rendy_core::hal::image::Extent {
+                        width: framebuffer_width,
+                        height: framebuffer_height,
+                        depth: framebuffer_layers as u32, // This is gfx-hal BUG as this parameter actually means framebuffer layers number,
+                    },
+                )
+                .map_err(NodeBuildError::OutOfMemory)
+        })
+        .collect()
+}
+
+/// Record secondary command buffers with acquire and release barriers
+/// for each phase of history images.
+fn record_barriers<B: Backend>(
+    ctx: &GraphContext<B>,
+    factory: &Factory<B>,
+    pool: &mut CommandPool<B, Graphics, IndividualReset>,
+    buffers: &[NodeBuffer],
+    images: &[NodeImage],
+) -> (
+    Vec<Option<BarriersCommands<B>>>,
+    Vec<Option<BarriersCommands<B>>>,
+) {
+    if !uses_pipeline_barriers::<B>(factory.device()) {
+        let none = || (0..ctx.history_period()).map(|_| None).collect();
+        return (none(), none());
+    }
+
+    (0..ctx.history_period())
+        .map(|phase| {
+            let acquire = record_barrier_commands(
+                pool,
+                Some(gfx_acquire_barriers_in_phase(ctx, phase, buffers, images)),
+            );
+            let release = record_barrier_commands(
+                pool,
+                Some(gfx_release_barriers_in_phase(ctx, phase, buffers, images)),
+            );
+            (acquire, release)
+        })
+        .unzip()
+}
+
+/// Find buffers used by render group.
+fn group_buffers(buffers: &[NodeBuffer], ids: &[BufferId]) -> Vec<NodeBuffer> {
+    ids.iter()
+        .map(|&id| {
+            buffers
+                .iter()
+                .find(|b| b.id == id)
+                .expect("Transient buffer wasn't provided")
+                .clone()
+        })
+        .collect()
+}
+
+/// Find images used by render group.
+fn group_images(images: &[NodeImage], ids: &[ImageId]) -> Vec<NodeImage> {
+    ids.iter()
+        .map(|&id| {
+            images
+                .iter()
+                .find(|i| i.id == id)
+                .expect("Transient image wasn't provided")
+                .clone()
+        })
+        .collect()
+}
+
 fn common_layout(acc: Layout, layout: Layout) -> Layout {
     match (acc, layout) {
         (Layout::Undefined, layout) => layout,
@@ -0,0 +1,124 @@
+use std::{any::Any, ops::Range};
+
+use {
+    crate::{allocator::Kind, block::Block, mapping::MappedRange},
+    gfx_hal::Backend,
+};
+
+/// Allocator implemented outside of this crate
+/// that can be added to `Heaps` for particular memory type
+/// with `Heaps::add_custom_allocator`.
+///
+/// `Heaps` picks it when `MemoryUsage::allocator_fitness` rates its `kind`
+/// higher than any built-in allocator configured for the memory type.
+pub trait CustomAllocator<B: Backend>: std::fmt::Debug + Send + Sync {
+    /// Get allocator kind.
+    /// Should be `Kind::Custom` with name unique among allocators of the memory type.
+    fn kind(&self) -> Kind;
+
+    /// Maximum allocation size.
+    fn max_allocation(&self) -> u64;
+
+    /// Allocate block of memory.
+    /// On success returns allocated block and amount of memory consumed from device.
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        align: u64,
+    ) -> Result<(CustomBlock<B>, u64), gfx_hal::device::AllocationError>;
+
+    /// Free block of memory previously allocated by this allocator.
+    /// Returns amount of memory returned to the device.
+    fn free(&mut self, device: &B::Device, block: CustomBlock<B>) -> u64;
+
+    /// Perform full cleanup of the memory allocated.
+    fn dispose(self: Box<Self>, device: &B::Device);
+}
+
+trait AnyBlock<B: Backend>: Block<B> + std::fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<B, T> AnyBlock<B> for T
+where
+    B: Backend,
+    T: Block<B> + std::fmt::Debug + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Type-erased block allocated by `CustomAllocator`.
+#[derive(Debug)]
+pub struct CustomBlock<B: Backend> {
+    block: Box<dyn AnyBlock<B>>,
+}
+
+impl<B> CustomBlock<B>
+where
+    B: Backend,
+{
+    /// Wrap allocator specific block.
+    pub fn new<T>(block: T) -> Self
+    where
+        T: Block<B> + std::fmt::Debug + Send + Sync + 'static,
+    {
+        CustomBlock {
+            block: Box::new(block),
+        }
+    }
+
+    /// Unwrap allocator specific block.
+    /// Returns `Err(self)` if block has another type.
+    pub fn downcast<T>(self) -> Result<T, Self>
+    where
+        T: Block<B> + std::fmt::Debug + Send + Sync + 'static,
+    {
+        if self.block.as_any().is::<T>() {
+            Ok(*self.block.into_any().downcast::<T>().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<B> Block<B> for CustomBlock<B>
+where
+    B: Backend,
+{
+    #[inline]
+    fn properties(&self) -> gfx_hal::memory::Properties {
+        self.block.properties()
+    }
+
+    #[inline]
+    fn memory(&self) -> &B::Memory {
+        self.block.memory()
+    }
+
+    #[inline]
+    fn range(&self) -> Range<u64> {
+        self.block.range()
+    }
+
+    #[inline]
+    fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        range: Range<u64>,
+    ) -> Result<MappedRange<'a, B>, gfx_hal::device::MapError> {
+        self.block.map(device, range)
+    }
+
+    #[inline]
+    fn unmap(&mut self, device: &B::Device) {
+        self.block.unmap(device)
+    }
+}
@@ -153,6 +153,10 @@ struct SizeEntry<B: Backend> {
     /// Bits per ready (non-exhausted) chunks with free blocks.
     ready_chunks: BitSet,
 
+    /// Bits per chunks drained by defragmentation pass.
+    /// Those chunks don't serve allocations until the pass ends.
+    draining_chunks: BitSet,
+
     /// List of chunks.
     chunks: slab::Slab<Chunk<B>>,
 }
@@ -166,6 +170,7 @@ where
             chunks: Default::default(),
             total_blocks: 0,
             ready_chunks: Default::default(),
+            draining_chunks: Default::default(),
         }
     }
 }
@@ -484,14 +489,75 @@ where
         chunk.release_blocks(block_index, count);
         if chunk.is_unused(block_size) {
             size_entry.ready_chunks.remove(chunk_index);
+            size_entry.draining_chunks.remove(chunk_index);
             let chunk = size_entry.chunks.remove(chunk_index as usize);
             self.free_chunk(device, chunk, block_size)
         } else {
-            size_entry.ready_chunks.add(chunk_index);
+            if !size_entry.draining_chunks.contains(chunk_index) {
+                size_entry.ready_chunks.add(chunk_index);
+            }
             0
         }
     }
 
+    /// Start defragmentation pass.
+    ///
+    /// Chunks where fraction of used blocks doesn't exceed `threshold`
+    /// stop serving allocations, so that blocks reallocated while the pass lasts
+    /// are packed into other chunks.
+    /// Use `is_movable` to find blocks that should be moved
+    /// and free them after their content is copied.
+    /// Drained chunk is freed as soon as its last block is freed.
+    ///
+    /// Sizes with single chunk are skipped
+    /// as moving its blocks would only allocate another chunk.
+    ///
+    /// Returns total size of drained chunks.
+    pub fn begin_defragmentation(&mut self, threshold: f32) -> u64 {
+        let mut drained = 0;
+        for (&block_size, size_entry) in &mut self.sizes {
+            if size_entry.chunks.len() < 2 {
+                continue;
+            }
+
+            for (chunk_index, chunk) in size_entry.chunks.iter() {
+                let blocks = chunk.blocks_count(block_size);
+                if chunk.used_blocks(block_size) as f32 <= blocks as f32 * threshold {
+                    log::trace!(
+                        "Drain chunk {} for blocks of size {}",
+                        chunk_index,
+                        block_size
+                    );
+                    size_entry.ready_chunks.remove(chunk_index as u32);
+                    size_entry.draining_chunks.add(chunk_index as u32);
+                    drained += chunk.size();
+                }
+            }
+        }
+        drained
+    }
+
+    /// Check if block resides in the chunk drained by defragmentation pass.
+    pub fn is_movable(&self, block: &DynamicBlock<B>) -> bool {
+        let block_size = block.size() / block.count as u64;
+        self.sizes.get(&block_size).map_or(false, |size_entry| {
+            size_entry.draining_chunks.contains(block.chunk_index)
+        })
+    }
+
+    /// Finish defragmentation pass.
+    /// Drained chunks that still have blocks allocated serve allocations again.
+    pub fn end_defragmentation(&mut self) {
+        for size_entry in self.sizes.values_mut() {
+            for chunk_index in (&size_entry.draining_chunks).iter() {
+                if !size_entry.chunks[chunk_index as usize].is_exhausted() {
+                    size_entry.ready_chunks.add(chunk_index);
+                }
+            }
+            size_entry.draining_chunks.clear();
+        }
+    }
+
     /// Perform full cleanup of the memory allocated.
     pub fn dispose(self) {
         if !thread::panicking() {
@@ -615,6 +681,16 @@ where
         start..end
     }
 
+    /// Get number of blocks in the chunk.
+    fn blocks_count(&self, block_size: u64) -> u32 {
+        (self.size() / block_size).min(MAX_BLOCKS_PER_CHUNK as u64) as u32
+    }
+
+    /// Get number of blocks in use.
+    fn used_blocks(&self, block_size: u64) -> u32 {
+        self.blocks_count(block_size) - self.blocks.count_ones()
+    }
+
     /// Check if there are free blocks.
     fn is_unused(&self, block_size: u64) -> bool {
         let blocks = (self.size() / block_size).min(MAX_BLOCKS_PER_CHUNK as u64);
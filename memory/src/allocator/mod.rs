@@ -1,19 +1,25 @@
 //! This module provides `Allocator` trait and few allocators that implements the trait.
 
+mod custom;
 mod dedicated;
 mod dynamic;
 mod linear;
+mod ring;
+mod tlsf;
 
 use crate::block::Block;
 
 pub use self::{
+    custom::{CustomAllocator, CustomBlock},
     dedicated::{DedicatedAllocator, DedicatedBlock},
     dynamic::{DynamicAllocator, DynamicBlock, DynamicConfig},
     linear::{LinearAllocator, LinearBlock, LinearConfig},
+    ring::{RingAllocator, RingBlock, RingConfig},
+    tlsf::{TlsfAllocator, TlsfBlock, TlsfConfig},
 };
 
 /// Allocator kind.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Kind {
     /// Memory object per allocation.
     Dedicated,
@@ -25,6 +31,17 @@ pub enum Kind {
     /// Fast and low overhead.
     /// Suitable for one-time-use allocations.
     Linear,
+
+    /// General purpose allocator for blocks of arbitrary size.
+    /// Wastes less memory than `Dynamic` on mid-size allocations.
+    Tlsf,
+
+    /// Allocator added with `Heaps::add_custom_allocator`, identified by name.
+    Custom(&'static str),
+
+    /// Ring of memory for transient per-frame data.
+    /// Only created explicitly with `Heaps::create_ring_allocator`.
+    Ring,
 }
 
 /// Allocator trait implemented for various allocators.
@@ -0,0 +1,362 @@
+use std::{collections::VecDeque, ops::Range, ptr::NonNull};
+
+use {
+    crate::{block::Block, mapping::*, memory::*, tracking::AllocationTag, util::*},
+    gfx_hal::{device::Device as _, Backend},
+};
+
+/// Memory block allocated from `RingAllocator`.
+/// Block is not freed explicitly,
+/// its range is reclaimed when the frame it was allocated for is complete.
+#[derive(Debug)]
+pub struct RingBlock<B: Backend> {
+    memory: *const Memory<B>,
+    ptr: NonNull<u8>,
+    range: Range<u64>,
+}
+
+unsafe impl<B> Send for RingBlock<B> where B: Backend {}
+unsafe impl<B> Sync for RingBlock<B> where B: Backend {}
+
+impl<B> RingBlock<B>
+where
+    B: Backend,
+{
+    fn shared_memory(&self) -> &Memory<B> {
+        // Memory won't be freed until frame this block was allocated for is complete.
+        unsafe { &*self.memory }
+    }
+}
+
+impl<B> Block<B> for RingBlock<B>
+where
+    B: Backend,
+{
+    #[inline]
+    fn properties(&self) -> gfx_hal::memory::Properties {
+        self.shared_memory().properties()
+    }
+
+    #[inline]
+    fn memory(&self) -> &B::Memory {
+        self.shared_memory().raw()
+    }
+
+    #[inline]
+    fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    #[inline]
+    fn map<'a>(
+        &'a mut self,
+        _device: &B::Device,
+        range: Range<u64>,
+    ) -> Result<MappedRange<'a, B>, gfx_hal::device::MapError> {
+        assert!(
+            range.start < range.end,
+            "Memory mapping region must have valid size"
+        );
+
+        if let Some((ptr, range)) = mapped_sub_range(self.ptr, self.range.clone(), range) {
+            let mapping = unsafe { MappedRange::from_raw(self.shared_memory(), ptr, range) };
+            Ok(mapping)
+        } else {
+            Err(gfx_hal::device::MapError::OutOfBounds)
+        }
+    }
+
+    #[inline]
+    fn unmap(&mut self, _device: &B::Device) {}
+}
+
+/// Config for `RingAllocator`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RingConfig {
+    /// Size of the ring.
+    /// Should fit data of all frames in flight,
+    /// otherwise bigger ring is allocated and used until the old one is drained.
+    pub ring_size: u64,
+}
+
+/// Ring allocator for transient data of the frame.
+///
+/// Blocks are allocated sequentially and tagged with index of the frame they are used in.
+/// All blocks of the frame are reclaimed at once
+/// after the frame's fences are signaled.
+/// Unlike `LinearAllocator` it never waits for individual blocks to be freed.
+///
+/// Ring allocator is created, used and disposed through `Heaps`,
+/// which account memory of the rings against heap budget.
+#[derive(Debug)]
+pub struct RingAllocator<B: Backend> {
+    memory_type: gfx_hal::MemoryTypeId,
+    memory_properties: gfx_hal::memory::Properties,
+    ring_size: u64,
+    tag: Option<AllocationTag>,
+
+    /// Rings with pending frames. Allocations are served from the last one.
+    rings: VecDeque<Ring<B>>,
+}
+
+unsafe impl<B> Send for RingAllocator<B> where B: Backend {}
+unsafe impl<B> Sync for RingAllocator<B> where B: Backend {}
+
+#[derive(Debug)]
+struct Ring<B: Backend> {
+    memory: Box<Memory<B>>,
+    ptr: NonNull<u8>,
+
+    /// Offset past last allocation. Grows monotonically, wraps modulo ring size.
+    head: u64,
+
+    /// Offset of the first byte still in use. Grows monotonically, wraps modulo ring size.
+    tail: u64,
+
+    /// Pending frames with `head` at the end of each one.
+    frames: VecDeque<(u64, u64)>,
+
+    /// Key of the ring in `AllocationTracker`.
+    tracking: usize,
+}
+
+impl<B> Ring<B>
+where
+    B: Backend,
+{
+    fn size(&self) -> u64 {
+        self.memory.size()
+    }
+
+    /// Find absolute offset where block fits.
+    fn fit(&self, size: u64, align: u64) -> Option<u64> {
+        fit(self.head, self.tail, self.size(), size, align)
+    }
+}
+
+/// Find absolute offset where block fits into the ring
+/// between `head` of allocated and `tail` of used memory.
+fn fit(head: u64, tail: u64, ring_size: u64, size: u64, align: u64) -> Option<u64> {
+    let position = head % ring_size;
+    let aligned_position = aligned(position, align);
+    let start = if aligned_position + size > ring_size {
+        // Skip to the beginning of the ring.
+        head - position + ring_size
+    } else {
+        head - position + aligned_position
+    };
+
+    if start + size - tail <= ring_size {
+        Some(start)
+    } else {
+        None
+    }
+}
+
+impl<B> RingAllocator<B>
+where
+    B: Backend,
+{
+    /// Get properties required by the `RingAllocator`.
+    pub fn properties_required() -> gfx_hal::memory::Properties {
+        gfx_hal::memory::Properties::CPU_VISIBLE
+    }
+
+    /// Create new `RingAllocator`
+    /// for `memory_type` with `memory_properties` specified,
+    /// with `RingConfig` provided.
+    /// Rings are reported under `tag`.
+    pub(crate) fn new(
+        memory_type: gfx_hal::MemoryTypeId,
+        memory_properties: gfx_hal::memory::Properties,
+        config: RingConfig,
+        tag: Option<AllocationTag>,
+    ) -> Self {
+        log::trace!(
+            "Create new 'ring' allocator: type: '{:?}', properties: '{:#?}' config: '{:#?}'",
+            memory_type,
+            memory_properties,
+            config
+        );
+        assert!(memory_properties.contains(Self::properties_required()));
+        assert!(
+            fits_usize(config.ring_size),
+            "Ring size must fit in both usize and u64"
+        );
+        RingAllocator {
+            memory_type,
+            memory_properties,
+            ring_size: config.ring_size,
+            tag,
+            rings: VecDeque::new(),
+        }
+    }
+
+    /// Get memory type of the rings.
+    pub fn memory_type(&self) -> gfx_hal::MemoryTypeId {
+        self.memory_type
+    }
+
+    pub(crate) fn tag(&self) -> Option<&AllocationTag> {
+        self.tag.as_ref()
+    }
+
+    /// Get size of the new ring required to fit the block.
+    /// Returns `None` if block fits into current ring.
+    pub(crate) fn required_ring(&self, size: u64, align: u64) -> Option<u64> {
+        match self.rings.back() {
+            Some(ring) if ring.fit(size, align).is_some() => None,
+            // Grow ring so that it would fit same data next time.
+            last => Some(
+                last.map_or(self.ring_size, |ring| ring.size() * 2)
+                    .max((size + align).next_power_of_two()),
+            ),
+        }
+    }
+
+    /// Allocate new ring that serves following allocations.
+    pub(crate) fn grow(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        tracking: usize,
+    ) -> Result<(), gfx_hal::device::AllocationError> {
+        log::trace!("Allocate ring of size: {} from device", size);
+
+        let (memory, ptr) = unsafe {
+            let raw = device.allocate_memory(self.memory_type, size)?;
+
+            let ptr = match device.map_memory(&raw, 0..size) {
+                Ok(ptr) => NonNull::new_unchecked(ptr),
+                Err(gfx_hal::device::MapError::OutOfMemory(error)) => {
+                    device.free_memory(raw);
+                    return Err(error.into());
+                }
+                Err(_) => panic!("Unexpected mapping failure"),
+            };
+
+            (Memory::from_raw(raw, size, self.memory_properties), ptr)
+        };
+
+        self.rings.push_back(Ring {
+            memory: Box::new(memory),
+            ptr,
+            head: 0,
+            tail: 0,
+            frames: VecDeque::new(),
+            tracking,
+        });
+        Ok(())
+    }
+
+    /// Allocate block for the frame with specified index.
+    /// Frame indices must not decrease between calls.
+    /// Returned block stays valid until `complete` is called with bound past `frame`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if block doesn't fit into current ring.
+    /// `grow` must be called first if `required_ring` returns new size.
+    pub(crate) fn alloc(&mut self, frame: u64, size: u64, align: u64) -> RingBlock<B> {
+        debug_assert!(align.is_power_of_two());
+
+        let ring = self.rings.back_mut().expect("Ring must be allocated");
+        let start = ring.fit(size, align).expect("Block must fit into the ring");
+        if let Some(&(last, _)) = ring.frames.back() {
+            assert!(last <= frame, "Frame indices must not decrease");
+        }
+        match ring.frames.back_mut() {
+            Some((last, end)) if *last == frame => *end = start + size,
+            _ => ring.frames.push_back((frame, start + size)),
+        }
+        ring.head = start + size;
+
+        let offset = start % ring.size();
+        let range = offset..offset + size;
+        let ptr = mapped_fitting_range(ring.ptr, 0..ring.size(), range.clone())
+            .expect("Block must be sub-range of ring");
+
+        RingBlock {
+            memory: &*ring.memory,
+            ptr,
+            range,
+        }
+    }
+
+    /// Reclaim blocks allocated for frames with index less than `upper_bound`.
+    /// Drained rings that were replaced by bigger ones are freed by `free_drained`.
+    pub(crate) fn complete(&mut self, upper_bound: u64) {
+        for ring in &mut self.rings {
+            while let Some(&(frame, end)) = ring.frames.front() {
+                if frame >= upper_bound {
+                    break;
+                }
+                ring.tail = end;
+                ring.frames.pop_front();
+            }
+        }
+    }
+
+    /// Free oldest ring if it is drained and replaced by newer one.
+    /// Returns size and tracking key of the freed ring.
+    pub(crate) fn free_drained(&mut self, device: &B::Device) -> Option<(u64, usize)> {
+        if self.rings.len() > 1 && self.rings[0].frames.is_empty() {
+            self.free_ring(device)
+        } else {
+            None
+        }
+    }
+
+    /// Free oldest ring regardless of pending frames.
+    /// Returns size and tracking key of the freed ring.
+    pub(crate) fn free_ring(&mut self, device: &B::Device) -> Option<(u64, usize)> {
+        let ring = self.rings.pop_front()?;
+        let size = ring.size();
+        unsafe {
+            device.unmap_memory(ring.memory.raw());
+            device.free_memory(ring.memory.into_raw());
+        }
+        Some((size, ring.tracking))
+    }
+
+    /// Check if blocks of any frame are not reclaimed yet.
+    pub(crate) fn is_pending(&self) -> bool {
+        self.rings.iter().any(|ring| !ring.frames.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fit;
+
+    #[test]
+    fn test_fit_empty() {
+        assert_eq!(fit(0, 0, 256, 256, 1), Some(0));
+        assert_eq!(fit(0, 0, 256, 257, 1), None);
+    }
+
+    #[test]
+    fn test_fit_aligned() {
+        assert_eq!(fit(10, 0, 256, 16, 16), Some(16));
+        assert_eq!(fit(16, 0, 256, 16, 16), Some(16));
+        assert_eq!(fit(10, 0, 256, 16, 1), Some(10));
+    }
+
+    #[test]
+    fn test_fit_wraps() {
+        // Block doesn't fit at the end and skips to the beginning of the ring.
+        assert_eq!(fit(200, 100, 256, 64, 1), Some(256));
+        // Skipped space is still in use by pending frames.
+        assert_eq!(fit(200, 50, 256, 64, 1), None);
+        // Offsets keep growing after wrapping.
+        assert_eq!(fit(300, 256, 256, 100, 4), Some(300));
+        assert_eq!(fit(300, 256, 256, 213, 4), None);
+    }
+
+    #[test]
+    fn test_fit_full() {
+        assert_eq!(fit(256, 0, 256, 1, 1), None);
+        assert_eq!(fit(256, 1, 256, 1, 1), Some(256));
+    }
+}
@@ -0,0 +1,561 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    ptr::NonNull,
+    thread,
+};
+
+use {
+    crate::{
+        allocator::{Allocator, Kind},
+        block::Block,
+        mapping::*,
+        memory::*,
+        util::*,
+    },
+    gfx_hal::{device::Device as _, Backend},
+};
+
+/// Memory block allocated from `TlsfAllocator`
+#[derive(Debug)]
+pub struct TlsfBlock<B: Backend> {
+    arena_index: u32,
+    memory: *const Memory<B>,
+    ptr: Option<NonNull<u8>>,
+    range: Range<u64>,
+    relevant: relevant::Relevant,
+}
+
+unsafe impl<B> Send for TlsfBlock<B> where B: Backend {}
+unsafe impl<B> Sync for TlsfBlock<B> where B: Backend {}
+
+impl<B> TlsfBlock<B>
+where
+    B: Backend,
+{
+    fn shared_memory(&self) -> &Memory<B> {
+        // Memory won't be freed until last block created from it deallocated.
+        unsafe { &*self.memory }
+    }
+
+    fn size(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    fn dispose(self) {
+        self.relevant.dispose();
+    }
+}
+
+impl<B> Block<B> for TlsfBlock<B>
+where
+    B: Backend,
+{
+    #[inline]
+    fn properties(&self) -> gfx_hal::memory::Properties {
+        self.shared_memory().properties()
+    }
+
+    #[inline]
+    fn memory(&self) -> &B::Memory {
+        self.shared_memory().raw()
+    }
+
+    #[inline]
+    fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    #[inline]
+    fn map<'a>(
+        &'a mut self,
+        _device: &B::Device,
+        range: Range<u64>,
+    ) -> Result<MappedRange<'a, B>, gfx_hal::device::MapError> {
+        debug_assert!(
+            range.start < range.end,
+            "Memory mapping region must have valid size"
+        );
+        if !self.shared_memory().host_visible() {
+            //TODO: invalid access error
+            return Err(gfx_hal::device::MapError::MappingFailed);
+        }
+
+        if let Some(ptr) = self.ptr {
+            if let Some((ptr, range)) = mapped_sub_range(ptr, self.range.clone(), range) {
+                let mapping = unsafe { MappedRange::from_raw(self.shared_memory(), ptr, range) };
+                Ok(mapping)
+            } else {
+                Err(gfx_hal::device::MapError::OutOfBounds)
+            }
+        } else {
+            Err(gfx_hal::device::MapError::MappingFailed)
+        }
+    }
+
+    #[inline]
+    fn unmap(&mut self, _device: &B::Device) {}
+}
+
+/// Config for `TlsfAllocator`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsfConfig {
+    /// All requests are rounded up to multiple of this value.
+    pub block_size_granularity: u64,
+
+    /// Size of memory objects allocated from device.
+    /// Allocations larger than quarter of this size are not served.
+    pub arena_size: u64,
+}
+
+/// Number of second level bins per power of two is `2 ^ SL_LOG2`.
+/// Allocation wastes at most `1 / 2 ^ SL_LOG2` of requested size to rounding.
+const SL_LOG2: u32 = 4;
+const SL_COUNT: u64 = 1 << SL_LOG2;
+const FL_COUNT: u32 = 64 - SL_LOG2 + 1;
+
+/// Two-level segregated fit allocator.
+/// Allocates variable sized blocks from big memory arenas.
+/// Free ranges are kept in bins by size,
+/// so suitable range is found in constant time,
+/// and merged with adjacent free ranges on deallocation.
+///
+/// Unlike `DynamicAllocator` it doesn't round sizes up to the power of two,
+/// which makes it suitable for mid-size allocations of arbitrary size.
+#[derive(Debug)]
+pub struct TlsfAllocator<B: Backend> {
+    /// Memory type that this allocator allocates.
+    memory_type: gfx_hal::MemoryTypeId,
+
+    /// Memory properties of the memory type.
+    memory_properties: gfx_hal::memory::Properties,
+
+    /// All requests are rounded up to multiple of this value.
+    block_size_granularity: u64,
+
+    /// Size of arenas.
+    arena_size: u64,
+
+    /// Memory objects allocated from device.
+    arenas: slab::Slab<Arena<B>>,
+
+    /// Bit per first level with non-empty bins.
+    fl_bitmap: u64,
+
+    /// Bits per non-empty second level bins for each first level.
+    sl_bitmaps: Vec<u32>,
+
+    /// Free ranges by bin. Each range is identified by arena index and offset.
+    bins: Vec<BTreeSet<(u32, u64)>>,
+}
+
+unsafe impl<B> Send for TlsfAllocator<B> where B: Backend {}
+unsafe impl<B> Sync for TlsfAllocator<B> where B: Backend {}
+
+#[derive(Debug)]
+struct Arena<B: Backend> {
+    memory: Box<Memory<B>>,
+    ptr: Option<NonNull<u8>>,
+
+    /// Free ranges sorted by offset.
+    free: BTreeMap<u64, u64>,
+}
+
+impl<B> Arena<B>
+where
+    B: Backend,
+{
+    fn is_unused(&self) -> bool {
+        self.free.get(&0) == Some(&self.memory.size())
+    }
+}
+
+impl<B> TlsfAllocator<B>
+where
+    B: Backend,
+{
+    /// Create new `TlsfAllocator`
+    /// for `memory_type` with `memory_properties` specified,
+    /// with `TlsfConfig` provided.
+    pub fn new(
+        memory_type: gfx_hal::MemoryTypeId,
+        memory_properties: gfx_hal::memory::Properties,
+        config: TlsfConfig,
+    ) -> Self {
+        log::trace!(
+            "Create new 'tlsf' allocator: type: '{:?}', properties: '{:#?}' config: '{:#?}'",
+            memory_type,
+            memory_properties,
+            config
+        );
+
+        assert!(
+            config.block_size_granularity.is_power_of_two(),
+            "Allocation granularity must be power of two"
+        );
+
+        assert!(
+            config.arena_size % config.block_size_granularity == 0,
+            "Arena size must be multiple of allocation granularity"
+        );
+
+        if memory_properties.contains(gfx_hal::memory::Properties::CPU_VISIBLE) {
+            assert!(
+                fits_usize(config.arena_size),
+                "Arena size must fit usize for mapping"
+            );
+        }
+
+        TlsfAllocator {
+            memory_type,
+            memory_properties,
+            block_size_granularity: config.block_size_granularity,
+            arena_size: config.arena_size,
+            arenas: slab::Slab::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: vec![0; FL_COUNT as usize],
+            bins: (0..FL_COUNT as u64 * SL_COUNT)
+                .map(|_| BTreeSet::new())
+                .collect(),
+        }
+    }
+
+    /// Maximum allocation size.
+    pub fn max_allocation(&self) -> u64 {
+        self.arena_size / 4
+    }
+
+    /// Allocate new arena from device.
+    fn alloc_arena(&mut self, device: &B::Device) -> Result<u32, gfx_hal::device::AllocationError> {
+        log::trace!("Allocate arena of size: {} from device", self.arena_size);
+
+        let (memory, ptr) = unsafe {
+            let raw = device.allocate_memory(self.memory_type, self.arena_size)?;
+
+            let ptr = if self
+                .memory_properties
+                .contains(gfx_hal::memory::Properties::CPU_VISIBLE)
+            {
+                log::trace!("Map new memory object");
+                match device.map_memory(&raw, 0..self.arena_size) {
+                    Ok(ptr) => Some(NonNull::new_unchecked(ptr)),
+                    Err(gfx_hal::device::MapError::OutOfMemory(error)) => {
+                        device.free_memory(raw);
+                        return Err(error.into());
+                    }
+                    Err(_) => panic!("Unexpected mapping failure"),
+                }
+            } else {
+                None
+            };
+            let memory = Memory::from_raw(raw, self.arena_size, self.memory_properties);
+            (memory, ptr)
+        };
+
+        let arena_index = self.arenas.insert(Arena {
+            memory: Box::new(memory),
+            ptr,
+            free: BTreeMap::new(),
+        }) as u32;
+        self.insert_free(arena_index, 0, self.arena_size);
+        Ok(arena_index)
+    }
+
+    fn free_arena(&mut self, device: &B::Device, arena_index: u32) -> u64 {
+        self.remove_free(arena_index, 0, self.arena_size);
+        let arena = self.arenas.remove(arena_index as usize);
+        log::trace!("Free arena: {:#?}", arena);
+        let size = arena.memory.size();
+        unsafe {
+            if arena.ptr.is_some() {
+                log::trace!("Unmap memory: {:#?}", arena.memory);
+                device.unmap_memory(arena.memory.raw());
+            }
+            device.free_memory(arena.memory.into_raw());
+        }
+        size
+    }
+
+    fn insert_free(&mut self, arena_index: u32, offset: u64, size: u64) {
+        let (fl, sl) = mapping(size);
+        self.bins[bin_index(fl, sl)].insert((arena_index, offset));
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl as usize] |= 1 << sl;
+        self.arenas[arena_index as usize].free.insert(offset, size);
+    }
+
+    fn remove_free(&mut self, arena_index: u32, offset: u64, size: u64) {
+        let (fl, sl) = mapping(size);
+        let ref mut bin = self.bins[bin_index(fl, sl)];
+        let removed = bin.remove(&(arena_index, offset));
+        debug_assert!(removed, "Free range must be in the bin");
+        if bin.is_empty() {
+            self.sl_bitmaps[fl as usize] &= !(1 << sl);
+            if self.sl_bitmaps[fl as usize] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+        self.arenas[arena_index as usize].free.remove(&offset);
+    }
+
+    /// Find free range of at least `size` bytes.
+    fn find_free(&self, size: u64) -> Option<(u32, u64, u64)> {
+        let (fl, sl) = mapping_search(size);
+
+        let sl_map = self.sl_bitmaps[fl as usize] & (!0u32).checked_shl(sl).unwrap_or(0);
+        let (fl, sl) = if sl_map != 0 {
+            (fl, sl_map.trailing_zeros())
+        } else {
+            let fl_map = self.fl_bitmap & (!0u64).checked_shl(fl + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+            let fl = fl_map.trailing_zeros();
+            (fl, self.sl_bitmaps[fl as usize].trailing_zeros())
+        };
+
+        let &(arena_index, offset) = self.bins[bin_index(fl, sl)].iter().next()?;
+        let size = self.arenas[arena_index as usize].free[&offset];
+        Some((arena_index, offset, size))
+    }
+
+    /// Perform full cleanup of the memory allocated.
+    /// Arenas are freed as soon as their last block is freed,
+    /// so nothing is left to free here.
+    pub fn dispose(self) {
+        if !thread::panicking() {
+            assert_eq!(self.arenas.len(), 0, "Arenas are still used");
+        } else if self.arenas.len() != 0 {
+            log::error!("Memory leak: {} arenas are still used", self.arenas.len());
+        }
+    }
+}
+
+impl<B> Allocator<B> for TlsfAllocator<B>
+where
+    B: Backend,
+{
+    type Block = TlsfBlock<B>;
+
+    fn kind() -> Kind {
+        Kind::Tlsf
+    }
+
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        align: u64,
+    ) -> Result<(TlsfBlock<B>, u64), gfx_hal::device::AllocationError> {
+        debug_assert!(size <= self.max_allocation());
+        debug_assert!(align.is_power_of_two());
+        let align = align.max(self.block_size_granularity);
+        let size = aligned(size, self.block_size_granularity);
+
+        log::trace!(
+            "Allocate tlsf block: size: {}, align: {}, type: {}",
+            size,
+            align,
+            self.memory_type.0
+        );
+
+        // Offsets are multiples of granularity,
+        // so extra space is needed only for alignment above it.
+        let padded = size + (align - self.block_size_granularity);
+
+        let (arena_index, offset, free_size, allocated) = match self.find_free(padded) {
+            Some((arena_index, offset, free_size)) => (arena_index, offset, free_size, 0),
+            None => {
+                let arena_index = self.alloc_arena(device)?;
+                (arena_index, 0, self.arena_size, self.arena_size)
+            }
+        };
+
+        self.remove_free(arena_index, offset, free_size);
+
+        let start = aligned(offset, align);
+        let end = start + size;
+        debug_assert!(end <= offset + free_size);
+        if start > offset {
+            self.insert_free(arena_index, offset, start - offset);
+        }
+        if offset + free_size > end {
+            self.insert_free(arena_index, end, offset + free_size - end);
+        }
+
+        let ref arena = self.arenas[arena_index as usize];
+        let block = TlsfBlock {
+            arena_index,
+            memory: &*arena.memory,
+            ptr: arena.ptr.map(|ptr| {
+                mapped_fitting_range(ptr, 0..self.arena_size, start..end)
+                    .expect("Block must be sub-range of arena")
+            }),
+            range: start..end,
+            relevant: relevant::Relevant,
+        };
+
+        Ok((block, allocated))
+    }
+
+    fn free(&mut self, device: &B::Device, block: TlsfBlock<B>) -> u64 {
+        log::trace!("Free block: {:#?}", block);
+
+        let arena_index = block.arena_index;
+        let mut offset = block.range.start;
+        let mut size = block.size();
+        block.dispose();
+
+        let (prev, next) = free_neighbours(&self.arenas[arena_index as usize].free, offset, size);
+
+        if let Some((prev, prev_size)) = prev {
+            self.remove_free(arena_index, prev, prev_size);
+            offset = prev;
+            size += prev_size;
+        }
+        if let Some(next_size) = next {
+            self.remove_free(arena_index, offset + size, next_size);
+            size += next_size;
+        }
+        self.insert_free(arena_index, offset, size);
+
+        if self.arenas[arena_index as usize].is_unused() {
+            self.free_arena(device, arena_index)
+        } else {
+            0
+        }
+    }
+}
+
+/// Get first and second level indices for the size.
+fn mapping(size: u64) -> (u32, u32) {
+    debug_assert_ne!(size, 0);
+    let log2 = 63 - size.leading_zeros();
+    if log2 < SL_LOG2 {
+        // Sizes smaller than `SL_COUNT` are binned linearly.
+        (0, size as u32)
+    } else {
+        let sl = (size >> (log2 - SL_LOG2)) - SL_COUNT;
+        (log2 - SL_LOG2 + 1, sl as u32)
+    }
+}
+
+/// Get first and second level indices of the first bin
+/// where every free range is at least `size` bytes.
+fn mapping_search(size: u64) -> (u32, u32) {
+    // Round size up to the next bin boundary.
+    let (fl, sl) = mapping(size);
+    if fl > 0 {
+        mapping(size + (1 << (fl - 1)) - 1)
+    } else {
+        (fl, sl)
+    }
+}
+
+fn bin_index(fl: u32, sl: u32) -> usize {
+    (fl as u64 * SL_COUNT + sl as u64) as usize
+}
+
+/// Find free ranges adjacent to `offset .. offset + size`
+/// among free ranges of the arena.
+/// Returns offset and size of the preceding range and size of the following one.
+fn free_neighbours(
+    free: &BTreeMap<u64, u64>,
+    offset: u64,
+    size: u64,
+) -> (Option<(u64, u64)>, Option<u64>) {
+    let prev = free
+        .range(..offset)
+        .next_back()
+        .map(|(&prev, &prev_size)| (prev, prev_size))
+        .filter(|&(prev, prev_size)| prev + prev_size == offset);
+    let next = free.get(&(offset + size)).cloned();
+    (prev, next)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Smallest size that maps to the bin.
+    fn bin_start(fl: u32, sl: u32) -> u64 {
+        if fl == 0 {
+            sl as u64
+        } else {
+            (SL_COUNT + sl as u64) << (fl - 1)
+        }
+    }
+
+    #[test]
+    fn test_mapping_small_sizes() {
+        for size in 1..SL_COUNT {
+            assert_eq!(mapping(size), (0, size as u32));
+        }
+    }
+
+    #[test]
+    fn test_mapping_boundaries() {
+        assert_eq!(mapping(SL_COUNT), (1, 0));
+        assert_eq!(mapping(SL_COUNT * 2 - 1), (1, SL_COUNT as u32 - 1));
+        assert_eq!(mapping(SL_COUNT * 2), (2, 0));
+        assert_eq!(mapping(SL_COUNT * 2 + 1), (2, 0));
+        assert_eq!(mapping(SL_COUNT * 2 + 2), (2, 1));
+        assert_eq!(mapping(1 << 20), (20 - SL_LOG2 + 1, 0));
+        assert_eq!(
+            mapping((1 << 21) - 1),
+            (20 - SL_LOG2 + 1, SL_COUNT as u32 - 1)
+        );
+        assert_eq!(mapping(!0), (FL_COUNT - 1, SL_COUNT as u32 - 1));
+    }
+
+    #[test]
+    fn test_mapping_bin_starts() {
+        for fl in 1..20 {
+            for sl in 0..SL_COUNT as u32 {
+                let start = bin_start(fl, sl);
+                assert_eq!(mapping(start), (fl, sl));
+                assert_ne!(mapping(start - 1), (fl, sl));
+                assert!(bin_index(fl, sl) < (FL_COUNT as u64 * SL_COUNT) as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mapping_monotonic() {
+        let mut last = mapping(1);
+        for size in 2..1 << 14 {
+            let next = mapping(size);
+            assert!(next >= last, "Mapping of {} decreased", size);
+            last = next;
+        }
+    }
+
+    #[test]
+    fn test_mapping_search_fits() {
+        for size in 1..1 << 14 {
+            let (fl, sl) = mapping_search(size);
+            assert!(bin_start(fl, sl) >= size, "Bin is too small for {}", size);
+            // No more than one bin is skipped.
+            assert!(bin_index(fl, sl) <= bin_index(mapping(size).0, mapping(size).1) + 1);
+        }
+    }
+
+    #[test]
+    fn test_free_neighbours() {
+        let mut free = BTreeMap::new();
+        free.insert(0, 64);
+        free.insert(128, 64);
+        free.insert(256, 64);
+
+        // Both neighbours are free.
+        assert_eq!(free_neighbours(&free, 64, 64), (Some((0, 64)), Some(64)));
+        // Only following range is free.
+        assert_eq!(free_neighbours(&free, 224, 32), (None, Some(64)));
+        // Only preceding range is free.
+        assert_eq!(free_neighbours(&free, 192, 32), (Some((128, 64)), None));
+        // Neighbouring ranges are in use.
+        assert_eq!(free_neighbours(&free, 352, 32), (None, None));
+        assert_eq!(free_neighbours(&free, 320, 16), (Some((256, 64)), None));
+    }
+}
@@ -1,10 +1,11 @@
-use crate::utilization::*;
+use {super::HeapBudget, crate::utilization::*};
 
 #[derive(Debug)]
 pub(super) struct MemoryHeap {
     size: u64,
     used: u64,
     effective: u64,
+    budget: HeapBudget,
 }
 
 impl MemoryHeap {
@@ -13,6 +14,7 @@ impl MemoryHeap {
             size,
             used: 0,
             effective: 0,
+            budget: HeapBudget::default(),
         }
     }
 
@@ -25,10 +27,31 @@ impl MemoryHeap {
         }
     }
 
-    pub(super) fn allocated(&mut self, used: u64, effective: u64) {
+    /// Bytes that can be allocated before hitting either heap size or hard limit.
+    pub(super) fn headroom(&self) -> u64 {
+        let limit = self
+            .budget
+            .hard
+            .map_or(self.size, |hard| hard.min(self.size));
+        limit.saturating_sub(self.used)
+    }
+
+    pub(super) fn budget(&self) -> HeapBudget {
+        self.budget
+    }
+
+    pub(super) fn set_budget(&mut self, budget: HeapBudget) {
+        self.budget = budget;
+    }
+
+    /// Record allocation.
+    /// Returns `true` if this allocation pushed usage over soft limit.
+    pub(super) fn allocated(&mut self, used: u64, effective: u64) -> bool {
+        let was_over = self.over_soft_limit();
         self.used += used;
         self.effective += effective;
         debug_assert!(self.used >= self.effective);
+        !was_over && self.over_soft_limit()
     }
 
     pub(super) fn freed(&mut self, used: u64, effective: u64) {
@@ -37,6 +60,10 @@ impl MemoryHeap {
         debug_assert!(self.used >= self.effective);
     }
 
+    fn over_soft_limit(&self) -> bool {
+        self.budget.soft.map_or(false, |soft| self.used > soft)
+    }
+
     pub(super) fn utilization(&self) -> MemoryHeapUtilization {
         MemoryHeapUtilization {
             utilization: MemoryUtilization {
@@ -1,5 +1,5 @@
 use {
-    super::{BlockFlavor, HeapsConfig},
+    super::{heap::MemoryHeap, BlockFlavor, HeapsConfig, HeapsError},
     crate::{allocator::*, usage::MemoryUsage, utilization::*},
     gfx_hal::memory::Properties,
 };
@@ -11,6 +11,8 @@ pub(super) struct MemoryType<B: gfx_hal::Backend> {
     dedicated: DedicatedAllocator,
     linear: Option<LinearAllocator<B>>,
     dynamic: Option<DynamicAllocator<B>>,
+    tlsf: Option<TlsfAllocator<B>>,
+    custom: Vec<Box<dyn CustomAllocator<B>>>,
     // chunk: Option<ChunkAllocator>,
     used: u64,
     effective: u64,
@@ -40,6 +42,10 @@ where
             dynamic: config
                 .dynamic
                 .map(|config| DynamicAllocator::new(memory_type, properties, config)),
+            tlsf: config
+                .tlsf
+                .map(|config| TlsfAllocator::new(memory_type, properties, config)),
+            custom: Vec::new(),
             used: 0,
             effective: 0,
         }
@@ -53,17 +59,57 @@ where
         self.heap_index
     }
 
+    pub(super) fn block_kind(&self, block: &BlockFlavor<B>) -> Kind {
+        match block {
+            BlockFlavor::Dedicated(_) => Kind::Dedicated,
+            BlockFlavor::Linear(_) => Kind::Linear,
+            BlockFlavor::Dynamic(_) => Kind::Dynamic,
+            BlockFlavor::Tlsf(_) => Kind::Tlsf,
+            BlockFlavor::Custom(index, _) => self.custom[*index].kind(),
+        }
+    }
+
+    pub(super) fn add_custom(&mut self, allocator: Box<dyn CustomAllocator<B>>) {
+        assert!(
+            self.custom
+                .iter()
+                .all(|custom| custom.kind() != allocator.kind()),
+            "Allocator of kind {:?} is already added",
+            allocator.kind()
+        );
+        self.custom.push(allocator);
+    }
+
+    /// Allocate block and record memory taken from the device in the `heap`.
+    /// Fails if allocator takes more memory from the device than hard limit of the `heap` allows.
+    /// Returns `true` along with the block if allocation pushed heap usage over soft limit.
     pub(super) fn alloc(
         &mut self,
         device: &B::Device,
+        heap: &mut MemoryHeap,
         usage: impl MemoryUsage,
         size: u64,
         align: u64,
-    ) -> Result<(BlockFlavor<B>, u64), gfx_hal::device::AllocationError> {
+    ) -> Result<(BlockFlavor<B>, bool), HeapsError> {
+        let headroom = heap.headroom();
         let (block, allocated) = self.alloc_impl(device, usage, size, align)?;
+
+        if allocated > headroom {
+            // Sub-allocator took whole chunk or line that doesn't fit into the budget.
+            // Memory it keeps after the block is freed stays accounted.
+            let effective = block.size();
+            let freed = self.free(device, block);
+            self.used += allocated;
+            self.used -= freed;
+            heap.allocated(allocated, effective);
+            heap.freed(freed, effective);
+            return Err(HeapsError::BudgetExceeded(self.heap_index, allocated));
+        }
+
         self.effective += block.size();
         self.used += allocated;
-        Ok((block, allocated))
+        let over_soft_limit = heap.allocated(allocated, block.size());
+        Ok((block, over_soft_limit))
     }
 
     fn alloc_impl(
@@ -73,6 +119,48 @@ where
         size: u64,
         align: u64,
     ) -> Result<(BlockFlavor<B>, u64), gfx_hal::device::AllocationError> {
+        let others = self
+            .dynamic
+            .as_ref()
+            .map(|_| usage.allocator_fitness(Kind::Dynamic))
+            .into_iter()
+            .chain(
+                self.linear
+                    .as_ref()
+                    .map(|_| usage.allocator_fitness(Kind::Linear)),
+            )
+            .max()
+            .unwrap_or(0);
+
+        let builtin = self
+            .tlsf
+            .as_ref()
+            .map_or(others, |_| others.max(usage.allocator_fitness(Kind::Tlsf)));
+
+        // Custom allocators are used only if usage explicitly rates them above built-in ones.
+        if let Some((_, index, custom)) = self
+            .custom
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, custom)| custom.max_allocation() >= size)
+            .map(|(index, custom)| (usage.allocator_fitness(custom.kind()), index, custom))
+            .filter(|&(fitness, _, _)| fitness > builtin)
+            .max_by_key(|&(fitness, _, _)| fitness)
+        {
+            return custom
+                .alloc(device, size, align)
+                .map(|(block, size)| (BlockFlavor::Custom(index, block), size));
+        }
+
+        if let Some(tlsf) = self.tlsf.as_mut() {
+            // Prefer `Tlsf` only if usage rates it higher than other present sub-allocators.
+            if tlsf.max_allocation() >= size && usage.allocator_fitness(Kind::Tlsf) > others {
+                return tlsf
+                    .alloc(device, size, align)
+                    .map(|(block, size)| (BlockFlavor::Tlsf(block), size));
+            }
+        }
+
         match (self.dynamic.as_mut(), self.linear.as_mut()) {
             (Some(dynamic), Some(linear)) => {
                 if dynamic.max_allocation() >= size
@@ -128,6 +216,27 @@ where
             BlockFlavor::Dedicated(block) => self.dedicated.free(device, block),
             BlockFlavor::Linear(block) => self.linear.as_mut().unwrap().free(device, block),
             BlockFlavor::Dynamic(block) => self.dynamic.as_mut().unwrap().free(device, block),
+            BlockFlavor::Tlsf(block) => self.tlsf.as_mut().unwrap().free(device, block),
+            BlockFlavor::Custom(index, block) => self.custom[index].free(device, block),
+        }
+    }
+
+    pub(super) fn begin_defragmentation(&mut self, threshold: f32) -> u64 {
+        self.dynamic
+            .as_mut()
+            .map_or(0, |dynamic| dynamic.begin_defragmentation(threshold))
+    }
+
+    pub(super) fn is_movable(&self, block: &BlockFlavor<B>) -> bool {
+        match block {
+            BlockFlavor::Dynamic(block) => self.dynamic.as_ref().unwrap().is_movable(block),
+            _ => false,
+        }
+    }
+
+    pub(super) fn end_defragmentation(&mut self) {
+        if let Some(dynamic) = self.dynamic.as_mut() {
+            dynamic.end_defragmentation();
         }
     }
 
@@ -142,6 +251,15 @@ where
             dynamic.dispose();
             log::trace!("Dynamic allocator disposed");
         }
+        if let Some(tlsf) = self.tlsf {
+            tlsf.dispose();
+            log::trace!("Tlsf allocator disposed");
+        }
+        for custom in self.custom {
+            let kind = custom.kind();
+            custom.dispose(device);
+            log::trace!("{:?} allocator disposed", kind);
+        }
     }
 
     pub(super) fn utilization(&self) -> MemoryTypeUtilization {
@@ -3,7 +3,15 @@ mod memory_type;
 
 use {
     self::{heap::MemoryHeap, memory_type::MemoryType},
-    crate::{allocator::*, block::Block, mapping::*, usage::MemoryUsage, util::*, utilization::*},
+    crate::{
+        allocator::*,
+        block::Block,
+        mapping::*,
+        tracking::{AllocationReport, AllocationTag, AllocationTracker},
+        usage::MemoryUsage,
+        util::*,
+        utilization::*,
+    },
     std::ops::Range,
 };
 
@@ -15,6 +23,8 @@ pub enum HeapsError {
     AllocationError(gfx_hal::device::AllocationError),
     /// No memory types among required for resource with requested properties was found.
     NoSuitableMemory(u32, gfx_hal::memory::Properties),
+    /// Allocation of requested size would exceed hard limit of the heap with specified index.
+    BudgetExceeded(usize, u64),
 }
 
 impl std::fmt::Display for HeapsError {
@@ -26,6 +36,11 @@ impl std::fmt::Display for HeapsError {
                 "Memory type among ({}) with properties ({:?}) not found",
                 e, e2
             ),
+            HeapsError::BudgetExceeded(heap, size) => write!(
+                f,
+                "Allocation of {} bytes would exceed budget of heap {}",
+                size, heap
+            ),
         }
     }
 }
@@ -52,6 +67,36 @@ pub struct HeapsConfig {
 
     /// Config for dynamic sub-allocator.
     pub dynamic: Option<DynamicConfig>,
+
+    /// Config for tlsf sub-allocator.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tlsf: Option<TlsfConfig>,
+}
+
+/// Limits on memory allocated from one heap.
+/// Both limits are in bytes and account memory the same way as `MemoryUtilization::used`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeapBudget {
+    /// Allocations that push heap usage over this limit succeed,
+    /// but are reported to the callback set with `Heaps::set_soft_limit_callback`.
+    pub soft: Option<u64>,
+
+    /// Allocations that would push heap usage over this limit
+    /// fail with `HeapsError::BudgetExceeded`.
+    pub hard: Option<u64>,
+}
+
+/// Callback invoked with heap index and its utilization
+/// when allocation pushes heap usage over soft limit.
+pub type SoftLimitCallback = Box<dyn FnMut(usize, MemoryHeapUtilization) + Send>;
+
+struct SoftLimit(SoftLimitCallback);
+
+impl std::fmt::Debug for SoftLimit {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str("SoftLimitCallback")
+    }
 }
 
 /// Heaps available on particular physical device.
@@ -59,6 +104,8 @@ pub struct HeapsConfig {
 pub struct Heaps<B: gfx_hal::Backend> {
     types: Vec<MemoryType<B>>,
     heaps: Vec<MemoryHeap>,
+    soft_limit: Option<SoftLimit>,
+    tracker: AllocationTracker,
 }
 
 impl<B> Heaps<B>
@@ -95,9 +142,65 @@ where
                 })
                 .collect(),
             heaps,
+            soft_limit: None,
+            tracker: AllocationTracker::default(),
         }
     }
 
+    /// Add custom allocator for the memory type.
+    /// It will serve allocations for `MemoryUsage` that rates its kind
+    /// higher than built-in allocators.
+    ///
+    /// # Panics
+    ///
+    /// Panics if allocator of the same kind is already added to the memory type.
+    pub fn add_custom_allocator(
+        &mut self,
+        memory_type: u32,
+        allocator: Box<dyn CustomAllocator<B>>,
+    ) {
+        self.types[memory_type as usize].add_custom(allocator);
+    }
+
+    /// Get budget of the heap.
+    pub fn budget(&self, heap_index: usize) -> HeapBudget {
+        self.heaps[heap_index].budget()
+    }
+
+    /// Set budget of the heap.
+    /// Limits are checked for subsequent allocations only,
+    /// lowering them below current usage doesn't free anything.
+    pub fn set_budget(&mut self, heap_index: usize, budget: HeapBudget) {
+        self.heaps[heap_index].set_budget(budget);
+    }
+
+    /// Set callback to invoke when allocation pushes heap usage over soft limit.
+    /// The callback is invoked once per crossing,
+    /// usage must drop below soft limit before it is invoked again for the same heap.
+    ///
+    /// **The callback is invoked during allocation, while `Heaps` are borrowed
+    /// (and locked when owned by `Factory`). It must not allocate or free memory
+    /// nor call back into `Heaps` or `Factory`, otherwise it deadlocks.**
+    /// Record the event and react to it after allocation returns instead.
+    pub fn set_soft_limit_callback(
+        &mut self,
+        callback: impl FnMut(usize, MemoryHeapUtilization) + Send + 'static,
+    ) {
+        self.soft_limit = Some(SoftLimit(Box::new(callback)));
+    }
+
+    /// Get number of bytes that can be allocated for intended `usage`
+    /// without exceeding either heap size or hard limit.
+    /// Returns largest headroom among memory types suitable for the `usage`.
+    pub fn headroom(&self, usage: impl MemoryUsage) -> u64 {
+        self.types
+            .iter()
+            .filter(|mt| mt.properties().contains(usage.properties_required()))
+            .map(|mt| self.heaps[mt.heap_index()].headroom())
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Allocate memory block
     /// from one of memory types specified by `mask`,
     /// for intended `usage`,
@@ -136,13 +239,32 @@ where
                 ));
             }
 
-            suitable_types
+            let available_types = suitable_types
                 .into_iter()
                 .filter(|(_, mt, _)| self.heaps[mt.heap_index()].available() > size + align)
+                .collect::<smallvec::SmallVec<[_; 64]>>();
+
+            if available_types.is_empty() {
+                log::error!("All suitable heaps are exhausted. {:#?}", self);
+                return Err(gfx_hal::device::OutOfMemory::Device.into());
+            }
+
+            let best_available = available_types
+                .iter()
+                .max_by_key(|&&(_, _, fitness)| fitness)
+                .map(|&(_, mt, _)| mt.heap_index())
+                .unwrap();
+
+            available_types
+                .into_iter()
+                .filter(|(_, mt, _)| self.heaps[mt.heap_index()].headroom() > size + align)
                 .max_by_key(|&(_, _, fitness)| fitness)
                 .ok_or_else(|| {
-                    log::error!("All suitable heaps are exhausted. {:#?}", self);
-                    gfx_hal::device::OutOfMemory::Device
+                    log::warn!(
+                        "All suitable heaps are over budget. {:#?}",
+                        self.utilization()
+                    );
+                    HeapsError::BudgetExceeded(best_available, size)
                 })?
         };
 
@@ -178,12 +300,32 @@ where
             return Err(gfx_hal::device::OutOfMemory::Device.into());
         }
 
-        let (block, allocated) = memory_type.alloc(device, usage, size, align)?;
-        memory_heap.allocated(allocated, block.size());
+        if memory_heap.headroom() < size {
+            return Err(HeapsError::BudgetExceeded(memory_type.heap_index(), size));
+        }
+
+        let tag = usage.tag().cloned();
+        let (block, over_soft_limit) =
+            memory_type.alloc(device, memory_heap, usage, size, align)?;
+        if over_soft_limit {
+            let heap_index = memory_type.heap_index();
+            log::warn!("Heap {} usage is over soft limit", heap_index);
+            if let Some(SoftLimit(callback)) = &mut self.soft_limit {
+                callback(heap_index, memory_heap.utilization());
+            }
+        }
+
+        let tracking = self.tracker.track(
+            tag,
+            memory_type.block_kind(&block),
+            memory_index,
+            block.size(),
+        );
 
         Ok(MemoryBlock {
             block,
             memory_index,
+            tracking,
         })
     }
 
@@ -200,17 +342,181 @@ where
         let ref mut memory_heap = self.heaps[memory_type.heap_index()];
         let freed = memory_type.free(device, block.block);
         memory_heap.freed(freed, size);
+        self.tracker.untrack(block.tracking);
+    }
+
+    /// Create ring allocator for transient per-frame data
+    /// in the memory type most suitable for `usage` among specified by `mask`.
+    ///
+    /// Rings are allocated from the device by `allocate_from_ring`
+    /// and accounted by utilization, budget and allocation report of the heaps
+    /// in full size, under tag of the `usage`.
+    pub fn create_ring_allocator(
+        &self,
+        mask: u32,
+        usage: impl MemoryUsage,
+        config: RingConfig,
+    ) -> Result<RingAllocator<B>, HeapsError> {
+        let required = usage.properties_required() | RingAllocator::<B>::properties_required();
+        let (index, mt) = self
+            .types
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| (mask & (1u32 << index)) != 0)
+            .filter(|(_, mt)| mt.properties().contains(required))
+            .max_by_key(|(_, mt)| usage.memory_fitness(mt.properties()))
+            .ok_or(HeapsError::NoSuitableMemory(mask, required))?;
+
+        Ok(RingAllocator::new(
+            gfx_hal::MemoryTypeId(index),
+            mt.properties(),
+            config,
+            usage.tag().cloned(),
+        ))
+    }
+
+    /// Allocate block from the ring for the frame with specified index.
+    /// Frame indices must not decrease between calls.
+    /// Block stays valid until `complete_ring` is called with bound past `frame`.
+    ///
+    /// Ring allocator must be created by this `Heaps`.
+    pub fn allocate_from_ring(
+        &mut self,
+        device: &B::Device,
+        ring: &mut RingAllocator<B>,
+        frame: u64,
+        size: u64,
+        align: u64,
+    ) -> Result<RingBlock<B>, HeapsError> {
+        if let Some(ring_size) = ring.required_ring(size, align) {
+            let memory_index = ring.memory_type().0 as u32;
+            let heap_index = self.types[memory_index as usize].heap_index();
+            let ref mut memory_heap = self.heaps[heap_index];
+
+            if memory_heap.available() < ring_size {
+                return Err(gfx_hal::device::OutOfMemory::Device.into());
+            }
+
+            if memory_heap.headroom() < ring_size {
+                return Err(HeapsError::BudgetExceeded(heap_index, ring_size));
+            }
+
+            let tracking =
+                self.tracker
+                    .track(ring.tag().cloned(), Kind::Ring, memory_index, ring_size);
+            if let Err(error) = ring.grow(device, ring_size, tracking) {
+                self.tracker.untrack(tracking);
+                return Err(error.into());
+            }
+
+            // Whole ring stays in use until it is replaced and drained.
+            if memory_heap.allocated(ring_size, ring_size) {
+                log::warn!("Heap {} usage is over soft limit", heap_index);
+                if let Some(SoftLimit(callback)) = &mut self.soft_limit {
+                    callback(heap_index, memory_heap.utilization());
+                }
+            }
+        }
+
+        Ok(ring.alloc(frame, size, align))
+    }
+
+    /// Reclaim blocks of the ring allocated for frames with index less than `upper_bound`
+    /// and free drained rings that were replaced by bigger ones.
+    ///
+    /// Ring allocator must be created by this `Heaps`.
+    pub fn complete_ring(
+        &mut self,
+        device: &B::Device,
+        ring: &mut RingAllocator<B>,
+        upper_bound: u64,
+    ) {
+        ring.complete(upper_bound);
+        while let Some((size, tracking)) = ring.free_drained(device) {
+            self.ring_freed(ring, size, tracking);
+        }
+    }
+
+    /// Free all memory of the ring allocator.
+    /// All frames blocks were allocated for must be complete.
+    ///
+    /// Ring allocator must be created by this `Heaps`.
+    pub fn dispose_ring_allocator(&mut self, device: &B::Device, mut ring: RingAllocator<B>) {
+        if ring.is_pending() {
+            log::error!("Ring allocator disposed while frames are pending");
+        }
+        while let Some((size, tracking)) = ring.free_ring(device) {
+            self.ring_freed(&ring, size, tracking);
+        }
+    }
+
+    fn ring_freed(&mut self, ring: &RingAllocator<B>, size: u64, tracking: usize) {
+        let heap_index = self.types[ring.memory_type().0].heap_index();
+        self.heaps[heap_index].freed(size, size);
+        self.tracker.untrack(tracking);
+    }
+
+    /// Start defragmentation pass.
+    ///
+    /// Sparsely populated chunks of dynamic allocators,
+    /// where fraction of used blocks doesn't exceed `threshold`,
+    /// are excluded from serving new allocations.
+    /// Blocks for which `is_movable` returns `true` should be reallocated,
+    /// their content copied and old blocks freed.
+    /// Chunks are returned to the device once all their blocks are freed.
+    ///
+    /// Returns total size of chunks that would be freed if all movable blocks are moved.
+    pub fn begin_defragmentation(&mut self, threshold: f32) -> u64 {
+        self.types
+            .iter_mut()
+            .map(|mt| mt.begin_defragmentation(threshold))
+            .sum()
+    }
+
+    /// Check if block should be moved by defragmentation pass.
+    pub fn is_movable(&self, block: &MemoryBlock<B>) -> bool {
+        self.types[block.memory_index as usize].is_movable(&block.block)
+    }
+
+    /// Get tag of the `MemoryUsage` block was allocated with.
+    pub fn block_tag(&self, block: &MemoryBlock<B>) -> Option<&AllocationTag> {
+        self.tracker.tag(block.tracking)
+    }
+
+    /// Get kind of allocator that served the block.
+    pub fn block_kind(&self, block: &MemoryBlock<B>) -> Kind {
+        self.types[block.memory_index as usize].block_kind(&block.block)
+    }
+
+    /// Finish defragmentation pass.
+    /// Chunks that weren't emptied serve allocations again.
+    ///
+    /// Call this after moved blocks are freed,
+    /// otherwise chunks they occupy may get reused.
+    pub fn end_defragmentation(&mut self) {
+        for mt in &mut self.types {
+            mt.end_defragmentation();
+        }
     }
 
     /// Dispose of allocator.
     /// Cleanup allocators before dropping.
     /// Will panic if memory instances are left allocated.
     pub fn dispose(self, device: &B::Device) {
+        if !self.tracker.is_empty() {
+            log::error!("Memory leak: {}", self.tracker.report());
+        }
         for mt in self.types {
             mt.dispose(device)
         }
     }
 
+    /// Get report of live allocations grouped by tag.
+    /// Allocations are tagged by `MemoryUsage::tag`.
+    pub fn allocation_report(&self) -> AllocationReport {
+        self.tracker.report()
+    }
+
     /// Get memory utilization.
     pub fn utilization(&self) -> TotalMemoryUtilization {
         TotalMemoryUtilization {
@@ -225,6 +531,7 @@ where
 pub struct MemoryBlock<B: gfx_hal::Backend> {
     block: BlockFlavor<B>,
     memory_index: u32,
+    tracking: usize,
 }
 
 impl<B> MemoryBlock<B>
@@ -242,6 +549,9 @@ enum BlockFlavor<B: gfx_hal::Backend> {
     Dedicated(DedicatedBlock<B>),
     Linear(LinearBlock<B>),
     Dynamic(DynamicBlock<B>),
+    Tlsf(TlsfBlock<B>),
+    /// Block and index of custom allocator that allocated it.
+    Custom(usize, CustomBlock<B>),
     // Chunk(ChunkBlock<B>),
 }
 
@@ -252,6 +562,8 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Tlsf($block) => $expr,
+            Custom(_, $block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -261,6 +573,8 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Tlsf($block) => $expr,
+            Custom(_, $block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -270,6 +584,8 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Tlsf($block) => $expr,
+            Custom(_, $block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -286,6 +602,8 @@ where
             Dedicated(block) => block.size(),
             Linear(block) => block.size(),
             Dynamic(block) => block.size(),
+            Tlsf(block) => block.size(),
+            Custom(_, block) => block.size(),
             // Chunk(block) => block.size(),
         }
     }
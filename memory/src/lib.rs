@@ -16,6 +16,7 @@ mod block;
 mod heaps;
 mod mapping;
 mod memory;
+mod tracking;
 mod usage;
 mod util;
 mod utilization;
@@ -23,9 +24,10 @@ mod utilization;
 pub use crate::{
     allocator::*,
     block::Block,
-    heaps::{Heaps, HeapsConfig, HeapsError, MemoryBlock},
+    heaps::{HeapBudget, Heaps, HeapsConfig, HeapsError, MemoryBlock, SoftLimitCallback},
     mapping::{write::Write, Coherent, MappedRange, MaybeCoherent, NonCoherent},
     memory::Memory,
+    tracking::{json_string, AllocationReport, AllocationReportEntry, AllocationTag, Tagged},
     usage::*,
     utilization::*,
 };
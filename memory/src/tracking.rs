@@ -0,0 +1,344 @@
+//! Tracking of live allocations by debug tags.
+//! See `AllocationTag` and `AllocationReport`.
+
+use {
+    crate::{allocator::Kind, usage::MemoryUsage},
+    std::{borrow::Cow, collections::HashMap, io::Write},
+};
+
+/// Debug tag attached to allocation.
+/// Allocations are grouped by tags in `AllocationReport`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AllocationTag {
+    /// Name of the subsystem or resource.
+    pub name: Cow<'static, str>,
+
+    /// Source location where allocation was requested.
+    pub location: Option<(&'static str, u32)>,
+}
+
+impl AllocationTag {
+    /// Create tag with the name.
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        AllocationTag {
+            name: name.into(),
+            location: None,
+        }
+    }
+
+    /// Set source location of the tag.
+    pub fn with_location(mut self, file: &'static str, line: u32) -> Self {
+        self.location = Some((file, line));
+        self
+    }
+}
+
+impl std::fmt::Display for AllocationTag {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some((file, line)) => write!(fmt, "{} ({}:{})", self.name, file, line),
+            None => write!(fmt, "{}", self.name),
+        }
+    }
+}
+
+/// Create `AllocationTag` with the name and location of the macro invocation.
+#[macro_export]
+macro_rules! allocation_tag {
+    ($name:expr) => {
+        $crate::AllocationTag::new($name).with_location(file!(), line!())
+    };
+}
+
+/// Memory usage with debug tag attached.
+/// Allocations made with it are reported under the tag.
+#[derive(Clone, Debug)]
+pub struct Tagged<U> {
+    usage: U,
+    tag: AllocationTag,
+}
+
+impl<U> Tagged<U> {
+    /// Attach tag to the memory usage.
+    pub fn new(usage: U, tag: AllocationTag) -> Self {
+        Tagged { usage, tag }
+    }
+}
+
+impl<U> MemoryUsage for Tagged<U>
+where
+    U: MemoryUsage,
+{
+    fn properties_required(&self) -> gfx_hal::memory::Properties {
+        self.usage.properties_required()
+    }
+
+    fn memory_fitness(&self, properties: gfx_hal::memory::Properties) -> u32 {
+        self.usage.memory_fitness(properties)
+    }
+
+    fn allocator_fitness(&self, kind: Kind) -> u32 {
+        self.usage.allocator_fitness(kind)
+    }
+
+    fn tag(&self) -> Option<&AllocationTag> {
+        Some(&self.tag)
+    }
+}
+
+#[derive(Debug)]
+struct TrackedAllocation {
+    tag: Option<AllocationTag>,
+    kind: Kind,
+    memory_type: u32,
+    size: u64,
+}
+
+/// Live allocations of `Heaps`.
+#[derive(Debug, Default)]
+pub(crate) struct AllocationTracker {
+    live: slab::Slab<TrackedAllocation>,
+}
+
+impl AllocationTracker {
+    pub(crate) fn track(
+        &mut self,
+        tag: Option<AllocationTag>,
+        kind: Kind,
+        memory_type: u32,
+        size: u64,
+    ) -> usize {
+        self.live.insert(TrackedAllocation {
+            tag,
+            kind,
+            memory_type,
+            size,
+        })
+    }
+
+    pub(crate) fn untrack(&mut self, key: usize) {
+        self.live.remove(key);
+    }
+
+    pub(crate) fn tag(&self, key: usize) -> Option<&AllocationTag> {
+        self.live[key].tag.as_ref()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.live.is_empty()
+    }
+
+    pub(crate) fn report(&self) -> AllocationReport {
+        let mut groups = HashMap::<_, AllocationReportEntry>::new();
+        for (_, allocation) in self.live.iter() {
+            let entry = groups
+                .entry((
+                    allocation.tag.clone(),
+                    allocation.kind,
+                    allocation.memory_type,
+                ))
+                .or_insert_with(|| AllocationReportEntry {
+                    tag: allocation.tag.clone(),
+                    kind: allocation.kind,
+                    memory_type: allocation.memory_type,
+                    count: 0,
+                    size: 0,
+                    largest: 0,
+                });
+            entry.count += 1;
+            entry.size += allocation.size;
+            entry.largest = entry.largest.max(allocation.size);
+        }
+
+        let mut entries = groups
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.size
+                .cmp(&a.size)
+                .then_with(|| a.tag.cmp(&b.tag))
+                .then_with(|| a.kind.cmp(&b.kind))
+                .then_with(|| a.memory_type.cmp(&b.memory_type))
+        });
+        AllocationReport { entries }
+    }
+}
+
+/// Live allocations sharing tag, allocator kind and memory type.
+#[derive(Clone, Debug)]
+pub struct AllocationReportEntry {
+    /// Tag of allocations. `None` for untagged ones.
+    pub tag: Option<AllocationTag>,
+
+    /// Kind of allocator that served allocations.
+    pub kind: Kind,
+
+    /// Memory type of allocations.
+    pub memory_type: u32,
+
+    /// Number of allocations.
+    pub count: usize,
+
+    /// Total size of allocations.
+    pub size: u64,
+
+    /// Size of the largest allocation.
+    pub largest: u64,
+}
+
+/// Report of live allocations grouped by tag, allocator kind and memory type.
+/// Entries are sorted by total size, largest first.
+#[derive(Clone, Debug)]
+pub struct AllocationReport {
+    /// Report entries.
+    pub entries: Vec<AllocationReportEntry>,
+}
+
+impl AllocationReport {
+    /// Get total size of allocations in the report.
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Write report as JSON document.
+    pub fn export_json(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"total_size\": {},", self.total_size())?;
+        writeln!(out, "  \"entries\": [")?;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                writeln!(out, ",")?;
+            }
+            let (name, location) = match &entry.tag {
+                Some(tag) => (
+                    json_string(&tag.name),
+                    tag.location.map_or("null".to_owned(), |(file, line)| {
+                        format!("{{\"file\": {}, \"line\": {}}}", json_string(file), line)
+                    }),
+                ),
+                None => ("null".to_owned(), "null".to_owned()),
+            };
+            write!(
+                out,
+                "    {{\"tag\": {}, \"location\": {}, \"kind\": {}, \"memory_type\": {}, \
+                 \"count\": {}, \"size\": {}, \"largest\": {}}}",
+                name,
+                location,
+                json_string(&format!("{:?}", entry.kind)),
+                entry.memory_type,
+                entry.count,
+                entry.size,
+                entry.largest,
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "  ]")?;
+        writeln!(out, "}}")
+    }
+}
+
+impl std::fmt::Display for AllocationReport {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            fmt,
+            "Live allocations: {} bytes in {} groups",
+            self.total_size(),
+            self.entries.len()
+        )?;
+        for entry in &self.entries {
+            let tag = entry
+                .tag
+                .as_ref()
+                .map_or("<untagged>".to_owned(), ToString::to_string);
+            writeln!(
+                fmt,
+                "{:>12} bytes in {:>5} blocks (largest {:>10}) | {:?} type {} | {}",
+                entry.size, entry.count, entry.largest, entry.kind, entry.memory_type, tag,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Format string as JSON string literal.
+pub fn json_string(value: &str) -> String {
+    let mut string = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            c if c.is_control() => string.push_str(&format!("\\u{:04x}", c as u32)),
+            c => string.push(c),
+        }
+    }
+    string.push('"');
+    string
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_grouping() {
+        let textures = AllocationTag::new("textures");
+        let meshes = AllocationTag::new("meshes");
+
+        let mut tracker = AllocationTracker::default();
+        tracker.track(Some(textures.clone()), Kind::Dynamic, 0, 256);
+        tracker.track(Some(textures.clone()), Kind::Dynamic, 0, 1024);
+        let freed = tracker.track(Some(textures.clone()), Kind::Dynamic, 0, 4096);
+        tracker.track(Some(textures.clone()), Kind::Dedicated, 0, 2048);
+        tracker.track(Some(textures.clone()), Kind::Dynamic, 1, 512);
+        tracker.track(Some(meshes.clone()), Kind::Dynamic, 0, 64);
+        tracker.track(None, Kind::Dynamic, 0, 128);
+        tracker.track(None, Kind::Dynamic, 0, 128);
+        tracker.untrack(freed);
+
+        let report = tracker.report();
+        let groups = report
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.tag.as_ref().map(|tag| &*tag.name),
+                    entry.kind,
+                    entry.memory_type,
+                    entry.count,
+                    entry.size,
+                    entry.largest,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Grouped by tag, kind and memory type, biggest groups first.
+        assert_eq!(
+            groups,
+            vec![
+                (Some("textures"), Kind::Dedicated, 0, 1, 2048, 2048),
+                (Some("textures"), Kind::Dynamic, 0, 2, 1280, 1024),
+                (Some("textures"), Kind::Dynamic, 1, 1, 512, 512),
+                (None, Kind::Dynamic, 0, 2, 256, 128),
+                (Some("meshes"), Kind::Dynamic, 0, 1, 64, 64),
+            ]
+        );
+        assert_eq!(report.total_size(), 2048 + 1280 + 512 + 256 + 64);
+    }
+
+    #[test]
+    fn test_report_location() {
+        let mut tracker = AllocationTracker::default();
+        tracker.track(Some(AllocationTag::new("a")), Kind::Linear, 0, 16);
+        tracker.track(
+            Some(AllocationTag::new("a").with_location("a.rs", 1)),
+            Kind::Linear,
+            0,
+            16,
+        );
+
+        // Same name requested from different places is reported separately.
+        assert_eq!(tracker.report().entries.len(), 2);
+    }
+}
@@ -1,7 +1,7 @@
 //! Defines usage types for memory bocks.
 //! See `Usage` and implementations for details.
 
-use crate::allocator::Kind;
+use crate::{allocator::Kind, tracking::AllocationTag};
 
 /// Memory usage trait.
 pub trait MemoryUsage: std::fmt::Debug {
@@ -17,6 +17,12 @@ pub trait MemoryUsage: std::fmt::Debug {
 
     /// Get comparable fitness value for memory allocator.
     fn allocator_fitness(&self, kind: Kind) -> u32;
+
+    /// Get debug tag for allocations made with this usage.
+    /// See `Tagged`.
+    fn tag(&self) -> Option<&AllocationTag> {
+        None
+    }
 }
 
 impl<T> MemoryUsage for T
@@ -33,6 +39,9 @@ where
     fn allocator_fitness(&self, kind: Kind) -> u32 {
         (&**self).allocator_fitness(kind)
     }
+    fn tag(&self) -> Option<&AllocationTag> {
+        (&**self).tag()
+    }
 }
 
 /// Full speed GPU access.
@@ -60,6 +69,9 @@ impl MemoryUsage for Data {
             Kind::Dedicated => 1,
             Kind::Dynamic => 2,
             Kind::Linear => 0,
+            Kind::Tlsf => 3,
+            Kind::Custom(_) => 0,
+            Kind::Ring => 0,
         }
     }
 }
@@ -91,6 +103,9 @@ impl MemoryUsage for Dynamic {
             Kind::Dedicated => 1,
             Kind::Dynamic => 2,
             Kind::Linear => 0,
+            Kind::Tlsf => 3,
+            Kind::Custom(_) => 0,
+            Kind::Ring => 0,
         }
     }
 }
@@ -121,6 +136,9 @@ impl MemoryUsage for Upload {
             Kind::Dedicated => 0,
             Kind::Dynamic => 1,
             Kind::Linear => 2,
+            Kind::Tlsf => 1,
+            Kind::Custom(_) => 0,
+            Kind::Ring => 0,
         }
     }
 }
@@ -151,6 +169,9 @@ impl MemoryUsage for Download {
             Kind::Dedicated => 0,
             Kind::Dynamic => 1,
             Kind::Linear => 2,
+            Kind::Tlsf => 1,
+            Kind::Custom(_) => 0,
+            Kind::Ring => 0,
         }
     }
 }
@@ -10,7 +10,7 @@ use {
     rendy::{
         command::{DrawIndexedCommand, QueueId, RenderPassEncoder},
         factory::{Config, Factory},
-        graph::{render::*, GraphBuilder, GraphContext, NodeBuffer, NodeImage},
+        graph::{render::*, GraphBuilder, GraphContext, NodeBuffer, NodeBuildError, NodeImage},
         hal::{self, adapter::PhysicalDevice as _, device::Device as _},
         init::winit::{
             event::{Event, WindowEvent},
@@ -254,6 +254,7 @@ where
 
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         factory: &Factory<B>,
         _queue: QueueId,
         _set_layouts: &[Handle<DescriptorSetLayout<B>>],
@@ -359,6 +360,19 @@ where
         }
     }
 
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _scene: &Scene<B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
+    }
+
     fn dispose(self, _factory: &mut Factory<B>, _scene: &Scene<B>) {}
 }
 
@@ -4,20 +4,16 @@
 //!
 
 use rendy::{
-    command::{
-        CommandBuffer, CommandPool, Compute, DrawCommand, ExecutableState, Families, Family,
-        MultiShot, PendingState, QueueId, RenderPassEncoder, SimultaneousUse, Submit,
-    },
+    command::{Compute, DrawCommand, EncoderCommon, Families, QueueId, RenderPassEncoder},
     factory::{BufferState, Config, Factory},
-    frame::Frames,
     graph::{
-        gfx_acquire_barriers, gfx_release_barriers,
+        compute::{SimpleComputePipeline, SimpleComputePipelineDesc},
         render::{
             Layout, PrepareResult, RenderGroupBuilder, SimpleGraphicsPipeline,
             SimpleGraphicsPipelineDesc,
         },
-        BufferAccess, Graph, GraphBuilder, GraphContext, Node, NodeBuffer, NodeBuildError,
-        NodeDesc, NodeImage, NodeSubmittable,
+        BufferAccess, Graph, GraphBuilder, GraphContext, ImageSize, NodeBuffer, NodeBuildError,
+        NodeImage,
     },
     hal::{self, device::Device as _},
     init::winit::{
@@ -87,6 +83,9 @@ lazy_static::lazy_static! {
             .collect()
     };
 
+    static ref BOUNCE_SHADERS: rendy::shader::ShaderSetBuilder = rendy::shader::ShaderSetBuilder::default()
+        .with_compute(&*BOUNCE_COMPUTE).unwrap();
+
     static ref SHADERS: rendy::shader::ShaderSetBuilder = rendy::shader::ShaderSetBuilder::default()
         .with_vertex(&*RENDER_VERTEX).unwrap()
         .with_fragment(&*RENDER_FRAGMENT).unwrap();
@@ -296,6 +295,7 @@ where
 
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         _factory: &Factory<B>,
         _queue: QueueId,
         _sets: &[Handle<DescriptorSetLayout<B>>],
@@ -329,67 +329,36 @@ where
         }
     }
 
-    fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
-}
-
-#[derive(Debug)]
-struct GravBounce<B: hal::Backend> {
-    set_layout: Handle<DescriptorSetLayout<B>>,
-    pipeline_layout: B::PipelineLayout,
-    pipeline: B::ComputePipeline,
-
-    descriptor_set: Escape<DescriptorSet<B>>,
-
-    command_pool: CommandPool<B, Compute>,
-    command_buffer:
-        CommandBuffer<B, Compute, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
-    submit: Submit<B, SimultaneousUse>,
-}
-
-impl<'a, B> NodeSubmittable<'a, B> for GravBounce<B>
-where
-    B: hal::Backend,
-{
-    type Submittable = &'a Submit<B, SimultaneousUse>;
-    type Submittables = &'a [Submit<B, SimultaneousUse>];
-}
-
-impl<B, T> Node<B, T> for GravBounce<B>
-where
-    B: hal::Backend,
-    T: ?Sized,
-{
-    type Capability = Compute;
-
-    fn run<'a>(
-        &'a mut self,
+    fn resize(
+        &mut self,
         _ctx: &GraphContext<B>,
-        _factory: &Factory<B>,
+        _factory: &mut Factory<B>,
         _aux: &T,
-        _frames: &'a Frames<B>,
-    ) -> &'a [Submit<B, SimultaneousUse>] {
-        std::slice::from_ref(&self.submit)
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
     }
 
-    unsafe fn dispose(mut self, factory: &mut Factory<B>, _aux: &T) {
-        drop(self.submit);
-        self.command_pool
-            .free_buffers(Some(self.command_buffer.mark_complete()));
-        factory.destroy_command_pool(self.command_pool);
-        factory.destroy_compute_pipeline(self.pipeline);
-        factory.destroy_pipeline_layout(self.pipeline_layout);
-    }
+    fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
 }
 
 #[derive(Debug, Default)]
 struct GravBounceDesc;
 
-impl<B, T> NodeDesc<B, T> for GravBounceDesc
+#[derive(Debug)]
+struct GravBounce<B: hal::Backend> {
+    descriptor_set: Escape<DescriptorSet<B>>,
+}
+
+impl<B, T> SimpleComputePipelineDesc<B, T> for GravBounceDesc
 where
     B: hal::Backend,
     T: ?Sized,
 {
-    type Node = GravBounce<B>;
+    type Pipeline = GravBounce<B>;
 
     fn buffers(&self) -> Vec<BufferAccess> {
         vec![BufferAccess {
@@ -399,16 +368,36 @@ where
         }]
     }
 
+    fn shaders(&self) -> &rendy::shader::ShaderSetBuilder {
+        &BOUNCE_SHADERS
+    }
+
+    #[cfg(not(feature = "spirv-reflection"))]
+    fn layout(&self) -> Result<Layout, hal::pso::CreationError> {
+        Ok(Layout {
+            sets: vec![rendy::graph::compute::SetLayout {
+                bindings: vec![hal::pso::DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: hal::pso::DescriptorType::StorageBuffer,
+                    count: 1,
+                    stage_flags: hal::pso::ShaderStageFlags::COMPUTE,
+                    immutable_samplers: false,
+                }],
+            }],
+            push_constants: Vec::new(),
+        })
+    }
+
     fn build<'a>(
         self,
         ctx: &GraphContext<B>,
         factory: &mut Factory<B>,
-        family: &mut Family<B>,
-        queue: usize,
+        queue: QueueId,
         _aux: &T,
         buffers: Vec<NodeBuffer>,
         images: Vec<NodeImage>,
-    ) -> Result<Self::Node, NodeBuildError> {
+        set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<GravBounce<B>, NodeBuildError> {
         assert!(images.is_empty());
         assert_eq!(buffers.len(), 1);
 
@@ -422,10 +411,7 @@ where
                     &POSVEL_DATA,
                     None,
                     BufferState {
-                        queue: QueueId {
-                            index: queue,
-                            family: family.id(),
-                        },
+                        queue,
                         stage: hal::pso::PipelineStage::COMPUTE_SHADER,
                         access: hal::buffer::Access::SHADER_WRITE
                             | hal::buffer::Access::SHADER_READ,
@@ -434,56 +420,8 @@ where
                 .map_err(NodeBuildError::Upload)
         }?;
 
-        log::trace!("Load shader module BOUNCE_COMPUTE");
-        let module = unsafe { BOUNCE_COMPUTE.module(factory) }
-            .map_err(rendy_core::hal::pso::CreationError::Shader)
-            .map_err(NodeBuildError::Pipeline)?;
-
-        let set_layout = Handle::from(
-            factory
-                .create_descriptor_set_layout(vec![hal::pso::DescriptorSetLayoutBinding {
-                    binding: 0,
-                    ty: hal::pso::DescriptorType::StorageBuffer,
-                    count: 1,
-                    stage_flags: hal::pso::ShaderStageFlags::COMPUTE,
-                    immutable_samplers: false,
-                }])
-                .map_err(NodeBuildError::OutOfMemory)?,
-        );
-
-        let pipeline_layout = unsafe {
-            factory
-                .device()
-                .create_pipeline_layout(
-                    std::iter::once(set_layout.raw()),
-                    std::iter::empty::<(hal::pso::ShaderStageFlags, std::ops::Range<u32>)>(),
-                )
-                .map_err(NodeBuildError::OutOfMemory)?
-        };
-
-        let pipeline = unsafe {
-            factory
-                .device()
-                .create_compute_pipeline(
-                    &hal::pso::ComputePipelineDesc {
-                        shader: hal::pso::EntryPoint {
-                            entry: "main",
-                            module: &module,
-                            specialization: hal::pso::Specialization::default(),
-                        },
-                        layout: &pipeline_layout,
-                        flags: hal::pso::PipelineCreationFlags::empty(),
-                        parent: hal::pso::BasePipeline::None,
-                    },
-                    None,
-                )
-                .map_err(NodeBuildError::Pipeline)?
-        };
-
-        unsafe { factory.destroy_shader_module(module) };
-
         let descriptor_set = factory
-            .create_descriptor_set(set_layout.clone())
+            .create_descriptor_set(set_layouts[0].clone())
             .map_err(NodeBuildError::OutOfMemory)?;
 
         unsafe {
@@ -500,50 +438,61 @@ where
                 }));
         }
 
-        let mut command_pool = factory
-            .create_command_pool(family)
-            .map_err(NodeBuildError::OutOfMemory)?
-            .with_capability::<Compute>()
-            .expect("Graph builder must provide family with Compute capability");
-        let initial = command_pool.allocate_buffers(1).remove(0);
-        let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
-        let mut encoder = recording.encoder();
-        encoder.bind_compute_pipeline(&pipeline);
+        Ok(GravBounce { descriptor_set })
+    }
+}
+
+impl<B, T> SimpleComputePipeline<B, T> for GravBounce<B>
+where
+    B: hal::Backend,
+    T: ?Sized,
+{
+    type Desc = GravBounceDesc;
+
+    fn prepare(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _sets: &[Handle<DescriptorSetLayout<B>>],
+        _index: usize,
+        _aux: &T,
+    ) -> PrepareResult {
+        PrepareResult::DrawReuse
+    }
+
+    fn dispatch(
+        &mut self,
+        layout: &B::PipelineLayout,
+        mut encoder: EncoderCommon<'_, B, Compute>,
+        _index: usize,
+        _aux: &T,
+    ) {
         unsafe {
             encoder.bind_compute_descriptor_sets(
-                &pipeline_layout,
+                layout,
                 0,
-                std::iter::once(descriptor_set.raw()),
+                std::iter::once(self.descriptor_set.raw()),
                 std::iter::empty::<u32>(),
             );
-
-            {
-                let (stages, barriers) = gfx_acquire_barriers(ctx, &*buffers, None);
-                log::info!("Acquire {:?} : {:#?}", stages, barriers);
-                encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
-            }
             encoder.dispatch(QUADS, 1, 1);
-
-            {
-                let (stages, barriers) = gfx_release_barriers(ctx, &*buffers, None);
-                log::info!("Release {:?} : {:#?}", stages, barriers);
-                encoder.pipeline_barrier(stages, hal::memory::Dependencies::empty(), barriers);
-            }
         }
+    }
 
-        let (submit, command_buffer) = recording.finish().submit();
-
-        Ok(GravBounce {
-            set_layout,
-            pipeline_layout,
-            pipeline,
-            descriptor_set,
-            // buffer_view,
-            command_pool,
-            command_buffer,
-            submit,
-        })
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
     }
+
+    fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
 }
 
 fn build_graph<B: hal::Backend>(
@@ -552,15 +501,17 @@ fn build_graph<B: hal::Backend>(
     surface: rendy::wsi::Surface<B>,
     window: &Window,
 ) -> Graph<B, ()> {
-    let mut graph_builder = GraphBuilder::<B, ()>::new();
+    let size = window.inner_size().to_physical(window.hidpi_factor());
+    let mut graph_builder =
+        GraphBuilder::<B, ()>::new().with_surface_extent(hal::window::Extent2D {
+            width: size.width as _,
+            height: size.height as _,
+        });
 
     let posvel = graph_builder.create_buffer(QUADS as u64 * std::mem::size_of::<[f32; 4]>() as u64);
 
-    let size = window.inner_size().to_physical(window.hidpi_factor());
-    let window_kind = hal::image::Kind::D2(size.width as u32, size.height as u32, 1, 1);
-
-    let depth = graph_builder.create_image(
-        window_kind,
+    let depth = graph_builder.create_sized_image(
+        ImageSize::full_surface(),
         1,
         hal::format::Format::D32Sfloat,
         Some(hal::command::ClearValue {
@@ -571,7 +522,7 @@ fn build_graph<B: hal::Backend>(
         }),
     );
 
-    let grav = graph_builder.add_node(GravBounceDesc.builder().with_buffer(posvel));
+    let grav = graph_builder.add_node(GravBounce::builder().with_buffer(posvel));
 
     graph_builder.add_node(
         QuadsRenderPipeline::builder()
@@ -623,11 +574,22 @@ fn main() {
                 match event {
                     Event::WindowEvent { event, .. } => match event {
                         WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(_dims) => {
-                            let started = std::time::Instant::now();
-                            graph.take().unwrap().dispose(&mut factory, &());
-                            log::trace!("Graph disposed in: {:?}", started.elapsed());
-                            return;
+                        WindowEvent::Resized(dims) => {
+                            let size = dims.to_physical(window.hidpi_factor());
+                            if let Some(ref mut graph) = graph {
+                                let started = std::time::Instant::now();
+                                graph
+                                    .resize(
+                                        &mut factory,
+                                        hal::window::Extent2D {
+                                            width: size.width as _,
+                                            height: size.height as _,
+                                        },
+                                        &(),
+                                    )
+                                    .unwrap();
+                                log::trace!("Graph resized in: {:?}", started.elapsed());
+                            }
                         }
                         _ => {}
                     },
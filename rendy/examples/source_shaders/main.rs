@@ -8,7 +8,8 @@ use rendy::{
     command::{Families, QueueId, RenderPassEncoder},
     factory::{Config, Factory},
     graph::{
-        present::PresentNode, render::*, Graph, GraphBuilder, GraphContext, NodeBuffer, NodeImage,
+        present::PresentNode, render::*, Graph, GraphBuilder, GraphContext, NodeBuffer,
+        NodeBuildError, NodeImage,
     },
     hal,
     init::winit::{
@@ -147,6 +148,7 @@ where
 
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         factory: &Factory<B>,
         _queue: QueueId,
         _set_layouts: &[Handle<DescriptorSetLayout<B>>],
@@ -214,6 +216,19 @@ where
         }
     }
 
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
+    }
+
     fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
 }
 
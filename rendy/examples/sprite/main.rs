@@ -7,9 +7,10 @@ use rendy::{
     command::{Families, QueueId, RenderPassEncoder},
     factory::{Config, Factory, ImageState},
     graph::{
-        present::PresentNode, render::*, Graph, GraphBuilder, GraphContext, NodeBuffer, NodeImage,
+        present::PresentNode, render::*, Graph, GraphBuilder, GraphContext, NodeBuffer,
+        NodeBuildError, NodeImage,
     },
-    hal::{self, device::Device as _},
+    hal,
     init::winit::{
         event::{Event, WindowEvent},
         event_loop::{ControlFlow, EventLoop},
@@ -178,28 +179,16 @@ where
             )
             .unwrap();
 
-        let descriptor_set = factory
+        let mut descriptor_set = factory
             .create_descriptor_set(set_layouts[0].clone())
             .unwrap();
 
         unsafe {
-            factory.device().write_descriptor_sets(vec![
-                hal::pso::DescriptorSetWrite {
-                    set: descriptor_set.raw(),
-                    binding: 0,
-                    array_offset: 0,
-                    descriptors: vec![hal::pso::Descriptor::Image(
-                        texture.view().raw(),
-                        hal::image::Layout::ShaderReadOnlyOptimal,
-                    )],
-                },
-                hal::pso::DescriptorSetWrite {
-                    set: descriptor_set.raw(),
-                    binding: 1,
-                    array_offset: 0,
-                    descriptors: vec![hal::pso::Descriptor::Sampler(texture.sampler().raw())],
-                },
-            ]);
+            descriptor_set
+                .write()
+                .texture(0, 0, &texture, hal::image::Layout::ShaderReadOnlyOptimal)
+                .sampler(1, 0, texture.sampler().clone())
+                .submit(factory.device());
         }
 
         #[cfg(feature = "spirv-reflection")]
@@ -271,6 +260,7 @@ where
 
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         _factory: &Factory<B>,
         _queue: QueueId,
         _set_layouts: &[Handle<DescriptorSetLayout<B>>],
@@ -299,6 +289,19 @@ where
         }
     }
 
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
+    }
+
     fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
 }
 
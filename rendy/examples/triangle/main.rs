@@ -7,7 +7,7 @@
 use rendy::{
     command::{Families, QueueId, RenderPassEncoder},
     factory::{Config, Factory},
-    graph::{render::*, Graph, GraphBuilder, GraphContext, NodeBuffer, NodeImage},
+    graph::{render::*, Graph, GraphBuilder, GraphContext, NodeBuffer, NodeBuildError, NodeImage},
     hal::{self, Backend},
     init::winit::{
         event::{Event, WindowEvent},
@@ -121,6 +121,7 @@ where
 
     fn prepare(
         &mut self,
+        _ctx: &GraphContext<B>,
         factory: &Factory<B>,
         _queue: QueueId,
         _set_layouts: &[Handle<DescriptorSetLayout<B>>],
@@ -188,6 +189,19 @@ where
         }
     }
 
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<(), NodeBuildError> {
+        // No graph images are referenced.
+        Ok(())
+    }
+
     fn dispose(self, _factory: &mut Factory<B>, _aux: &T) {}
 }
 
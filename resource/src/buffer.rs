@@ -9,7 +9,7 @@ use {
         CreationError,
     },
     relevant::Relevant,
-    rendy_core::hal::{device::Device as _, Backend},
+    rendy_core::hal::{device::Device as _, memory::Requirements, Backend},
 };
 
 /// Buffer info.
@@ -31,7 +31,7 @@ pub struct BufferInfo {
 pub struct Buffer<B: Backend> {
     device: DeviceId,
     raw: B::Buffer,
-    block: MemoryBlock<B>,
+    block: Option<MemoryBlock<B>>,
     info: BufferInfo,
     relevant: Relevant,
 }
@@ -83,18 +83,72 @@ where
         Ok(Buffer {
             device: device.id(),
             raw: buf,
-            block,
+            block: Some(block),
+            info,
+            relevant: Relevant,
+        })
+    }
+
+    /// Create buffer without binding any memory to it.
+    /// Returns raw buffer and its memory requirements.
+    ///
+    /// Raw buffer must be either bound with [`bind_aliased`]
+    /// or destroyed.
+    ///
+    /// [`bind_aliased`]: #method.bind_aliased
+    pub unsafe fn create_unbound(
+        device: &Device<B>,
+        info: BufferInfo,
+    ) -> Result<(B::Buffer, Requirements), BufferCreationError> {
+        log::trace!("{:#?}@unbound", info);
+        assert_ne!(info.size, 0);
+
+        let buf = device
+            .create_buffer(info.size, info.usage)
+            .map_err(CreationError::Create)?;
+        let reqs = device.get_buffer_requirements(&buf);
+        Ok((buf, reqs))
+    }
+
+    /// Bind raw buffer created by [`create_unbound`] to the memory region that is owned elsewhere.
+    /// Multiple resources may be bound to overlapping regions
+    /// as long as they are never used at the same time.
+    /// Raw buffer is destroyed if binding fails.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must be allocated from this `Device`,
+    /// satisfy buffer requirements at `offset`
+    /// and stay allocated until buffer is disposed.
+    ///
+    /// [`create_unbound`]: #method.create_unbound
+    pub unsafe fn bind_aliased(
+        device: &Device<B>,
+        info: BufferInfo,
+        mut raw: B::Buffer,
+        memory: &B::Memory,
+        offset: u64,
+    ) -> Result<Self, BufferCreationError> {
+        if let Err(err) = device.bind_buffer_memory(memory, offset, &mut raw) {
+            device.destroy_buffer(raw);
+            return Err(CreationError::Bind(err));
+        }
+
+        Ok(Buffer {
+            device: device.id(),
+            raw,
+            block: None,
             info,
             relevant: Relevant,
         })
     }
 
     /// Dispose of buffer resource.
-    /// Deallocate memory block.
+    /// Deallocate memory block if buffer owns one.
     pub unsafe fn dispose(self, device: &Device<B>, heaps: &mut Heaps<B>) {
         self.assert_device_owner(device);
         device.destroy_buffer(self.raw);
-        heaps.free(device, self.block);
+        self.block.map(|block| heaps.free(device, block));
         self.relevant.dispose();
     }
 
@@ -109,13 +163,15 @@ where
     }
 
     /// Get reference to memory block occupied by buffer.
-    pub fn block(&self) -> &MemoryBlock<B> {
-        &self.block
+    /// Returns `None` for buffers bound to memory they don't own.
+    pub fn block(&self) -> Option<&MemoryBlock<B>> {
+        self.block.as_ref()
     }
 
     /// Get mutable reference to memory block occupied by buffer.
-    pub unsafe fn block_mut(&mut self) -> &mut MemoryBlock<B> {
-        &mut self.block
+    /// Returns `None` for buffers bound to memory they don't own.
+    pub unsafe fn block_mut(&mut self) -> Option<&mut MemoryBlock<B>> {
+        self.block.as_mut()
     }
 
     /// Get buffer info.
@@ -129,9 +185,11 @@ where
     /// [`map`]: #method.map
     /// [`InvalidAccess`]: https://docs.rs/gfx-hal/0.1/rendy_core::hal/mapping/enum.Error.html#InvalidAccess
     pub fn visible(&self) -> bool {
-        self.block
-            .properties()
-            .contains(rendy_core::hal::memory::Properties::CPU_VISIBLE)
+        self.block.as_ref().map_or(false, |block| {
+            block
+                .properties()
+                .contains(rendy_core::hal::memory::Properties::CPU_VISIBLE)
+        })
     }
 
     /// Map range of the buffer to the CPU accessible memory.
@@ -140,7 +198,10 @@ where
         device: &Device<B>,
         range: std::ops::Range<u64>,
     ) -> Result<MappedRange<'a, B>, rendy_core::hal::device::MapError> {
-        self.block.map(device, range)
+        match &mut self.block {
+            Some(block) => block.map(device, range),
+            None => Err(rendy_core::hal::device::MapError::MappingFailed),
+        }
     }
 
     /// Get buffer info.
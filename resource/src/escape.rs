@@ -158,6 +158,13 @@ pub struct Handle<T> {
     inner: Arc<Escape<T>>,
 }
 
+impl<T> Handle<T> {
+    /// Get mutable reference to the value if this is the only handle to it.
+    pub fn get_mut(handle: &mut Self) -> Option<&mut T> {
+        Arc::get_mut(&mut handle.inner).map(|escape| &mut **escape)
+    }
+}
+
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Self {
         Handle {
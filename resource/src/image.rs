@@ -10,7 +10,7 @@ use {
         CreationError,
     },
     relevant::Relevant,
-    rendy_core::hal::{device::Device as _, format, Backend},
+    rendy_core::hal::{device::Device as _, format, memory::Requirements, Backend},
 };
 
 /// Image info.
@@ -116,6 +116,74 @@ where
         })
     }
 
+    /// Create image without binding any memory to it.
+    /// Returns raw image and its memory requirements.
+    ///
+    /// Raw image must be either bound with [`bind_aliased`]
+    /// or destroyed.
+    ///
+    /// [`bind_aliased`]: #method.bind_aliased
+    pub unsafe fn create_unbound(
+        device: &Device<B>,
+        info: ImageInfo,
+    ) -> Result<(B::Image, Requirements), ImageCreationError> {
+        assert!(
+            info.levels <= info.kind.num_levels(),
+            "Number of mip leves ({}) cannot be greater than {} for given kind {:?}",
+            info.levels,
+            info.kind.num_levels(),
+            info.kind,
+        );
+
+        log::trace!("{:#?}@unbound", info);
+
+        let img = device
+            .create_image(
+                info.kind,
+                info.levels,
+                info.format,
+                info.tiling,
+                info.usage,
+                info.view_caps,
+            )
+            .map_err(CreationError::Create)?;
+        let reqs = device.get_image_requirements(&img);
+        Ok((img, reqs))
+    }
+
+    /// Bind raw image created by [`create_unbound`] to the memory region that is owned elsewhere.
+    /// Multiple resources may be bound to overlapping regions
+    /// as long as they are never used at the same time.
+    /// Raw image is destroyed if binding fails.
+    ///
+    /// # Safety
+    ///
+    /// `memory` must be allocated from this `Device`,
+    /// satisfy image requirements at `offset`
+    /// and stay allocated until image is disposed.
+    ///
+    /// [`create_unbound`]: #method.create_unbound
+    pub unsafe fn bind_aliased(
+        device: &Device<B>,
+        info: ImageInfo,
+        mut raw: B::Image,
+        memory: &B::Memory,
+        offset: u64,
+    ) -> Result<Self, ImageCreationError> {
+        if let Err(err) = device.bind_image_memory(memory, offset, &mut raw) {
+            device.destroy_image(raw);
+            return Err(CreationError::Bind(err));
+        }
+
+        Ok(Image {
+            device: device.id(),
+            raw,
+            block: None,
+            info,
+            relevant: Relevant,
+        })
+    }
+
     /// Create image handler for swapchain image.
     pub unsafe fn create_from_swapchain(device: DeviceId, info: ImageInfo, raw: B::Image) -> Self {
         Image {
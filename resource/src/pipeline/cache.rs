@@ -0,0 +1,148 @@
+//! A cache to store and retrieve pipeline layouts
+
+use {
+    super::PipelineLayout,
+    crate::{escape::Handle, set::DescriptorSetLayout},
+    rendy_core::hal::{device::OutOfMemory, pso::ShaderStageFlags, Backend},
+    std::{
+        collections::hash_map::{Entry, HashMap},
+        ops::{Deref, DerefMut, Range},
+    },
+};
+
+/// Descriptor set layouts are identified by address.
+/// Cached pipeline layouts keep them alive, so addresses are not reused.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct LayoutKey {
+    sets: Vec<usize>,
+    push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+}
+
+impl LayoutKey {
+    fn new<T>(sets: &[Handle<T>], push_constants: &[(ShaderStageFlags, Range<u32>)]) -> Self {
+        LayoutKey {
+            sets: sets.iter().map(|set| &**set as *const T as usize).collect(),
+            push_constants: push_constants.to_vec(),
+        }
+    }
+}
+
+/// Pipeline layout cache holds handlers to created layouts.
+#[derive(Debug)]
+pub struct PipelineLayoutCache<B: Backend> {
+    layouts: HashMap<LayoutKey, Handle<PipelineLayout<B>>>,
+}
+
+impl<B> Default for PipelineLayoutCache<B>
+where
+    B: Backend,
+{
+    fn default() -> Self {
+        PipelineLayoutCache {
+            layouts: HashMap::default(),
+        }
+    }
+}
+
+impl<B> PipelineLayoutCache<B>
+where
+    B: Backend,
+{
+    /// Get pipeline layout with specified descriptor set layouts and push constants.
+    /// Create new one using closure provided.
+    pub fn get(
+        &mut self,
+        sets: &[Handle<DescriptorSetLayout<B>>],
+        push_constants: &[(ShaderStageFlags, Range<u32>)],
+        create: impl FnOnce() -> Result<Handle<PipelineLayout<B>>, OutOfMemory>,
+    ) -> Result<Handle<PipelineLayout<B>>, OutOfMemory> {
+        let key = LayoutKey::new(sets, push_constants);
+        Ok(match self.layouts.entry(key) {
+            Entry::Occupied(occupied) => occupied.get().clone(),
+            Entry::Vacant(vacant) => {
+                let layout = create()?;
+                vacant.insert(layout).clone()
+            }
+        })
+    }
+
+    /// Get pipeline layout with specified descriptor set layouts and push constants.
+    /// Create new one using closure provided.
+    /// Does not lock for writing if layout exists.
+    pub fn get_with_upgradable_lock<R, W, U>(
+        read: R,
+        upgrade: U,
+        sets: &[Handle<DescriptorSetLayout<B>>],
+        push_constants: &[(ShaderStageFlags, Range<u32>)],
+        create: impl FnOnce() -> Result<Handle<PipelineLayout<B>>, OutOfMemory>,
+    ) -> Result<Handle<PipelineLayout<B>>, OutOfMemory>
+    where
+        R: Deref<Target = Self>,
+        W: DerefMut<Target = Self>,
+        U: FnOnce(R) -> W,
+    {
+        let key = LayoutKey::new(sets, push_constants);
+        if let Some(layout) = read.layouts.get(&key) {
+            return Ok(layout.clone());
+        }
+        let layout = create()?;
+        {
+            upgrade(read).layouts.insert(key, layout.clone());
+        }
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::escape::Terminal};
+
+    #[test]
+    fn test_layout_key() {
+        let mut terminal = Terminal::new();
+        let first: Handle<u32> = terminal.escape(0).into();
+        let second: Handle<u32> = terminal.escape(0).into();
+        let vertex = (ShaderStageFlags::VERTEX, 0..16);
+        let fragment = (ShaderStageFlags::FRAGMENT, 16..32);
+
+        let key = LayoutKey::new(&[first.clone(), second.clone()], &[vertex.clone()]);
+
+        // Clones of handles refer to the same layout.
+        assert_eq!(
+            key,
+            LayoutKey::new(&[first.clone(), second.clone()], &[vertex.clone()])
+        );
+
+        // Equal layouts created separately are distinct.
+        assert_ne!(
+            key,
+            LayoutKey::new(&[first.clone(), first.clone()], &[vertex.clone()])
+        );
+
+        // Order of sets matters.
+        assert_ne!(
+            key,
+            LayoutKey::new(&[second.clone(), first.clone()], &[vertex.clone()])
+        );
+
+        // Push constants are part of the key.
+        assert_ne!(key, LayoutKey::new(&[first.clone(), second.clone()], &[]));
+        assert_ne!(
+            key,
+            LayoutKey::new(
+                &[first.clone(), second.clone()],
+                &[vertex.clone(), fragment]
+            )
+        );
+        assert_ne!(
+            key,
+            LayoutKey::new(
+                &[first.clone(), second.clone()],
+                &[(ShaderStageFlags::VERTEX, 0..8)]
+            )
+        );
+
+        drop((first, second));
+        assert_eq!(terminal.drain().count(), 2);
+    }
+}
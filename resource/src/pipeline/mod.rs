@@ -0,0 +1,298 @@
+//! Pipeline layout, pipeline and pipeline cache wrappers.
+
+mod cache;
+
+use {
+    crate::{
+        core::{device_owned, Device, DeviceId},
+        escape::Handle,
+        set::DescriptorSetLayout,
+    },
+    relevant::Relevant,
+    rendy_core::hal::{
+        device::{Device as _, OutOfMemory},
+        pso::{ComputePipelineDesc, CreationError, GraphicsPipelineDesc, ShaderStageFlags},
+        Backend,
+    },
+    std::ops::Range,
+};
+
+pub use crate::pipeline::cache::PipelineLayoutCache;
+
+/// Pipeline layout info.
+#[derive(Clone, Debug)]
+pub struct PipelineLayoutInfo<B: Backend> {
+    /// Layouts of descriptor sets.
+    pub sets: Vec<Handle<DescriptorSetLayout<B>>>,
+
+    /// Push constant ranges.
+    pub push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+}
+
+/// Generic pipeline layout resource wrapper.
+/// Keeps descriptor set layouts it was created with alive.
+#[derive(Debug)]
+pub struct PipelineLayout<B: Backend> {
+    device: DeviceId,
+    raw: B::PipelineLayout,
+    info: PipelineLayoutInfo<B>,
+    relevant: Relevant,
+}
+
+device_owned!(PipelineLayout<B>);
+
+impl<B> PipelineLayout<B>
+where
+    B: Backend,
+{
+    /// Create new pipeline layout.
+    ///
+    /// # Safety
+    ///
+    /// Descriptor set layouts must belong to the `device`.
+    pub unsafe fn create(
+        device: &Device<B>,
+        info: PipelineLayoutInfo<B>,
+    ) -> Result<Self, OutOfMemory> {
+        let raw = device.create_pipeline_layout(
+            info.sets.iter().map(|set| set.raw()),
+            info.push_constants.iter(),
+        )?;
+
+        Ok(PipelineLayout {
+            device: device.id(),
+            raw,
+            info,
+            relevant: Relevant,
+        })
+    }
+
+    /// Destroy pipeline layout resource.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        device.destroy_pipeline_layout(self.raw);
+        self.relevant.dispose();
+    }
+
+    /// Get reference to raw pipeline layout resource.
+    pub fn raw(&self) -> &B::PipelineLayout {
+        &self.raw
+    }
+
+    /// Get mutable reference to raw pipeline layout resource.
+    pub unsafe fn raw_mut(&mut self) -> &mut B::PipelineLayout {
+        &mut self.raw
+    }
+
+    /// Get pipeline layout info.
+    pub fn info(&self) -> &PipelineLayoutInfo<B> {
+        &self.info
+    }
+}
+
+/// Generic graphics pipeline resource wrapper.
+#[derive(Debug)]
+pub struct GraphicsPipeline<B: Backend> {
+    device: DeviceId,
+    raw: B::GraphicsPipeline,
+    layout: Handle<PipelineLayout<B>>,
+    relevant: Relevant,
+}
+
+device_owned!(GraphicsPipeline<B>);
+
+impl<B> GraphicsPipeline<B>
+where
+    B: Backend,
+{
+    /// Create new graphics pipeline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `desc.layout` is not the raw layout of `layout`.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` and `cache` must belong to the `device`.
+    pub unsafe fn create(
+        device: &Device<B>,
+        desc: &GraphicsPipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+        cache: Option<&PipelineCache<B>>,
+    ) -> Result<Self, CreationError> {
+        assert!(
+            std::ptr::eq(desc.layout, layout.raw()),
+            "Pipeline must be created with the raw layout of the handle"
+        );
+
+        let raw = device.create_graphics_pipeline(desc, cache.map(PipelineCache::raw))?;
+
+        Ok(GraphicsPipeline {
+            device: device.id(),
+            raw,
+            layout,
+            relevant: Relevant,
+        })
+    }
+
+    /// Destroy graphics pipeline resource.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        device.destroy_graphics_pipeline(self.raw);
+        self.relevant.dispose();
+    }
+
+    /// Get reference to raw graphics pipeline resource.
+    pub fn raw(&self) -> &B::GraphicsPipeline {
+        &self.raw
+    }
+
+    /// Get mutable reference to raw graphics pipeline resource.
+    pub unsafe fn raw_mut(&mut self) -> &mut B::GraphicsPipeline {
+        &mut self.raw
+    }
+
+    /// Get layout of the pipeline.
+    pub fn layout(&self) -> &Handle<PipelineLayout<B>> {
+        &self.layout
+    }
+}
+
+/// Generic compute pipeline resource wrapper.
+#[derive(Debug)]
+pub struct ComputePipeline<B: Backend> {
+    device: DeviceId,
+    raw: B::ComputePipeline,
+    layout: Handle<PipelineLayout<B>>,
+    relevant: Relevant,
+}
+
+device_owned!(ComputePipeline<B>);
+
+impl<B> ComputePipeline<B>
+where
+    B: Backend,
+{
+    /// Create new compute pipeline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `desc.layout` is not the raw layout of `layout`.
+    ///
+    /// # Safety
+    ///
+    /// Objects referenced by `desc` and `cache` must belong to the `device`.
+    pub unsafe fn create(
+        device: &Device<B>,
+        desc: &ComputePipelineDesc<'_, B>,
+        layout: Handle<PipelineLayout<B>>,
+        cache: Option<&PipelineCache<B>>,
+    ) -> Result<Self, CreationError> {
+        assert!(
+            std::ptr::eq(desc.layout, layout.raw()),
+            "Pipeline must be created with the raw layout of the handle"
+        );
+
+        let raw = device.create_compute_pipeline(desc, cache.map(PipelineCache::raw))?;
+
+        Ok(ComputePipeline {
+            device: device.id(),
+            raw,
+            layout,
+            relevant: Relevant,
+        })
+    }
+
+    /// Destroy compute pipeline resource.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        device.destroy_compute_pipeline(self.raw);
+        self.relevant.dispose();
+    }
+
+    /// Get reference to raw compute pipeline resource.
+    pub fn raw(&self) -> &B::ComputePipeline {
+        &self.raw
+    }
+
+    /// Get mutable reference to raw compute pipeline resource.
+    pub unsafe fn raw_mut(&mut self) -> &mut B::ComputePipeline {
+        &mut self.raw
+    }
+
+    /// Get layout of the pipeline.
+    pub fn layout(&self) -> &Handle<PipelineLayout<B>> {
+        &self.layout
+    }
+}
+
+/// Generic pipeline cache resource wrapper.
+///
+/// Content of the cache can be retrieved with `data`
+/// and passed to `create` later, for example after application restart.
+/// Data produced by another device or driver version is ignored.
+#[derive(Debug)]
+pub struct PipelineCache<B: Backend> {
+    device: DeviceId,
+    raw: B::PipelineCache,
+    relevant: Relevant,
+}
+
+device_owned!(PipelineCache<B>);
+
+impl<B> PipelineCache<B>
+where
+    B: Backend,
+{
+    /// Create new pipeline cache with initial data.
+    pub fn create(device: &Device<B>, data: Option<&[u8]>) -> Result<Self, OutOfMemory> {
+        let raw = unsafe { device.create_pipeline_cache(data) }?;
+
+        Ok(PipelineCache {
+            device: device.id(),
+            raw,
+            relevant: Relevant,
+        })
+    }
+
+    /// Get content of the cache.
+    pub fn data(&self, device: &Device<B>) -> Result<Vec<u8>, OutOfMemory> {
+        self.assert_device_owner(device);
+        unsafe { device.get_pipeline_cache_data(&self.raw) }
+    }
+
+    /// Merge content of other caches into this one.
+    pub fn merge<'a>(
+        &mut self,
+        device: &Device<B>,
+        sources: impl IntoIterator<Item = &'a PipelineCache<B>>,
+    ) -> Result<(), OutOfMemory> {
+        self.assert_device_owner(device);
+        unsafe {
+            device.merge_pipeline_caches(
+                &self.raw,
+                sources.into_iter().map(|source| {
+                    source.assert_device_owner(device);
+                    &source.raw
+                }),
+            )
+        }
+    }
+
+    /// Destroy pipeline cache resource.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        device.destroy_pipeline_cache(self.raw);
+        self.relevant.dispose();
+    }
+
+    /// Get reference to raw pipeline cache resource.
+    pub fn raw(&self) -> &B::PipelineCache {
+        &self.raw
+    }
+
+    /// Get mutable reference to raw pipeline cache resource.
+    pub unsafe fn raw_mut(&mut self) -> &mut B::PipelineCache {
+        &mut self.raw
+    }
+}
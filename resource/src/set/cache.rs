@@ -0,0 +1,174 @@
+//! A cache to store and retrieve descriptor set layouts
+
+use {
+    super::DescriptorSetLayout,
+    crate::escape::Handle,
+    rendy_core::hal::{
+        device::OutOfMemory,
+        pso::{DescriptorSetLayoutBinding, DescriptorType, ShaderStageFlags},
+        Backend,
+    },
+    std::{
+        collections::hash_map::{Entry, HashMap},
+        ops::{Deref, DerefMut},
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct BindingKey {
+    binding: u32,
+    ty: DescriptorType,
+    count: usize,
+    stage_flags: ShaderStageFlags,
+    immutable_samplers: bool,
+}
+
+/// Bindings sorted by binding index, so that order of bindings doesn't matter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct LayoutKey(Vec<BindingKey>);
+
+impl LayoutKey {
+    fn new(bindings: &[DescriptorSetLayoutBinding]) -> Self {
+        let mut keys = bindings
+            .iter()
+            .map(|binding| BindingKey {
+                binding: binding.binding,
+                ty: binding.ty,
+                count: binding.count,
+                stage_flags: binding.stage_flags,
+                immutable_samplers: binding.immutable_samplers,
+            })
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.binding);
+        LayoutKey(keys)
+    }
+}
+
+/// Descriptor set layout cache holds handlers to created layouts.
+#[derive(Debug)]
+pub struct DescriptorSetLayoutCache<B: Backend> {
+    layouts: HashMap<LayoutKey, Handle<DescriptorSetLayout<B>>>,
+}
+
+impl<B> Default for DescriptorSetLayoutCache<B>
+where
+    B: Backend,
+{
+    fn default() -> Self {
+        DescriptorSetLayoutCache {
+            layouts: HashMap::default(),
+        }
+    }
+}
+
+impl<B> DescriptorSetLayoutCache<B>
+where
+    B: Backend,
+{
+    /// Get descriptor set layout with specified bindings.
+    /// Create new one using closure provided.
+    pub fn get(
+        &mut self,
+        bindings: &[DescriptorSetLayoutBinding],
+        create: impl FnOnce() -> Result<Handle<DescriptorSetLayout<B>>, OutOfMemory>,
+    ) -> Result<Handle<DescriptorSetLayout<B>>, OutOfMemory> {
+        Ok(match self.layouts.entry(LayoutKey::new(bindings)) {
+            Entry::Occupied(occupied) => occupied.get().clone(),
+            Entry::Vacant(vacant) => {
+                let layout = create()?;
+                vacant.insert(layout).clone()
+            }
+        })
+    }
+
+    /// Get descriptor set layout with specified bindings.
+    /// Create new one using closure provided.
+    /// Does not lock for writing if layout exists.
+    pub fn get_with_upgradable_lock<R, W, U>(
+        read: R,
+        upgrade: U,
+        bindings: &[DescriptorSetLayoutBinding],
+        create: impl FnOnce() -> Result<Handle<DescriptorSetLayout<B>>, OutOfMemory>,
+    ) -> Result<Handle<DescriptorSetLayout<B>>, OutOfMemory>
+    where
+        R: Deref<Target = Self>,
+        W: DerefMut<Target = Self>,
+        U: FnOnce(R) -> W,
+    {
+        let key = LayoutKey::new(bindings);
+        if let Some(layout) = read.layouts.get(&key) {
+            return Ok(layout.clone());
+        }
+        let layout = create()?;
+        {
+            upgrade(read).layouts.insert(key, layout.clone());
+        }
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn binding(binding: u32, ty: DescriptorType, count: usize) -> DescriptorSetLayoutBinding {
+        DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            immutable_samplers: false,
+        }
+    }
+
+    #[test]
+    fn test_layout_key() {
+        let sampler = binding(0, DescriptorType::Sampler, 1);
+        let attachment = binding(1, DescriptorType::InputAttachment, 1);
+        let key = LayoutKey::new(&[sampler.clone(), attachment.clone()]);
+
+        // Order of bindings doesn't matter.
+        assert_eq!(key, LayoutKey::new(&[attachment.clone(), sampler.clone()]));
+
+        // Every field of a binding is part of the key.
+        assert_ne!(key, LayoutKey::new(&[sampler.clone()]));
+        assert_ne!(
+            key,
+            LayoutKey::new(&[
+                sampler.clone(),
+                binding(2, DescriptorType::InputAttachment, 1)
+            ])
+        );
+        assert_ne!(
+            key,
+            LayoutKey::new(&[sampler.clone(), binding(1, DescriptorType::Sampler, 1)])
+        );
+        assert_ne!(
+            key,
+            LayoutKey::new(&[
+                sampler.clone(),
+                binding(1, DescriptorType::InputAttachment, 2)
+            ])
+        );
+        assert_ne!(
+            key,
+            LayoutKey::new(&[
+                sampler.clone(),
+                DescriptorSetLayoutBinding {
+                    stage_flags: ShaderStageFlags::VERTEX,
+                    ..attachment.clone()
+                },
+            ])
+        );
+        assert_ne!(
+            key,
+            LayoutKey::new(&[
+                sampler.clone(),
+                DescriptorSetLayoutBinding {
+                    immutable_samplers: true,
+                    ..attachment.clone()
+                },
+            ])
+        );
+    }
+}
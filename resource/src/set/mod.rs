@@ -1,3 +1,8 @@
+//! Descriptor set and descriptor set layout wrappers.
+
+mod cache;
+mod write;
+
 use {
     crate::{
         core::{device_owned, Device, DeviceId},
@@ -9,6 +14,11 @@ use {
     smallvec::SmallVec,
 };
 
+pub use crate::set::{
+    cache::DescriptorSetLayoutCache,
+    write::{DescriptorSetWriter, SampledImage},
+};
+
 /// Descriptor set layout info.
 #[derive(Clone, Debug)]
 pub struct DescriptorSetInfo {
@@ -83,6 +93,7 @@ pub struct DescriptorSet<B: Backend> {
     device: DeviceId,
     set: descriptor::DescriptorSet<B>,
     layout: Handle<DescriptorSetLayout<B>>,
+    bound: Vec<write::BoundDescriptor<B>>,
     relevant: Relevant,
 }
 
@@ -107,6 +118,7 @@ where
             device: device.id(),
             set: sets.swap_remove(0),
             layout: layout.clone(),
+            bound: Vec::new(),
             relevant: Relevant,
         })
     }
@@ -135,6 +147,7 @@ where
             device: device.id(),
             set,
             layout: layout.clone(),
+            bound: Vec::new(),
             relevant: Relevant,
         }));
 
@@ -161,4 +174,10 @@ where
     pub fn layout(&mut self) -> &Handle<DescriptorSetLayout<B>> {
         &self.layout
     }
+
+    /// Start writing descriptors into the set.
+    /// Written resources are kept alive as long as they are bound to the set.
+    pub fn write(&mut self) -> DescriptorSetWriter<'_, B> {
+        DescriptorSetWriter::new(self)
+    }
 }
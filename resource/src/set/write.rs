@@ -0,0 +1,289 @@
+//! Typed descriptor writes that keep written resources alive.
+
+use {
+    super::DescriptorSet,
+    crate::{buffer::Buffer, core::Device, escape::Handle, image::ImageView, sampler::Sampler},
+    rendy_core::hal::{
+        buffer,
+        device::Device as _,
+        image,
+        pso::{
+            BufferDescriptorFormat, BufferDescriptorType, Descriptor, DescriptorSetLayoutBinding,
+            DescriptorSetWrite, DescriptorType, ImageDescriptorType,
+        },
+        Backend,
+    },
+    std::ops::Range,
+};
+
+/// Image view with sampler that can be written into image descriptors.
+/// Implemented by textures.
+pub trait SampledImage<B: Backend> {
+    /// Get image view handle.
+    fn image_view(&self) -> &Handle<ImageView<B>>;
+
+    /// Get sampler handle.
+    fn sampler(&self) -> &Handle<Sampler<B>>;
+}
+
+/// Resource written into descriptor.
+#[derive(Debug)]
+enum DescriptorResource<B: Backend> {
+    Buffer(Handle<Buffer<B>>, Range<Option<u64>>),
+    Image(Handle<ImageView<B>>, image::Layout),
+    Sampler(Handle<Sampler<B>>),
+    CombinedImageSampler(Handle<ImageView<B>>, image::Layout, Handle<Sampler<B>>),
+}
+
+impl<B> DescriptorResource<B>
+where
+    B: Backend,
+{
+    fn raw(&self) -> Descriptor<'_, B> {
+        match self {
+            DescriptorResource::Buffer(buffer, range) => {
+                Descriptor::Buffer(buffer.raw(), range.clone())
+            }
+            DescriptorResource::Image(view, layout) => Descriptor::Image(view.raw(), *layout),
+            DescriptorResource::Sampler(sampler) => Descriptor::Sampler(sampler.raw()),
+            DescriptorResource::CombinedImageSampler(view, layout, sampler) => {
+                Descriptor::CombinedImageSampler(view.raw(), *layout, sampler.raw())
+            }
+        }
+    }
+}
+
+/// Resource written into descriptor at binding and array index.
+#[derive(Debug)]
+pub(crate) struct BoundDescriptor<B: Backend> {
+    binding: u32,
+    array_index: usize,
+    resource: DescriptorResource<B>,
+}
+
+/// Writer of descriptors into `DescriptorSet`.
+/// Created with `DescriptorSet::write`.
+///
+/// Each write is validated against binding of the set layout.
+/// Written resources are kept alive by the set until overwritten or the set is destroyed.
+#[derive(Debug)]
+pub struct DescriptorSetWriter<'a, B: Backend> {
+    set: &'a mut DescriptorSet<B>,
+    writes: Vec<BoundDescriptor<B>>,
+}
+
+impl<'a, B> DescriptorSetWriter<'a, B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(set: &'a mut DescriptorSet<B>) -> Self {
+        DescriptorSetWriter {
+            set,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Find layout binding and check array index.
+    fn layout_binding(&self, binding: u32, array_index: usize) -> &DescriptorSetLayoutBinding {
+        let layout_binding = self
+            .set
+            .layout
+            .info()
+            .bindings
+            .iter()
+            .find(|layout_binding| layout_binding.binding == binding)
+            .unwrap_or_else(|| panic!("Binding {} is not in the set layout", binding));
+
+        assert!(
+            array_index < layout_binding.count,
+            "Array index {} is out of bounds of binding {} with {} descriptors",
+            array_index,
+            binding,
+            layout_binding.count
+        );
+        layout_binding
+    }
+
+    fn push(mut self, binding: u32, array_index: usize, resource: DescriptorResource<B>) -> Self {
+        self.writes.push(BoundDescriptor {
+            binding,
+            array_index,
+            resource,
+        });
+        self
+    }
+
+    /// Write buffer range into uniform or storage buffer descriptor.
+    /// `None` bounds of the `range` mean start and end of the buffer.
+    pub fn buffer(
+        self,
+        binding: u32,
+        array_index: usize,
+        buffer: Handle<Buffer<B>>,
+        range: Range<Option<u64>>,
+    ) -> Self {
+        let ty = self.layout_binding(binding, array_index).ty;
+        let usage = match ty {
+            DescriptorType::Buffer {
+                ty: BufferDescriptorType::Uniform,
+                format: BufferDescriptorFormat::Structured { .. },
+            } => buffer::Usage::UNIFORM,
+            DescriptorType::Buffer {
+                ty: BufferDescriptorType::Storage { .. },
+                format: BufferDescriptorFormat::Structured { .. },
+            } => buffer::Usage::STORAGE,
+            ty => panic!("Binding {} of type {:?} can't hold buffer", binding, ty),
+        };
+
+        assert!(
+            buffer.info().usage.contains(usage),
+            "Buffer written into binding {} must have {:?} usage",
+            binding,
+            usage
+        );
+        let end = range.end.unwrap_or(buffer.size());
+        assert!(
+            range.start.unwrap_or(0) <= end && end <= buffer.size(),
+            "Range {:?} is out of bounds of the buffer",
+            range
+        );
+
+        self.push(
+            binding,
+            array_index,
+            DescriptorResource::Buffer(buffer, range),
+        )
+    }
+
+    /// Write image view into sampled image, storage image or input attachment descriptor.
+    pub fn image_view(
+        self,
+        binding: u32,
+        array_index: usize,
+        view: Handle<ImageView<B>>,
+        layout: image::Layout,
+    ) -> Self {
+        let ty = self.layout_binding(binding, array_index).ty;
+        let usage = match ty {
+            DescriptorType::Image {
+                ty:
+                    ImageDescriptorType::Sampled {
+                        with_sampler: false,
+                    },
+            } => image::Usage::SAMPLED,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Storage { .. },
+            } => image::Usage::STORAGE,
+            DescriptorType::InputAttachment => image::Usage::INPUT_ATTACHMENT,
+            ty => panic!("Binding {} of type {:?} can't hold image view", binding, ty),
+        };
+
+        assert!(
+            view.image().info().usage.contains(usage),
+            "Image written into binding {} must have {:?} usage",
+            binding,
+            usage
+        );
+
+        self.push(
+            binding,
+            array_index,
+            DescriptorResource::Image(view, layout),
+        )
+    }
+
+    /// Write sampler into sampler descriptor.
+    pub fn sampler(self, binding: u32, array_index: usize, sampler: Handle<Sampler<B>>) -> Self {
+        let ty = self.layout_binding(binding, array_index).ty;
+        match ty {
+            DescriptorType::Sampler => {}
+            ty => panic!("Binding {} of type {:?} can't hold sampler", binding, ty),
+        }
+
+        self.push(binding, array_index, DescriptorResource::Sampler(sampler))
+    }
+
+    /// Write image view with sampler into combined image sampler descriptor.
+    pub fn combined_image_sampler(
+        self,
+        binding: u32,
+        array_index: usize,
+        view: Handle<ImageView<B>>,
+        layout: image::Layout,
+        sampler: Handle<Sampler<B>>,
+    ) -> Self {
+        let ty = self.layout_binding(binding, array_index).ty;
+        match ty {
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            } => {}
+            ty => panic!(
+                "Binding {} of type {:?} can't hold combined image sampler",
+                binding, ty
+            ),
+        }
+
+        assert!(
+            view.image().info().usage.contains(image::Usage::SAMPLED),
+            "Image written into binding {} must have {:?} usage",
+            binding,
+            image::Usage::SAMPLED
+        );
+
+        self.push(
+            binding,
+            array_index,
+            DescriptorResource::CombinedImageSampler(view, layout, sampler),
+        )
+    }
+
+    /// Write texture into combined image sampler or sampled image descriptor.
+    /// Sampler of the texture is not written in the latter case.
+    /// `layout` must be the layout the texture was transitioned to.
+    pub fn texture(
+        self,
+        binding: u32,
+        array_index: usize,
+        texture: &impl SampledImage<B>,
+        layout: image::Layout,
+    ) -> Self {
+        let ty = self.layout_binding(binding, array_index).ty;
+        match ty {
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            } => self.combined_image_sampler(
+                binding,
+                array_index,
+                texture.image_view().clone(),
+                layout,
+                texture.sampler().clone(),
+            ),
+            _ => self.image_view(binding, array_index, texture.image_view().clone(), layout),
+        }
+    }
+
+    /// Write descriptors into the set.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the one the set was allocated from.
+    /// The set must not be used by pending command buffers.
+    pub unsafe fn submit(self, device: &Device<B>) {
+        let DescriptorSetWriter { set, writes } = self;
+        set.assert_device_owner(device);
+
+        device.write_descriptor_sets(writes.iter().map(|write| DescriptorSetWrite {
+            set: set.raw(),
+            binding: write.binding,
+            array_offset: write.array_index,
+            descriptors: Some(write.resource.raw()),
+        }));
+
+        for write in writes {
+            set.bound.retain(|bound| {
+                bound.binding != write.binding || bound.array_index != write.array_index
+            });
+            set.bound.push(write);
+        }
+    }
+}
@@ -6,8 +6,8 @@ use {
         memory::Data,
         pixel::AsPixel,
         resource::{
-            Escape, Handle, Image, ImageCreationError, ImageInfo, ImageView,
-            ImageViewCreationError, ImageViewInfo, Sampler,
+            Handle, Image, ImageCreationError, ImageInfo, ImageView, ImageViewCreationError,
+            ImageViewInfo, SampledImage, Sampler,
         },
     },
     rendy_core::hal::{
@@ -23,7 +23,7 @@ use {
 #[derive(Debug)]
 pub struct Texture<B: Backend> {
     image: Handle<Image<B>>,
-    view: Escape<ImageView<B>>,
+    view: Handle<ImageView<B>>,
     sampler: Handle<Sampler<B>>,
     premultiplied: bool,
 }
@@ -47,9 +47,22 @@ where
         &self.view
     }
 
+    /// Get image view handle.
+    pub fn view_handle(&self) -> &Handle<ImageView<B>> {
+        &self.view
+    }
+
     /// Get mutable reference to image view.
+    ///
+    /// # Panics
+    ///
+    /// Panics if image view handle was shared, e.g. written to a descriptor set.
+    #[deprecated(
+        since = "0.5.2",
+        note = "Image view may be shared with descriptor sets. Use `view` or `view_handle` instead."
+    )]
     pub fn view_mut(&mut self) -> &mut ImageView<B> {
-        &mut self.view
+        Handle::get_mut(&mut self.view).expect("Image view handle is shared")
     }
 
     /// Get whether texture has premultiplied alpha
@@ -58,6 +71,19 @@ where
     }
 }
 
+impl<B> SampledImage<B> for Texture<B>
+where
+    B: Backend,
+{
+    fn image_view(&self) -> &Handle<ImageView<B>> {
+        &self.view
+    }
+
+    fn sampler(&self) -> &Handle<Sampler<B>> {
+        &self.sampler
+    }
+}
+
 /// Number of mip levels
 #[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -473,6 +499,7 @@ impl<'a> TextureBuilder<'a> {
                     },
                 )
                 .map_err(BuildError::ImageView)?
+                .into()
         };
 
         let sampler = factory